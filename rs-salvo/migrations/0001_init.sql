@@ -25,7 +25,7 @@ CREATE TABLE IF NOT EXISTS weibo_hot_timeline_pic
  pic_id TEXT NOT NULL,
  pic_url TEXT NOT NULL);
 
--- 创建表格weibo_hot_timeline_pic
+-- 创建表格weibo_hot_timeline_comm
 CREATE TABLE IF NOT EXISTS weibo_hot_timeline_comm
 (id INTEGER PRIMARY KEY AUTOINCREMENT,
  mid TEXT NOT NULL,
@@ -0,0 +1,10 @@
+-- 创建表格weibo_hot_search_snap，每次爬取热搜时记录排名与热度
+CREATE TABLE IF NOT EXISTS weibo_hot_search_snap
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ title TEXT NOT NULL,
+ rank INTEGER NOT NULL,
+ number INTEGER NOT NULL,
+ special TEXT NOT NULL DEFAULT '',
+ occur_era TEXT NOT NULL CHECK (occur_era GLOB '????-??-??'),
+ snap_era TEXT NOT NULL CHECK (snap_era GLOB '????-??-?? ??:??:??'));
+CREATE INDEX IF NOT EXISTS weibo_hot_search_snap_title_era ON weibo_hot_search_snap (title, snap_era);
@@ -0,0 +1,20 @@
+-- 创建表格weibo_sched，记录定时爬取任务的配置与运行情况
+CREATE TABLE IF NOT EXISTS weibo_sched
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ job TEXT NOT NULL,
+ enabled BOOLEAN NOT NULL DEFAULT 0,
+ interval_secs INTEGER NOT NULL DEFAULT 0,
+ cron TEXT NOT NULL DEFAULT '',
+ jitter_secs INTEGER NOT NULL DEFAULT 0,
+ pic BOOLEAN NOT NULL DEFAULT 0,
+ comm BOOLEAN NOT NULL DEFAULT 0,
+ last_era TEXT NOT NULL DEFAULT '',
+ next_era TEXT NOT NULL DEFAULT '',
+ last_sta TEXT NOT NULL DEFAULT '',
+ last_flaw TEXT NOT NULL DEFAULT '',
+ UNIQUE (job));
+
+INSERT OR IGNORE INTO weibo_sched (job, interval_secs, jitter_secs)
+VALUES ('hot_search', 600, 30),
+       ('hot_timeline', 1800, 60),
+       ('hot_timeline_comm', 3600, 60);
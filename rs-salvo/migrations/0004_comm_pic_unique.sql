@@ -0,0 +1,14 @@
+-- weibo_hot_timeline_comm增加点赞数与回复数
+ALTER TABLE weibo_hot_timeline_comm ADD COLUMN like_cnt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline_comm ADD COLUMN reply_cnt INTEGER NOT NULL DEFAULT 0;
+
+-- 清理重复的评论与图片，相同的键只保留最新的一条，之后建立唯一索引
+DELETE FROM weibo_hot_timeline_comm WHERE id NOT IN
+  (SELECT max(id) FROM weibo_hot_timeline_comm GROUP BY mid, comm_mid);
+CREATE UNIQUE INDEX IF NOT EXISTS weibo_hot_timeline_comm_mid_comm_mid
+  ON weibo_hot_timeline_comm (mid, comm_mid);
+
+DELETE FROM weibo_hot_timeline_pic WHERE id NOT IN
+  (SELECT max(id) FROM weibo_hot_timeline_pic GROUP BY mid, pic_id);
+CREATE UNIQUE INDEX IF NOT EXISTS weibo_hot_timeline_pic_mid_pic_id
+  ON weibo_hot_timeline_pic (mid, pic_id);
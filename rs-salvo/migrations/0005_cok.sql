@@ -0,0 +1,13 @@
+-- 创建表格weibo_cok，请求微博时轮换使用的cookie池
+CREATE TABLE IF NOT EXISTS weibo_cok
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ cok TEXT NOT NULL,
+ label TEXT NOT NULL DEFAULT '',
+ sta TEXT NOT NULL DEFAULT 'active' CHECK (sta IN ('active', 'quarantined', 'retired')),
+ use_cnt INTEGER NOT NULL DEFAULT 0,
+ flaw_cnt INTEGER NOT NULL DEFAULT 0,
+ last_flaw TEXT NOT NULL DEFAULT '',
+ used_era TEXT NOT NULL DEFAULT '',
+ until_era TEXT NOT NULL DEFAULT '',
+ add_era TEXT NOT NULL CHECK (add_era GLOB '????-??-?? ??:??:??'),
+ UNIQUE (cok));
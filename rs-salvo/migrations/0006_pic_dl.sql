@@ -0,0 +1,14 @@
+-- 记录每张热门推荐图片的下载结果，失败的图片可以稍后重试
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_sta TEXT NOT NULL DEFAULT 'pending'
+  CHECK (dl_sta IN ('pending', 'done', 'failed'));
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_size INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_pth TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_flaw TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_cnt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN dl_era TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_pic_dl_sta ON weibo_hot_timeline_pic (dl_sta);
+
+-- 重试下载失败的图片，默认不启用
+INSERT OR IGNORE INTO weibo_sched (job, interval_secs, jitter_secs)
+VALUES ('hot_timeline_pic', 3600, 60);
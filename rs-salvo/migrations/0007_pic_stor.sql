@@ -0,0 +1,7 @@
+-- 图片按内容的SHA-256存储，记录真实格式与尺寸，相同内容的图片共用同一个文件
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN pic_hash TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN pic_fmt TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN pic_width INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN pic_height INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_pic_hash ON weibo_hot_timeline_pic (pic_hash);
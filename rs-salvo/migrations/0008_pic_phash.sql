@@ -0,0 +1,2 @@
+-- 图片的感知哈希（dHash，16位十六进制），用于查找经过轻微修改后重新上传的相似图片
+ALTER TABLE weibo_hot_timeline_pic ADD COLUMN pic_phash TEXT NOT NULL DEFAULT '';
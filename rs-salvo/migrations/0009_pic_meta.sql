@@ -0,0 +1,19 @@
+-- 图片中的EXIF/XMP元数据，按内容的哈希与weibo_hot_timeline_pic关联，相同内容的图片共用一条
+CREATE TABLE IF NOT EXISTS weibo_hot_timeline_pic_meta
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ pic_hash TEXT NOT NULL UNIQUE,
+ cam_make TEXT NOT NULL DEFAULT '',
+ cam_model TEXT NOT NULL DEFAULT '',
+ lens_model TEXT NOT NULL DEFAULT '',
+ software TEXT NOT NULL DEFAULT '',
+ shot_era TEXT NOT NULL DEFAULT '',
+ modify_era TEXT NOT NULL DEFAULT '',
+ gps_flag INTEGER NOT NULL DEFAULT 0,
+ gps_lat REAL,
+ gps_lon REAL,
+ exif_tags TEXT NOT NULL DEFAULT '{}',
+ xmp TEXT NOT NULL DEFAULT '',
+ meta_era TEXT NOT NULL DEFAULT '');
+
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_pic_meta_gps_flag
+  ON weibo_hot_timeline_pic_meta (gps_flag);
@@ -0,0 +1,21 @@
+-- 热门推荐记录转发、评论、点赞数，来源、IP属地、是否长微博，以及发布者的认证与粉丝数
+ALTER TABLE weibo_hot_timeline ADD COLUMN repost_cnt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline ADD COLUMN comm_cnt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline ADD COLUMN like_cnt INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline ADD COLUMN source TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline ADD COLUMN region_name TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline ADD COLUMN long_text INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline ADD COLUMN mem_verified INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE weibo_hot_timeline ADD COLUMN mem_verified_ilk INTEGER NOT NULL DEFAULT -1;
+ALTER TABLE weibo_hot_timeline ADD COLUMN mem_follower_cnt INTEGER NOT NULL DEFAULT 0;
+
+-- 创建表格weibo_hot_timeline_snap，每次爬取热门推荐时记录互动数据，用于还原互动数随时间的增长
+CREATE TABLE IF NOT EXISTS weibo_hot_timeline_snap
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ mid TEXT NOT NULL,
+ repost_cnt INTEGER NOT NULL DEFAULT 0,
+ comm_cnt INTEGER NOT NULL DEFAULT 0,
+ like_cnt INTEGER NOT NULL DEFAULT 0,
+ mem_follower_cnt INTEGER NOT NULL DEFAULT 0,
+ snap_era TEXT NOT NULL CHECK (snap_era GLOB '????-??-?? ??:??:??'));
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_snap_mid_era ON weibo_hot_timeline_snap (mid, snap_era);
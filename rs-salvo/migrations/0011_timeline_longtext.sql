@@ -0,0 +1,2 @@
+-- 长微博通过statuses/longtext获取全文后，text_expanded为1；获取失败时text仍为截断的内容
+ALTER TABLE weibo_hot_timeline ADD COLUMN text_expanded INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- 记录发布的完整时间（UTC）与原始时区偏移，以及最近一次爬取的时间；created_at无法解析时UTC时间为空
+ALTER TABLE weibo_hot_timeline ADD COLUMN occur_utc TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline ADD COLUMN occur_offset TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline ADD COLUMN crawl_era TEXT NOT NULL DEFAULT '';
+
+ALTER TABLE weibo_hot_timeline_comm ADD COLUMN comm_utc TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_comm ADD COLUMN comm_offset TEXT NOT NULL DEFAULT '';
+ALTER TABLE weibo_hot_timeline_comm ADD COLUMN crawl_era TEXT NOT NULL DEFAULT '';
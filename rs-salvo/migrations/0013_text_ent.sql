@@ -0,0 +1,20 @@
+-- 创建表格weibo_hot_timeline_ent，记录热门推荐正文中的话题、提及与链接
+CREATE TABLE IF NOT EXISTS weibo_hot_timeline_ent
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ mid TEXT NOT NULL REFERENCES weibo_hot_timeline (mid) ON DELETE CASCADE,
+ ent_ilk TEXT NOT NULL CHECK (ent_ilk IN ('topic', 'mention', 'url')),
+ ent TEXT NOT NULL,
+ UNIQUE (mid, ent_ilk, ent));
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_ent_ilk_ent ON weibo_hot_timeline_ent (ent_ilk, ent);
+
+-- 创建表格weibo_hot_timeline_comm_ent，记录热门推荐评论中的话题、提及与链接
+CREATE TABLE IF NOT EXISTS weibo_hot_timeline_comm_ent
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ mid TEXT NOT NULL,
+ comm_mid TEXT NOT NULL,
+ ent_ilk TEXT NOT NULL CHECK (ent_ilk IN ('topic', 'mention', 'url')),
+ ent TEXT NOT NULL,
+ FOREIGN KEY (mid, comm_mid) REFERENCES weibo_hot_timeline_comm (mid, comm_mid) ON DELETE CASCADE,
+ UNIQUE (mid, comm_mid, ent_ilk, ent));
+CREATE INDEX IF NOT EXISTS weibo_hot_timeline_comm_ent_ilk_ent
+  ON weibo_hot_timeline_comm_ent (ent_ilk, ent);
@@ -0,0 +1,23 @@
+-- 创建表格weibo_keyword，关键词监控的关键词，同一关键词可以属于多个列表
+CREATE TABLE IF NOT EXISTS weibo_keyword
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ keyword_list TEXT NOT NULL DEFAULT 'default',
+ keyword TEXT NOT NULL CHECK (keyword != ''),
+ add_era TEXT NOT NULL CHECK (add_era GLOB '????-??-?? ??:??:??'),
+ UNIQUE (keyword_list, keyword));
+CREATE INDEX IF NOT EXISTS weibo_keyword_keyword ON weibo_keyword (keyword);
+
+-- 创建表格weibo_keyword_hit，记录热搜标题、热门推荐与评论中命中的关键词。
+-- hit_start与hit_finish为命中位置在原文中的字节偏移，左闭右开；同一位置的命中只记录第一次
+CREATE TABLE IF NOT EXISTS weibo_keyword_hit
+(id INTEGER PRIMARY KEY AUTOINCREMENT,
+ keyword TEXT NOT NULL,
+ hit_ilk TEXT NOT NULL CHECK (hit_ilk IN ('hot_search', 'timeline', 'comm')),
+ hit_key TEXT NOT NULL,
+ mid TEXT NOT NULL DEFAULT '',
+ hit_start INTEGER NOT NULL,
+ hit_finish INTEGER NOT NULL,
+ hit_era TEXT NOT NULL CHECK (hit_era GLOB '????-??-?? ??:??:??'),
+ UNIQUE (keyword, hit_ilk, hit_key, mid, hit_start));
+CREATE INDEX IF NOT EXISTS weibo_keyword_hit_era ON weibo_keyword_hit (hit_era);
+CREATE INDEX IF NOT EXISTS weibo_keyword_hit_mid ON weibo_keyword_hit (mid);
@@ -0,0 +1,23 @@
+-- 表格weibo_keyword_hit增加命中位置的字符偏移，左闭右开
+ALTER TABLE weibo_keyword_hit ADD COLUMN hit_char_start INTEGER NOT NULL DEFAULT -1;
+ALTER TABLE weibo_keyword_hit ADD COLUMN hit_char_finish INTEGER NOT NULL DEFAULT -1;
+
+-- 由字节偏移换算已有命中的字符偏移：按字节截取原文的前缀，再按字符计数。
+-- 原文已被删除的命中保留为-1
+UPDATE weibo_keyword_hit SET
+  hit_char_start = coalesce(length(cast(substr(cast(
+    CASE hit_ilk
+      WHEN 'hot_search' THEN hit_key
+      WHEN 'timeline' THEN (SELECT t.text FROM weibo_hot_timeline t
+                            WHERE t.mid = weibo_keyword_hit.mid)
+      ELSE (SELECT c.text FROM weibo_hot_timeline_comm c
+            WHERE c.mid = weibo_keyword_hit.mid AND c.comm_mid = weibo_keyword_hit.hit_key)
+    END AS BLOB), 1, hit_start) AS TEXT)), -1),
+  hit_char_finish = coalesce(length(cast(substr(cast(
+    CASE hit_ilk
+      WHEN 'hot_search' THEN hit_key
+      WHEN 'timeline' THEN (SELECT t.text FROM weibo_hot_timeline t
+                            WHERE t.mid = weibo_keyword_hit.mid)
+      ELSE (SELECT c.text FROM weibo_hot_timeline_comm c
+            WHERE c.mid = weibo_keyword_hit.mid AND c.comm_mid = weibo_keyword_hit.hit_key)
+    END AS BLOB), 1, hit_finish) AS TEXT)), -1);
@@ -2,6 +2,7 @@ use log::info;
 use salvo_core::http::{Request, ResBody, Response, StatusCode};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
 
+#[derive(Default)]
 pub struct LogLogger;
 
 impl LogLogger {
@@ -25,7 +26,7 @@ impl Handler for LogLogger {
       _ => StatusCode::OK,
     }).to_string();
     if let ResBody::Error(flaw) = &resp.body {
-      info!("{} {} {} {}", req_met, req_uri, resp_sta, flaw.to_string());
+      info!("{} {} {} {}", req_met, req_uri, resp_sta, flaw);
     } else {
       info!("{} {} {}", req_met, req_uri, resp_sta);
     }
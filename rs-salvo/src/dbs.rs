@@ -1,11 +1,82 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use jzon::object;
 use jzon::JsonValue;
 use rbatis::RBatis;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
+use crate::ents::WeiboEnt;
+use crate::ents::WeiboEntIlk;
 use crate::exceptions::WeiboError;
+use crate::meta::WeiboPicMeta;
+use crate::pics::WeiboPicStor;
+use crate::prefs::prefs;
+use crate::watch::WeiboHitIlk;
+use crate::wm::WmSite;
+
+/// 时间范围筛选条件，上下限均包含在内，格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS（UTC）
+#[derive(Clone, Debug, Default)]
+pub struct WeiboEraSpan {
+  // 时间的下限，可选
+  pub from_era: Option<String>,
+  // 时间的上限，只有日期时包含当天，可选
+  pub to_era: Option<String>,
+}
+
+impl WeiboEraSpan {
+  /// 将时间范围加入select_by_map的查询条件
+  ///
+  /// ## 参数
+  /// - `era_qry`: select_by_map的查询条件
+  /// - `era_col`: 比较的列或表达式，只能是常量，不能来自请求
+  fn era_span_qry(&self, era_qry: &mut rbs::Value, era_col: &str) {
+    if let Some(from_era) = &self.from_era {
+      era_qry.insert(rbs::value!(format!("{era_col} >= ")), rbs::value!(from_era.clone()));
+    }
+    if let Some(to_era) = &self.to_era {
+      era_qry.insert(rbs::value!(format!("{era_col} <= ")), rbs::value!(era_to_bound(to_era)));
+    }
+  }
+
+  /// 将时间范围加入SQL语句的where条件
+  ///
+  /// ## 参数
+  /// - `era_sent`: SQL语句，已包含where
+  /// - `era_pars`: SQL语句的参数
+  /// - `era_col`: 比较的列或表达式，只能是常量，不能来自请求
+  fn era_span_sent(&self, era_sent: &mut String, era_pars: &mut Vec<rbs::Value>, era_col: &str) {
+    if let Some(from_era) = &self.from_era {
+      era_sent.push_str(&format!(" and {era_col} >= ?"));
+      era_pars.push(rbs::value!(from_era.clone()));
+    }
+    if let Some(to_era) = &self.to_era {
+      era_sent.push_str(&format!(" and {era_col} <= ?"));
+      era_pars.push(rbs::value!(era_to_bound(to_era)));
+    }
+  }
+
+  /// 只保留时间范围的日期部分，用于只记录日期的列
+  fn era_span_date(&self) -> Self {
+    Self {
+      from_era: self.from_era.as_ref().map(|from_era| from_era.chars().take(10).collect()),
+      to_era: self.to_era.as_ref().map(|to_era| to_era.chars().take(10).collect()),
+    }
+  }
+}
+
+/// 时间的上限只有日期时，补全为当天的最后一秒，使上限包含当天全部的数据
+///
+/// ## 参数
+/// - `to_era`: 时间的上限，格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS
+fn era_to_bound(to_era: &str) -> String {
+  if to_era.len() == "YYYY-MM-DD".len() {
+    format!("{to_era} 23:59:59")
+  } else {
+    to_era.to_string()
+  }
+}
 
 /// 微博热搜
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -60,11 +131,13 @@ impl WeiboHotSearch {
   /// - `weibo_db_rb_conn`：rbatis数据库连接
   /// - `weibo_title`: 热搜标题，可选
   /// - `occur_era`: 热搜出现的年月日，格式YYYY-MM-DD，可选
+  /// - `occur_span`: 热搜出现的时间范围，热搜只记录日期，因此只比较日期部分
   ///
   /// ## 返回
   /// 成功则返回符合查询条件的微博热搜数据
   pub async fn weibo_hot_search_r(weibo_db_rb_conn: &RBatis, weibo_title: Option<String>,
-                                  occur_era: Option<String>) -> Result<Vec<Self>, WeiboError> {
+                                  occur_era: Option<String>, occur_span: &WeiboEraSpan)
+                                  -> Result<Vec<Self>, WeiboError> {
     let mut weibo_hot_search_r_qry = rbs::value! {};
     if let Some(weibo_title) = weibo_title {
       weibo_hot_search_r_qry.insert(rbs::value!("title"), rbs::value!(weibo_title));
@@ -72,6 +145,7 @@ impl WeiboHotSearch {
     if let Some(occur_era) = occur_era {
       weibo_hot_search_r_qry.insert(rbs::value!("occur_era"), rbs::value!(occur_era));
     }
+    occur_span.era_span_date().era_span_qry(&mut weibo_hot_search_r_qry, "occur_era");
 
     Self::select_by_map(weibo_db_rb_conn, weibo_hot_search_r_qry).await.map_err(|flaw| {
       WeiboError::RbatisError(flaw.to_string())
@@ -134,6 +208,116 @@ impl WeiboHotSearch {
   }
 }
 
+/// 微博热搜快照，每次爬取热搜时记录一次，用于还原热搜排名与热度随时间的变化
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotSearchSnap {
+  pub id: Option<usize>,
+  // 热搜标题
+  pub title: String,
+  // 热搜排名，从1开始
+  pub rank: u32,
+  // 热搜热度
+  pub number: u32,
+  // 热搜是否为特殊热搜，比如“热”、“新”
+  pub special: String,
+  // 爬取的日期，格式为YYYY-MM-DD
+  pub occur_era: String,
+  // 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub snap_era: String,
+}
+rbatis::crud!(WeiboHotSearchSnap {}, "weibo_hot_search_snap");
+
+impl From<WeiboHotSearchSnap> for JsonValue {
+  fn from(weibo_hot_search_snap: WeiboHotSearchSnap) -> Self {
+    object! {
+      id: weibo_hot_search_snap.id,
+      title: weibo_hot_search_snap.title,
+      rank: weibo_hot_search_snap.rank,
+      number: weibo_hot_search_snap.number,
+      special: weibo_hot_search_snap.special,
+      occur_era: weibo_hot_search_snap.occur_era,
+      snap_era: weibo_hot_search_snap.snap_era
+    }
+  }
+}
+
+impl WeiboHotSearchSnap {
+  /// 创建一个微博热搜快照WeiboHotSearchSnap对象
+  ///
+  /// ## 参数
+  /// - `realtime_title`: 热搜标题
+  /// - `realtime_rank`: 热搜排名，从1开始
+  /// - `realtime_number`: 热搜热度
+  /// - `realtime_special`: 热搜是否为特殊热搜，比如“热”、“新”
+  /// - `occur_era`: 爬取的日期，格式为YYYY-MM-DD
+  /// - `snap_era`: 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub fn weibo_hot_search_snap_c(
+    realtime_title: String, realtime_rank: u32, realtime_number: u32, realtime_special: String,
+    occur_era: String, snap_era: String,
+  ) -> Self {
+    Self {
+      id: None,
+      title: realtime_title,
+      rank: realtime_rank,
+      number: realtime_number,
+      special: realtime_special,
+      occur_era,
+      snap_era,
+    }
+  }
+
+  /// 获取微博热搜快照WeiboHotSearchSnap对象，按爬取时间升序排列
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `weibo_title`: 热搜标题
+  /// - `snap_from_era`: 爬取时间的下限（含），格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS，可选
+  /// - `snap_to_era`: 爬取时间的上限（含），格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS，可选
+  ///
+  /// ## 返回
+  /// 成功则返回该热搜的排名与热度曲线
+  pub async fn weibo_hot_search_snap_r(
+    weibo_db_rb_conn: &RBatis, weibo_title: String, snap_from_era: Option<String>,
+    snap_to_era: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_hot_search_snap_sent =
+      "select * from weibo_hot_search_snap where title = ?".to_string();
+    let mut weibo_hot_search_snap_pars = vec![rbs::value!(weibo_title)];
+    if let Some(snap_from_era) = snap_from_era {
+      weibo_hot_search_snap_sent.push_str(" and snap_era >= ?");
+      weibo_hot_search_snap_pars.push(rbs::value!(snap_from_era));
+    }
+    if let Some(snap_to_era) = snap_to_era {
+      // 只有日期时，包含当天全部的快照
+      weibo_hot_search_snap_sent.push_str(" and snap_era <= ?");
+      weibo_hot_search_snap_pars.push(rbs::value!(era_to_bound(&snap_to_era)));
+    }
+    weibo_hot_search_snap_sent.push_str(" order by snap_era, id");
+
+    weibo_db_rb_conn.query_decode(&weibo_hot_search_snap_sent, weibo_hot_search_snap_pars).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 插入微博热搜快照WeiboHotSearchSnap数据，每次爬取均保留
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `hot_search_snap_arrs`: 新的微博热搜快照数据
+  pub async fn weibo_hot_search_snap_u(
+    weibo_db_rb_conn: &RBatis, hot_search_snap_arrs: Vec<Self>) -> Result<(), WeiboError> {
+    if hot_search_snap_arrs.is_empty() {
+      return Ok(());
+    }
+
+    Self::insert_batch(weibo_db_rb_conn, &hot_search_snap_arrs, 50).await.map(|_| ()).map_err(
+      |flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      }
+    )
+  }
+}
+
 /// 微博热门推荐
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WeiboHotTimeline {
@@ -148,8 +332,37 @@ pub struct WeiboHotTimeline {
   pub mem_id: String,
   // 热门推荐的发布者的名称
   pub mem_name: String,
-  // 热门推荐出现的时间，格式为YYYY-MM-DD
+  // 热门推荐发布的日期，按发布者的时区，格式为YYYY-MM-DD；created_at无法解析时为爬取的日期
   pub occur_era: String,
+  // 热门推荐发布的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）；created_at无法解析时为空
+  pub occur_utc: String,
+  // 热门推荐发布时的时区偏移，格式为±HH:MM；created_at无法解析时为空
+  pub occur_offset: String,
+  // 最近一次爬取到热门推荐的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub crawl_era: String,
+  // 热门推荐的转发数
+  pub repost_cnt: u32,
+  // 热门推荐的评论数
+  pub comm_cnt: u32,
+  // 热门推荐的点赞数
+  pub like_cnt: u32,
+  // 热门推荐的发布来源，比如“iPhone客户端”
+  pub source: String,
+  // 热门推荐的IP属地，去掉了“发布于 ”前缀
+  pub region_name: String,
+  // 热门推荐是否为长微博，未展开时text只包含截断的内容
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub long_text: bool,
+  // 长微博是否已通过statuses/longtext展开，展开后text为全文
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub text_expanded: bool,
+  // 发布者是否为认证用户
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub mem_verified: bool,
+  // 发布者的认证类型，-1为未认证，0为个人认证，其余为机构认证
+  pub mem_verified_ilk: i32,
+  // 发布者的粉丝数
+  pub mem_follower_cnt: u32,
 }
 rbatis::crud!(WeiboHotTimeline {}, "weibo_hot_timeline");
 
@@ -162,7 +375,20 @@ impl From<WeiboHotTimeline> for JsonValue {
       text: weibo_hot_timeline.text,
       mem_id: weibo_hot_timeline.mem_id,
       mem_name: weibo_hot_timeline.mem_name,
-      occur_era: weibo_hot_timeline.occur_era
+      occur_era: weibo_hot_timeline.occur_era,
+      occur_utc: weibo_hot_timeline.occur_utc,
+      occur_offset: weibo_hot_timeline.occur_offset,
+      crawl_era: weibo_hot_timeline.crawl_era,
+      repost_cnt: weibo_hot_timeline.repost_cnt,
+      comm_cnt: weibo_hot_timeline.comm_cnt,
+      like_cnt: weibo_hot_timeline.like_cnt,
+      source: weibo_hot_timeline.source,
+      region_name: weibo_hot_timeline.region_name,
+      long_text: weibo_hot_timeline.long_text,
+      text_expanded: weibo_hot_timeline.text_expanded,
+      mem_verified: weibo_hot_timeline.mem_verified,
+      mem_verified_ilk: weibo_hot_timeline.mem_verified_ilk,
+      mem_follower_cnt: weibo_hot_timeline.mem_follower_cnt
     }
   }
 }
@@ -195,10 +421,17 @@ impl WeiboHotTimeline {
   /// - `timeline_text`: 热门推荐的内容
   /// - `timeline_mem_id`: 热门推荐的发布者的编号
   /// - `timeline_mem_name`: 热门推荐的发布者的名称
-  /// - `timeline_occur_era`: 热门推荐出现的时间，格式为YYYY-MM-DD
+  /// - `timeline_occur_era`: 热门推荐发布的日期，格式为YYYY-MM-DD
+  /// - `timeline_occur_utc`: 热门推荐发布的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  /// - `timeline_occur_offset`: 热门推荐发布时的时区偏移，格式为±HH:MM
+  /// - `timeline_crawl_era`: 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  /// - `timeline_metr`: 热门推荐的互动数与发布者信息
+  #[allow(clippy::too_many_arguments)]
   pub fn weibo_hot_timeline_c(timeline_mid: String, timeline_mblogid: String, timeline_text: String,
                               timeline_mem_id: String, timeline_mem_name: String,
-                              timeline_occur_era: String) -> Self {
+                              timeline_occur_era: String, timeline_occur_utc: String,
+                              timeline_occur_offset: String, timeline_crawl_era: String,
+                              timeline_metr: WeiboHotTimelineMetr) -> Self {
     Self {
       id: None,
       mid: timeline_mid,
@@ -207,9 +440,32 @@ impl WeiboHotTimeline {
       mem_id: timeline_mem_id,
       mem_name: timeline_mem_name,
       occur_era: timeline_occur_era,
+      occur_utc: timeline_occur_utc,
+      occur_offset: timeline_occur_offset,
+      crawl_era: timeline_crawl_era,
+      repost_cnt: timeline_metr.repost_cnt,
+      comm_cnt: timeline_metr.comm_cnt,
+      like_cnt: timeline_metr.like_cnt,
+      source: timeline_metr.source,
+      region_name: timeline_metr.region_name,
+      long_text: timeline_metr.long_text,
+      text_expanded: false,
+      mem_verified: timeline_metr.mem_verified,
+      mem_verified_ilk: timeline_metr.mem_verified_ilk,
+      mem_follower_cnt: timeline_metr.mem_follower_cnt,
     }
   }
 
+  /// 根据热门推荐生成本次爬取的互动数快照
+  ///
+  /// ## 参数
+  /// - `snap_era`: 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub fn weibo_hot_timeline_snap(&self, snap_era: String) -> WeiboHotTimelineSnap {
+    WeiboHotTimelineSnap::weibo_hot_timeline_snap_c(
+      self.mid.clone(), self.repost_cnt, self.comm_cnt, self.like_cnt, self.mem_follower_cnt,
+      snap_era)
+  }
+
   /// 获取微博热门推荐WeiboHotTimeline对象
   ///
   /// ## 参数
@@ -217,15 +473,18 @@ impl WeiboHotTimeline {
   /// - `timeline_mid`: 热门推荐的mid，可选
   /// - `timeline_mem_id`: 热门推荐的发布者的编号，可选
   /// - `timeline_mem_name`: 热门推荐的发布者的名称，可选
-  /// - `timeline_occur_era`: 热门推荐出现的时间，格式YYYY-MM-DD，可选
+  /// - `timeline_occur_era`: 热门推荐发布的日期，格式YYYY-MM-DD，可选
+  /// - `timeline_occur_span`: 热门推荐发布的时间范围，按UTC时间比较，没有UTC时间时按日期比较
   /// - `pic`: 是否查询图片
   /// - `comm`: 是否查询评论
   ///
   /// ## 返回
   /// 成功则返回符合查询条件的微博热门推荐数据
+  #[allow(clippy::too_many_arguments)]
   pub async fn weibo_hot_timeline_r(
     weibo_db_rb_conn: &RBatis, timeline_mid: Option<String>, timeline_mem_id: Option<String>,
-    timeline_mem_name: Option<String>, timeline_occur_era: Option<String>, pic: bool, comm: bool)
+    timeline_mem_name: Option<String>, timeline_occur_era: Option<String>,
+    timeline_occur_span: &WeiboEraSpan, pic: bool, comm: bool)
     -> Result<Vec<WeiboHotTimelinePicComm>, WeiboError> {
     let mut weibo_hot_timeline_r_qry = rbs::value! {};
     if let Some(timeline_mid) = timeline_mid {
@@ -240,6 +499,8 @@ impl WeiboHotTimeline {
     if let Some(timeline_occur_era) = timeline_occur_era {
       weibo_hot_timeline_r_qry.insert(rbs::value!("occur_era"), rbs::value!(timeline_occur_era));
     }
+    timeline_occur_span.era_span_qry(
+      &mut weibo_hot_timeline_r_qry, "coalesce(nullif(occur_utc, ''), occur_era)");
 
     let timeline_arrs = Self::select_by_map(weibo_db_rb_conn, weibo_hot_timeline_r_qry).await.
       map_err(|flaw| {
@@ -254,7 +515,7 @@ impl WeiboHotTimeline {
       let timeline_pic_arrs = WeiboHotTimelinePic::weibo_hot_timeline_pic_r(
         weibo_db_rb_conn, Some(&timeline_mid_arrs)).await?;
       for timeline_pic_arri in timeline_pic_arrs {
-        timeline_pic_tbls.entry(timeline_pic_arri.mid.clone()).or_insert_with(Vec::new).
+        timeline_pic_tbls.entry(timeline_pic_arri.mid.clone()).or_default().
           push(timeline_pic_arri);
       }
     }
@@ -263,9 +524,10 @@ impl WeiboHotTimeline {
     let mut timeline_comm_tbls: HashMap<String, Vec<WeiboHotTimelineComm>> = HashMap::new();
     if comm {
       let timeline_comm_arrs = WeiboHotTimelineComm::weibo_hot_timeline_comm_r(
-        weibo_db_rb_conn, Some(&timeline_mid_arrs), None, None, None, None).await?;
+        weibo_db_rb_conn, Some(&timeline_mid_arrs), None, None, None, None,
+        &WeiboEraSpan::default()).await?;
       for timeline_comm_arri in timeline_comm_arrs {
-        timeline_comm_tbls.entry(timeline_comm_arri.mid.clone()).or_insert_with(Vec::new).
+        timeline_comm_tbls.entry(timeline_comm_arri.mid.clone()).or_default().
           push(timeline_comm_arri);
       }
     }
@@ -284,7 +546,56 @@ impl WeiboHotTimeline {
     Ok(hot_timeline_arrs)
   }
 
+  /// 获取已存储的微博热门推荐的mid
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid_arrs`: 待检查的热门推荐的mid
+  ///
+  /// ## 返回
+  /// 成功则返回其中已存储在数据库中的mid
+  pub async fn weibo_hot_timeline_mid_r(
+    weibo_db_rb_conn: &RBatis, timeline_mid_arrs: &Vec<String>,
+  ) -> Result<HashSet<String>, WeiboError> {
+    if timeline_mid_arrs.is_empty() {
+      return Ok(HashSet::new());
+    }
+
+    let weibo_hot_timeline_r_qry = rbs::value! {"mid": timeline_mid_arrs};
+    let timeline_arrs = Self::select_by_map(weibo_db_rb_conn, weibo_hot_timeline_r_qry).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    Ok(timeline_arrs.into_iter().map(|timeline_arri| timeline_arri.mid).collect())
+  }
+
+  /// 获取已展开全文的长微博的全文
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid_arrs`: 待检查的热门推荐的mid
+  ///
+  /// ## 返回
+  /// 成功则返回其中已存储全文的mid与全文，这些长微博无需再次获取全文
+  pub async fn weibo_hot_timeline_expanded_r(
+    weibo_db_rb_conn: &RBatis, timeline_mid_arrs: &Vec<String>,
+  ) -> Result<HashMap<String, String>, WeiboError> {
+    if timeline_mid_arrs.is_empty() {
+      return Ok(HashMap::new());
+    }
+
+    let weibo_hot_timeline_r_qry = rbs::value! {"mid": timeline_mid_arrs, "text_expanded": true};
+    let timeline_arrs = Self::select_by_map(weibo_db_rb_conn, weibo_hot_timeline_r_qry).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    Ok(timeline_arrs.into_iter().
+      map(|timeline_arri| (timeline_arri.mid, timeline_arri.text)).
+      collect())
+  }
+
   /// 更新微博热门推荐WeiboHotTimeline数据，如果有相同的mid则更新；否则直接插入。
+  /// 已展开的长微博不会被截断的内容覆盖，created_at无法解析时保留已有的发布时间。
   ///
   /// ## 参数
   /// - `weibo_db_rb_conn`：rbatis数据库连接
@@ -298,23 +609,55 @@ impl WeiboHotTimeline {
     let mut weibo_hot_timeline_ques = vec![];
     let mut weibo_hot_timeline_pars = vec![];
     for hot_timeline_arri in hot_timeline_arrs.iter() {
-      weibo_hot_timeline_ques.push("(?, ?, ?, ?, ?, ?)");
+      weibo_hot_timeline_ques.push("(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)");
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mid.clone()));
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mblogid.clone()));
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.text.clone()));
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mem_id.clone()));
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mem_name.clone()));
       weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.occur_era.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.occur_utc.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.occur_offset.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.crawl_era.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.repost_cnt));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.comm_cnt));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.like_cnt));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.source.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.region_name.clone()));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.long_text));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.text_expanded));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mem_verified));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mem_verified_ilk));
+      weibo_hot_timeline_pars.push(rbs::value!(hot_timeline_arri.mem_follower_cnt));
     }
 
     let weibo_hot_search_sent = format!(
-      "insert into weibo_hot_timeline (mid, mblogid, text, mem_id, mem_name, occur_era) \
+      "insert into weibo_hot_timeline (mid, mblogid, text, mem_id, mem_name, occur_era, \
+         occur_utc, occur_offset, crawl_era, repost_cnt, comm_cnt, like_cnt, source, region_name, long_text, text_expanded, \
+         mem_verified, mem_verified_ilk, mem_follower_cnt) \
        values {} \
        on conflict(mid) do update set \
          mblogid = excluded.mblogid, \
-         text = excluded.text, \
+         text = case when excluded.text_expanded or not weibo_hot_timeline.text_expanded \
+           then excluded.text else weibo_hot_timeline.text end, \
+         text_expanded = max(excluded.text_expanded, weibo_hot_timeline.text_expanded), \
          mem_name = excluded.mem_name, \
-         occur_era = excluded.occur_era",
+         occur_era = case when excluded.occur_utc = '' \
+           then weibo_hot_timeline.occur_era else excluded.occur_era end, \
+         occur_utc = case when excluded.occur_utc = '' \
+           then weibo_hot_timeline.occur_utc else excluded.occur_utc end, \
+         occur_offset = case when excluded.occur_utc = '' \
+           then weibo_hot_timeline.occur_offset else excluded.occur_offset end, \
+         crawl_era = excluded.crawl_era, \
+         repost_cnt = excluded.repost_cnt, \
+         comm_cnt = excluded.comm_cnt, \
+         like_cnt = excluded.like_cnt, \
+         source = excluded.source, \
+         region_name = excluded.region_name, \
+         long_text = excluded.long_text, \
+         mem_verified = excluded.mem_verified, \
+         mem_verified_ilk = excluded.mem_verified_ilk, \
+         mem_follower_cnt = excluded.mem_follower_cnt",
       weibo_hot_timeline_ques.join(", "));
 
     weibo_db_rb_conn.exec(&weibo_hot_search_sent, weibo_hot_timeline_pars).await.map(|_| ()
@@ -356,7 +699,144 @@ impl WeiboHotTimeline {
                                          but no_sieve guarantee isn't provided.".to_string()));
     }
 
-    Self::delete_by_map(weibo_db_rb_conn, weibo_hot_timeline_d_qry).await.map(|_| ()).map_err(
+    Self::delete_by_map(weibo_db_rb_conn, weibo_hot_timeline_d_qry).await.map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+    // 外键的级联删除依赖每个连接的foreign_keys设置，显式删除已失去热门推荐的实体
+    weibo_db_rb_conn.exec(
+      "delete from weibo_hot_timeline_ent where mid not in (select mid from weibo_hot_timeline)",
+      vec![]).await.map(|_| ()).map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+}
+
+/// 微博热门推荐的互动数与发布者信息，从热门推荐列表中提取
+#[derive(Clone, Debug, Default)]
+pub struct WeiboHotTimelineMetr {
+  // 转发数
+  pub repost_cnt: u32,
+  // 评论数
+  pub comm_cnt: u32,
+  // 点赞数
+  pub like_cnt: u32,
+  // 发布来源
+  pub source: String,
+  // IP属地
+  pub region_name: String,
+  // 是否为长微博
+  pub long_text: bool,
+  // 发布者是否为认证用户
+  pub mem_verified: bool,
+  // 发布者的认证类型，-1为未认证
+  pub mem_verified_ilk: i32,
+  // 发布者的粉丝数
+  pub mem_follower_cnt: u32,
+}
+
+/// 微博热门推荐的互动数快照，每次爬取热门推荐时记录一次，用于还原互动数随时间的增长
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotTimelineSnap {
+  pub id: Option<usize>,
+  // 热门推荐的mid
+  pub mid: String,
+  // 转发数
+  pub repost_cnt: u32,
+  // 评论数
+  pub comm_cnt: u32,
+  // 点赞数
+  pub like_cnt: u32,
+  // 发布者的粉丝数
+  pub mem_follower_cnt: u32,
+  // 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub snap_era: String,
+}
+rbatis::crud!(WeiboHotTimelineSnap {}, "weibo_hot_timeline_snap");
+
+impl From<WeiboHotTimelineSnap> for JsonValue {
+  fn from(weibo_hot_timeline_snap: WeiboHotTimelineSnap) -> Self {
+    object! {
+      id: weibo_hot_timeline_snap.id,
+      mid: weibo_hot_timeline_snap.mid,
+      repost_cnt: weibo_hot_timeline_snap.repost_cnt,
+      comm_cnt: weibo_hot_timeline_snap.comm_cnt,
+      like_cnt: weibo_hot_timeline_snap.like_cnt,
+      mem_follower_cnt: weibo_hot_timeline_snap.mem_follower_cnt,
+      snap_era: weibo_hot_timeline_snap.snap_era
+    }
+  }
+}
+
+impl WeiboHotTimelineSnap {
+  /// 创建一个微博热门推荐互动数快照WeiboHotTimelineSnap对象
+  ///
+  /// ## 参数
+  /// - `timeline_mid`: 热门推荐的mid
+  /// - `timeline_repost_cnt`: 转发数
+  /// - `timeline_comm_cnt`: 评论数
+  /// - `timeline_like_cnt`: 点赞数
+  /// - `timeline_mem_follower_cnt`: 发布者的粉丝数
+  /// - `snap_era`: 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub fn weibo_hot_timeline_snap_c(
+    timeline_mid: String, timeline_repost_cnt: u32, timeline_comm_cnt: u32,
+    timeline_like_cnt: u32, timeline_mem_follower_cnt: u32, snap_era: String,
+  ) -> Self {
+    Self {
+      id: None,
+      mid: timeline_mid,
+      repost_cnt: timeline_repost_cnt,
+      comm_cnt: timeline_comm_cnt,
+      like_cnt: timeline_like_cnt,
+      mem_follower_cnt: timeline_mem_follower_cnt,
+      snap_era,
+    }
+  }
+
+  /// 获取微博热门推荐互动数快照WeiboHotTimelineSnap对象，按爬取时间升序排列
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid`: 热门推荐的mid
+  /// - `snap_from_era`: 爬取时间的下限（含），格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS，可选
+  /// - `snap_to_era`: 爬取时间的上限（含），格式YYYY-MM-DD或YYYY-MM-DD HH:MM:SS，可选
+  ///
+  /// ## 返回
+  /// 成功则返回该热门推荐的互动数曲线
+  pub async fn weibo_hot_timeline_snap_r(
+    weibo_db_rb_conn: &RBatis, timeline_mid: String, snap_from_era: Option<String>,
+    snap_to_era: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_hot_timeline_snap_sent =
+      "select * from weibo_hot_timeline_snap where mid = ?".to_string();
+    let mut weibo_hot_timeline_snap_pars = vec![rbs::value!(timeline_mid)];
+    if let Some(snap_from_era) = snap_from_era {
+      weibo_hot_timeline_snap_sent.push_str(" and snap_era >= ?");
+      weibo_hot_timeline_snap_pars.push(rbs::value!(snap_from_era));
+    }
+    if let Some(snap_to_era) = snap_to_era {
+      // 只有日期时，包含当天全部的快照
+      weibo_hot_timeline_snap_sent.push_str(" and snap_era <= ?");
+      weibo_hot_timeline_snap_pars.push(rbs::value!(era_to_bound(&snap_to_era)));
+    }
+    weibo_hot_timeline_snap_sent.push_str(" order by snap_era, id");
+
+    weibo_db_rb_conn.query_decode(&weibo_hot_timeline_snap_sent, weibo_hot_timeline_snap_pars).
+      await.map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 插入微博热门推荐互动数快照WeiboHotTimelineSnap数据，每次爬取均保留
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `hot_timeline_snap_arrs`: 新的微博热门推荐互动数快照数据
+  pub async fn weibo_hot_timeline_snap_u(
+    weibo_db_rb_conn: &RBatis, hot_timeline_snap_arrs: Vec<Self>) -> Result<(), WeiboError> {
+    if hot_timeline_snap_arrs.is_empty() {
+      return Ok(());
+    }
+
+    Self::insert_batch(weibo_db_rb_conn, &hot_timeline_snap_arrs, 50).await.map(|_| ()).map_err(
       |flaw| {
         WeiboError::RbatisError(flaw.to_string())
       }
@@ -374,6 +854,28 @@ pub struct WeiboHotTimelinePic {
   pub pic_id: String,
   // 图片url
   pub pic_url: String,
+  // 下载状态：pending、done、failed
+  pub dl_sta: String,
+  // 下载到的字节数
+  pub dl_size: u64,
+  // 存储到本地的图片文件路径
+  pub dl_pth: String,
+  // 最近一次下载失败的原因
+  pub dl_flaw: String,
+  // 已尝试下载的次数
+  pub dl_cnt: u32,
+  // 最近一次尝试下载的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub dl_era: String,
+  // 图片内容的SHA-256，十六进制小写
+  pub pic_hash: String,
+  // 按魔数识别的图片格式：jpg、png、gif、webp
+  pub pic_fmt: String,
+  // 图片宽度，无法解析时为0
+  pub pic_width: u32,
+  // 图片高度，无法解析时为0
+  pub pic_height: u32,
+  // 图片的感知哈希（dHash），无法解码时为空
+  pub pic_phash: String,
 }
 rbatis::crud!(WeiboHotTimelinePic {}, "weibo_hot_timeline_pic");
 
@@ -383,7 +885,18 @@ impl From<WeiboHotTimelinePic> for JsonValue {
       id: weibo_hot_timeline_pic.id,
       mid: weibo_hot_timeline_pic.mid,
       pic_id: weibo_hot_timeline_pic.pic_id,
-      pic_url: weibo_hot_timeline_pic.pic_url
+      pic_url: weibo_hot_timeline_pic.pic_url,
+      dl_sta: weibo_hot_timeline_pic.dl_sta,
+      dl_size: weibo_hot_timeline_pic.dl_size,
+      dl_pth: weibo_hot_timeline_pic.dl_pth,
+      dl_flaw: weibo_hot_timeline_pic.dl_flaw,
+      dl_cnt: weibo_hot_timeline_pic.dl_cnt,
+      dl_era: weibo_hot_timeline_pic.dl_era,
+      pic_hash: weibo_hot_timeline_pic.pic_hash,
+      pic_fmt: weibo_hot_timeline_pic.pic_fmt,
+      pic_width: weibo_hot_timeline_pic.pic_width,
+      pic_height: weibo_hot_timeline_pic.pic_height,
+      pic_phash: weibo_hot_timeline_pic.pic_phash
     }
   }
 }
@@ -402,6 +915,17 @@ impl WeiboHotTimelinePic {
       mid: timeline_mid,
       pic_id: timeline_pic_id,
       pic_url: timeline_pic_url,
+      dl_sta: "pending".to_string(),
+      dl_size: 0,
+      dl_pth: String::new(),
+      dl_flaw: String::new(),
+      dl_cnt: 0,
+      dl_era: String::new(),
+      pic_hash: String::new(),
+      pic_fmt: String::new(),
+      pic_width: 0,
+      pic_height: 0,
+      pic_phash: String::new(),
     }
   }
 
@@ -426,22 +950,183 @@ impl WeiboHotTimelinePic {
     })
   }
 
-  /// 更新微博热门推荐图片WeiboHotTimelinePic数据
+  /// 更新微博热门推荐图片WeiboHotTimelinePic数据，如果有相同的mid与pic_id则更新；否则直接插入。
   ///
   /// ## 参数
   /// - `weibo_db_rb_conn`：rbatis数据库连接
   /// - `hot_timeline_pic_arrs`: 新的微博热门推荐图片数据
   pub async fn weibo_hot_timeline_pic_u(
     weibo_db_rb_conn: &RBatis, hot_timeline_pic_arrs: Vec<Self>) -> Result<(), WeiboError> {
-    if hot_timeline_pic_arrs.is_empty() {
-      return Ok(());
+    // 同一条语句中不能两次更新同一行，相同的mid与pic_id只保留最后一条
+    let mut hot_timeline_pic_keys: HashSet<(String, String)> = HashSet::new();
+    let mut hot_timeline_pic_arrs: Vec<Self> = hot_timeline_pic_arrs.into_iter().rev().
+      filter(|pic_arri| {
+        hot_timeline_pic_keys.insert((pic_arri.mid.clone(), pic_arri.pic_id.clone()))
+      }).
+      collect();
+    hot_timeline_pic_arrs.reverse();
+
+    for hot_timeline_pic_chks in hot_timeline_pic_arrs.chunks(prefs().db_upsert_chk) {
+      let mut weibo_hot_timeline_pic_ques = vec![];
+      let mut weibo_hot_timeline_pic_pars = vec![];
+      for hot_timeline_pic_arri in hot_timeline_pic_chks.iter() {
+        weibo_hot_timeline_pic_ques.push("(?, ?, ?)");
+        weibo_hot_timeline_pic_pars.push(rbs::value!(hot_timeline_pic_arri.mid.clone()));
+        weibo_hot_timeline_pic_pars.push(rbs::value!(hot_timeline_pic_arri.pic_id.clone()));
+        weibo_hot_timeline_pic_pars.push(rbs::value!(hot_timeline_pic_arri.pic_url.clone()));
+      }
+
+      let weibo_hot_timeline_pic_sent = format!(
+        "insert into weibo_hot_timeline_pic (mid, pic_id, pic_url) \
+         values {} \
+         on conflict(mid, pic_id) do update set \
+           pic_url = excluded.pic_url",
+        weibo_hot_timeline_pic_ques.join(", "));
+
+      weibo_db_rb_conn.exec(&weibo_hot_timeline_pic_sent, weibo_hot_timeline_pic_pars).await.
+        map_err(|flaw| {
+          WeiboError::RbatisError(flaw.to_string())
+        })?;
     }
+    Ok(())
+  }
 
-    Self::insert_batch(weibo_db_rb_conn, &hot_timeline_pic_arrs, 10).await.map(|_| ()).map_err(
-      |flaw| {
-        WeiboError::RbatisError(flaw.to_string())
+  /// 获取尚未下载成功且下载次数未达上限的微博热门推荐图片，用于重试下载
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid_arrs`: 热门推荐的mid，可选
+  /// - `dl_cnt_max`: 下载次数上限
+  pub async fn weibo_hot_timeline_pic_dl_r(weibo_db_rb_conn: &RBatis,
+                                           timeline_mid_arrs: Option<&Vec<String>>,
+                                           dl_cnt_max: u32) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_hot_timeline_pic_sent = "select * from weibo_hot_timeline_pic \
+                                           where dl_sta != 'done' and dl_cnt < ?".to_string();
+    let mut weibo_hot_timeline_pic_pars = vec![rbs::value!(dl_cnt_max)];
+    if let Some(timeline_mid_arrs) = timeline_mid_arrs {
+      if timeline_mid_arrs.is_empty() {
+        return Ok(vec![]);
       }
-    )
+      weibo_hot_timeline_pic_sent.push_str(&format!(
+        " and mid in ({})", vec!["?"; timeline_mid_arrs.len()].join(", ")));
+      weibo_hot_timeline_pic_pars.extend(timeline_mid_arrs.iter().
+        map(|timeline_mid| rbs::value!(timeline_mid)));
+    }
+    weibo_hot_timeline_pic_sent.push_str(" order by id");
+
+    weibo_db_rb_conn.query_decode(&weibo_hot_timeline_pic_sent, weibo_hot_timeline_pic_pars).
+      await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 记录一次微博热门推荐图片的下载结果，下载次数加一
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `pic_id`: 图片记录的id
+  /// - `dl_rst`: 下载结果，成功时为存入图片库的图片，失败时为原因
+  /// - `dl_era`: 尝试下载的时间
+  pub async fn weibo_hot_timeline_pic_dl_u(weibo_db_rb_conn: &RBatis, pic_id: usize,
+                                           dl_rst: &Result<WeiboPicStor, WeiboError>,
+                                           dl_era: &str) -> Result<(), WeiboError> {
+    let (weibo_hot_timeline_pic_sent, weibo_hot_timeline_pic_pars) = match dl_rst {
+      Ok(pic_stor) => (
+        "update weibo_hot_timeline_pic set \
+           dl_sta = 'done', dl_size = ?, dl_pth = ?, dl_flaw = '', \
+           dl_cnt = dl_cnt + 1, dl_era = ?, \
+           pic_hash = ?, pic_fmt = ?, pic_width = ?, pic_height = ?, pic_phash = ? \
+         where id = ?",
+        vec![rbs::value!(pic_stor.size), rbs::value!(&pic_stor.pth), rbs::value!(dl_era),
+             rbs::value!(&pic_stor.hash), rbs::value!(pic_stor.fmt.pic_ext()),
+             rbs::value!(pic_stor.width), rbs::value!(pic_stor.height),
+             rbs::value!(&pic_stor.phash), rbs::value!(pic_id)]),
+      Err(flaw) => (
+        "update weibo_hot_timeline_pic set \
+           dl_sta = 'failed', dl_size = 0, dl_pth = '', dl_flaw = ?, \
+           dl_cnt = dl_cnt + 1, dl_era = ? \
+         where id = ?",
+        vec![rbs::value!(flaw.to_string()), rbs::value!(dl_era), rbs::value!(pic_id)]),
+    };
+
+    weibo_db_rb_conn.exec(weibo_hot_timeline_pic_sent, weibo_hot_timeline_pic_pars).await.
+      map(|_| ()).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 获取已下载到图片库的一张微博热门推荐图片，按mid与图片id或按内容的哈希查找
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid`: 热门推荐的mid，可选
+  /// - `timeline_pic_id`: 图片id，可选
+  /// - `pic_hash`: 图片内容的SHA-256，可选
+  ///
+  /// ## 返回
+  /// 没有符合条件且已下载成功的图片时返回None
+  pub async fn weibo_hot_timeline_pic_stor_r(weibo_db_rb_conn: &RBatis,
+                                             timeline_mid: Option<&str>,
+                                             timeline_pic_id: Option<&str>,
+                                             pic_hash: Option<&str>,
+  ) -> Result<Option<Self>, WeiboError> {
+    let mut weibo_hot_timeline_pic_r_qry = rbs::value! {"dl_sta": "done"};
+    if let Some(timeline_mid) = timeline_mid {
+      weibo_hot_timeline_pic_r_qry.insert(rbs::value!("mid"), rbs::value!(timeline_mid));
+    }
+    if let Some(timeline_pic_id) = timeline_pic_id {
+      weibo_hot_timeline_pic_r_qry.insert(rbs::value!("pic_id"), rbs::value!(timeline_pic_id));
+    }
+    if let Some(pic_hash) = pic_hash {
+      weibo_hot_timeline_pic_r_qry.insert(rbs::value!("pic_hash"), rbs::value!(pic_hash));
+    }
+
+    Self::select_by_map(weibo_db_rb_conn, weibo_hot_timeline_pic_r_qry).await.
+      map(|hot_timeline_pic_arrs| {
+        hot_timeline_pic_arrs.into_iter().find(|pic_arri| !pic_arri.pic_hash.is_empty())
+      }).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 获取已下载的微博热门推荐图片，按是否已计算感知哈希筛选
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `pic_phash_done`: 为true时获取已计算感知哈希的图片，用于查找相似图片；
+  ///   为false时获取尚未计算的图片，用于补算
+  pub async fn weibo_hot_timeline_pic_phash_r(weibo_db_rb_conn: &RBatis, pic_phash_done: bool,
+  ) -> Result<Vec<Self>, WeiboError> {
+    let weibo_hot_timeline_pic_sent = format!(
+      "select * from weibo_hot_timeline_pic \
+       where dl_sta = 'done' and pic_hash != '' and pic_phash {} '' \
+       order by id",
+      if pic_phash_done { "!=" } else { "=" });
+
+    weibo_db_rb_conn.query_decode(&weibo_hot_timeline_pic_sent, vec![]).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 记录图片的感知哈希，内容相同的图片一并更新
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `pic_hash`: 图片内容的SHA-256
+  /// - `pic_phash`: 感知哈希
+  pub async fn weibo_hot_timeline_pic_phash_u(weibo_db_rb_conn: &RBatis, pic_hash: &str,
+                                              pic_phash: &str) -> Result<(), WeiboError> {
+    weibo_db_rb_conn.exec(
+      "update weibo_hot_timeline_pic set pic_phash = ? where pic_hash = ?",
+      vec![rbs::value!(pic_phash), rbs::value!(pic_hash)]).await.
+      map(|_| ()).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
   }
 
   /// 删除微博热门推荐图片WeiboHotTimelinePic数据
@@ -469,6 +1154,160 @@ impl WeiboHotTimelinePic {
   }
 }
 
+/// 微博热门推荐图片中的EXIF/XMP元数据，按内容的哈希与WeiboHotTimelinePic关联
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotTimelinePicMeta {
+  pub id: Option<usize>,
+  // 图片内容的SHA-256
+  pub pic_hash: String,
+  // 相机厂商
+  pub cam_make: String,
+  // 相机型号
+  pub cam_model: String,
+  // 镜头型号
+  pub lens_model: String,
+  // 处理软件
+  pub software: String,
+  // 拍摄时间，保留图片中的原始格式
+  pub shot_era: String,
+  // 修改时间，保留图片中的原始格式
+  pub modify_era: String,
+  // 是否带有位置信息
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub gps_flag: bool,
+  // 纬度，南纬为负
+  pub gps_lat: Option<f64>,
+  // 经度，西经为负
+  pub gps_lon: Option<f64>,
+  // 全部EXIF字段，键为`IFD/字段名`
+  pub exif_tags: BTreeMap<String, String>,
+  // XMP数据包原文
+  pub xmp: String,
+  // 解析元数据的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub meta_era: String,
+}
+rbatis::crud!(WeiboHotTimelinePicMeta {}, "weibo_hot_timeline_pic_meta");
+
+impl From<WeiboHotTimelinePicMeta> for JsonValue {
+  fn from(weibo_hot_timeline_pic_meta: WeiboHotTimelinePicMeta) -> Self {
+    let mut exif_tags = JsonValue::new_object();
+    for (exif_tag, exif_val) in weibo_hot_timeline_pic_meta.exif_tags {
+      exif_tags[exif_tag] = exif_val.into();
+    }
+    object! {
+      id: weibo_hot_timeline_pic_meta.id,
+      pic_hash: weibo_hot_timeline_pic_meta.pic_hash,
+      cam_make: weibo_hot_timeline_pic_meta.cam_make,
+      cam_model: weibo_hot_timeline_pic_meta.cam_model,
+      lens_model: weibo_hot_timeline_pic_meta.lens_model,
+      software: weibo_hot_timeline_pic_meta.software,
+      shot_era: weibo_hot_timeline_pic_meta.shot_era,
+      modify_era: weibo_hot_timeline_pic_meta.modify_era,
+      gps_flag: weibo_hot_timeline_pic_meta.gps_flag,
+      gps_lat: weibo_hot_timeline_pic_meta.gps_lat,
+      gps_lon: weibo_hot_timeline_pic_meta.gps_lon,
+      exif_tags: exif_tags,
+      xmp: weibo_hot_timeline_pic_meta.xmp,
+      meta_era: weibo_hot_timeline_pic_meta.meta_era
+    }
+  }
+}
+
+impl WeiboHotTimelinePicMeta {
+  /// 获取微博热门推荐图片的元数据WeiboHotTimelinePicMeta对象
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid`: 只获取该热门推荐的图片的元数据，可选
+  /// - `pic_hash`: 图片内容的SHA-256，可选
+  /// - `gps_flag`: 是否带有位置信息，可选
+  pub async fn weibo_hot_timeline_pic_meta_r(weibo_db_rb_conn: &RBatis,
+                                             timeline_mid: Option<String>,
+                                             pic_hash: Option<String>,
+                                             gps_flag: Option<bool>,
+  ) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_hot_timeline_pic_meta_sent =
+      "select * from weibo_hot_timeline_pic_meta where 1 = 1".to_string();
+    let mut weibo_hot_timeline_pic_meta_pars = vec![];
+    if let Some(timeline_mid) = timeline_mid {
+      weibo_hot_timeline_pic_meta_sent.push_str(
+        " and pic_hash in (select pic_hash from weibo_hot_timeline_pic where mid = ?)");
+      weibo_hot_timeline_pic_meta_pars.push(rbs::value!(timeline_mid));
+    }
+    if let Some(pic_hash) = pic_hash {
+      weibo_hot_timeline_pic_meta_sent.push_str(" and pic_hash = ?");
+      weibo_hot_timeline_pic_meta_pars.push(rbs::value!(pic_hash));
+    }
+    if let Some(gps_flag) = gps_flag {
+      weibo_hot_timeline_pic_meta_sent.push_str(" and gps_flag = ?");
+      weibo_hot_timeline_pic_meta_pars.push(rbs::value!(gps_flag as i32));
+    }
+    weibo_hot_timeline_pic_meta_sent.push_str(" order by id");
+
+    weibo_db_rb_conn.query_decode(&weibo_hot_timeline_pic_meta_sent,
+                                  weibo_hot_timeline_pic_meta_pars).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 获取已下载但尚未解析元数据的微博热门推荐图片，例如升级前下载的图片，内容相同的图片只返回一张
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  pub async fn weibo_hot_timeline_pic_meta_pending_r(
+    weibo_db_rb_conn: &RBatis) -> Result<Vec<WeiboHotTimelinePic>, WeiboError> {
+    weibo_db_rb_conn.query_decode(
+      "select * from weibo_hot_timeline_pic \
+       where id in (select min(id) from weibo_hot_timeline_pic \
+                    where dl_sta = 'done' and pic_hash != '' \
+                      and pic_hash not in (select pic_hash from weibo_hot_timeline_pic_meta) \
+                    group by pic_hash) \
+       order by id", vec![]).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 记录图片的元数据，已有相同内容的图片的记录时更新
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `pic_hash`: 图片内容的SHA-256
+  /// - `pic_meta`: 解析出的元数据
+  /// - `meta_era`: 解析元数据的时间
+  pub async fn weibo_hot_timeline_pic_meta_u(weibo_db_rb_conn: &RBatis, pic_hash: &str,
+                                             pic_meta: &WeiboPicMeta,
+                                             meta_era: &str) -> Result<(), WeiboError> {
+    let mut exif_tags = JsonValue::new_object();
+    for (exif_tag, exif_val) in pic_meta.exif_tags.iter() {
+      exif_tags[exif_tag.as_str()] = exif_val.as_str().into();
+    }
+    weibo_db_rb_conn.exec(
+      "insert into weibo_hot_timeline_pic_meta \
+         (pic_hash, cam_make, cam_model, lens_model, software, shot_era, modify_era, \
+          gps_flag, gps_lat, gps_lon, exif_tags, xmp, meta_era) \
+       values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+       on conflict(pic_hash) do update set \
+         cam_make = excluded.cam_make, cam_model = excluded.cam_model, \
+         lens_model = excluded.lens_model, software = excluded.software, \
+         shot_era = excluded.shot_era, modify_era = excluded.modify_era, \
+         gps_flag = excluded.gps_flag, gps_lat = excluded.gps_lat, gps_lon = excluded.gps_lon, \
+         exif_tags = excluded.exif_tags, xmp = excluded.xmp, meta_era = excluded.meta_era",
+      vec![rbs::value!(pic_hash), rbs::value!(&pic_meta.cam_make),
+           rbs::value!(&pic_meta.cam_model), rbs::value!(&pic_meta.lens_model),
+           rbs::value!(&pic_meta.software), rbs::value!(&pic_meta.shot_era),
+           rbs::value!(&pic_meta.modify_era), rbs::value!(pic_meta.gps_flag as i32),
+           rbs::value!(pic_meta.gps_lat), rbs::value!(pic_meta.gps_lon),
+           rbs::value!(jzon::stringify(exif_tags)), rbs::value!(&pic_meta.xmp),
+           rbs::value!(meta_era)]).await.
+      map(|_| ()).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+}
+
 /// 微博热门推荐的评论
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WeiboHotTimelineComm {
@@ -484,6 +1323,16 @@ pub struct WeiboHotTimelineComm {
   pub reply: bool,
   // 如果是评论回复，存储其根评论的id
   pub senior_id: String,
+  // 评论的点赞数
+  pub like_cnt: u32,
+  // 评论的回复数
+  pub reply_cnt: u32,
+  // 评论的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）；created_at无法解析时为空
+  pub comm_utc: String,
+  // 评论时的时区偏移，格式为±HH:MM；created_at无法解析时为空
+  pub comm_offset: String,
+  // 最近一次爬取到评论的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub crawl_era: String,
 }
 rbatis::crud!(WeiboHotTimelineComm {}, "weibo_hot_timeline_comm");
 
@@ -498,7 +1347,12 @@ impl From<WeiboHotTimelineComm> for JsonValue {
       mem_name: weibo_hot_timeline_comm.mem_name,
       comm_era: weibo_hot_timeline_comm.comm_era,
       reply: weibo_hot_timeline_comm.reply,
-      senior_id: weibo_hot_timeline_comm.senior_id
+      senior_id: weibo_hot_timeline_comm.senior_id,
+      like_cnt: weibo_hot_timeline_comm.like_cnt,
+      reply_cnt: weibo_hot_timeline_comm.reply_cnt,
+      comm_utc: weibo_hot_timeline_comm.comm_utc,
+      comm_offset: weibo_hot_timeline_comm.comm_offset,
+      crawl_era: weibo_hot_timeline_comm.crawl_era
     }
   }
 }
@@ -515,10 +1369,19 @@ impl WeiboHotTimelineComm {
   /// - `timeline_comm_era`: 评论时间
   /// - `timeline_reply`: 是否是评论回复
   /// - `timeline_senior_id`: 如果是评论回复，存储其根评论的id
+  /// - `timeline_like_cnt`: 评论的点赞数
+  /// - `timeline_reply_cnt`: 评论的回复数
+  /// - `timeline_comm_utc`: 评论的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  /// - `timeline_comm_offset`: 评论时的时区偏移，格式为±HH:MM
+  /// - `timeline_crawl_era`: 爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  #[allow(clippy::too_many_arguments)]
   pub fn weibo_hot_timeline_comm_c(timeline_mid: String, timeline_comm_mid: String,
                                    timeline_text: String, timeline_mem_id: String,
                                    timeline_mem_name: String, timeline_comm_era: String,
-                                   timeline_reply: bool, timeline_senior_id: String) -> Self {
+                                   timeline_reply: bool, timeline_senior_id: String,
+                                   timeline_like_cnt: u32, timeline_reply_cnt: u32,
+                                   timeline_comm_utc: String, timeline_comm_offset: String,
+                                   timeline_crawl_era: String) -> Self {
     Self {
       id: None,
       mid: timeline_mid,
@@ -529,6 +1392,11 @@ impl WeiboHotTimelineComm {
       comm_era: timeline_comm_era,
       reply: timeline_reply,
       senior_id: timeline_senior_id,
+      like_cnt: timeline_like_cnt,
+      reply_cnt: timeline_reply_cnt,
+      comm_utc: timeline_comm_utc,
+      comm_offset: timeline_comm_offset,
+      crawl_era: timeline_crawl_era,
     }
   }
 
@@ -541,6 +1409,7 @@ impl WeiboHotTimelineComm {
   /// - `timeline_mem_id`: 评论用户id，可选
   /// - `timeline_mem_name`: 评论用户名，可选
   /// - `timeline_comm_era`: 评论时间，可选
+  /// - `timeline_comm_span`: 评论的时间范围，按UTC时间比较，没有UTC时间时按日期比较
   ///
   /// ## 返回
   /// 成功则返回符合查询条件的微博热门推荐评论数据
@@ -548,7 +1417,8 @@ impl WeiboHotTimelineComm {
     weibo_db_rb_conn: &RBatis,
     timeline_mid_arrs: Option<&Vec<String>>, timeline_comm_mid: Option<String>,
     timeline_mem_id: Option<String>, timeline_mem_name: Option<String>,
-    timeline_comm_era: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    timeline_comm_era: Option<String>, timeline_comm_span: &WeiboEraSpan)
+    -> Result<Vec<Self>, WeiboError> {
     let mut weibo_hot_timeline_comm_r_qry = rbs::value! {};
     if let Some(timeline_mid) = timeline_mid_arrs {
       weibo_hot_timeline_comm_r_qry.insert(rbs::value!("mid"), rbs::value!(timeline_mid));
@@ -565,28 +1435,74 @@ impl WeiboHotTimelineComm {
     if let Some(timeline_comm_era) = timeline_comm_era {
       weibo_hot_timeline_comm_r_qry.insert(rbs::value!("comm_era"), rbs::value!(timeline_comm_era));
     }
+    timeline_comm_span.era_span_qry(
+      &mut weibo_hot_timeline_comm_r_qry, "coalesce(nullif(comm_utc, ''), comm_era)");
 
     Self::select_by_map(weibo_db_rb_conn, weibo_hot_timeline_comm_r_qry).await.map_err(|flaw| {
       WeiboError::RbatisError(flaw.to_string())
     })
   }
 
-  /// 更新微博热门推荐评论WeiboHotTimelineComm数据
+  /// 更新微博热门推荐评论WeiboHotTimelineComm数据，如果有相同的mid与comm_mid则更新内容与计数；
+  /// 否则直接插入。
   ///
   /// ## 参数
   /// - `weibo_db_rb_conn`：rbatis数据库连接
   /// - `hot_timeline_comm_arrs`: 新的微博热门推荐评论数据
   pub async fn weibo_hot_timeline_comm_u(
     weibo_db_rb_conn: &RBatis, hot_timeline_comm_arrs: Vec<Self>) -> Result<(), WeiboError> {
-    if hot_timeline_comm_arrs.is_empty() {
-      return Ok(());
-    }
+    // 同一条语句中不能两次更新同一行，相同的mid与comm_mid只保留最后一条
+    let mut hot_timeline_comm_keys: HashSet<(String, String)> = HashSet::new();
+    let mut hot_timeline_comm_arrs: Vec<Self> = hot_timeline_comm_arrs.into_iter().rev().
+      filter(|comm_arri| {
+        hot_timeline_comm_keys.insert((comm_arri.mid.clone(), comm_arri.comm_mid.clone()))
+      }).
+      collect();
+    hot_timeline_comm_arrs.reverse();
 
-    Self::insert_batch(weibo_db_rb_conn, &hot_timeline_comm_arrs, 10).await.map(|_| ()).map_err(
-      |flaw| {
-        WeiboError::RbatisError(flaw.to_string())
+    for hot_timeline_comm_chks in hot_timeline_comm_arrs.chunks(prefs().db_upsert_chk) {
+      let mut weibo_hot_timeline_comm_ques = vec![];
+      let mut weibo_hot_timeline_comm_pars = vec![];
+      for hot_timeline_comm_arri in hot_timeline_comm_chks.iter() {
+        weibo_hot_timeline_comm_ques.push("(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)");
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.mid.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.comm_mid.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.text.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.mem_id.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.mem_name.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.comm_era.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.reply));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.senior_id.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.like_cnt));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.reply_cnt));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.comm_utc.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.comm_offset.clone()));
+        weibo_hot_timeline_comm_pars.push(rbs::value!(hot_timeline_comm_arri.crawl_era.clone()));
       }
-    )
+
+      let weibo_hot_timeline_comm_sent = format!(
+        "insert into weibo_hot_timeline_comm \
+           (mid, comm_mid, text, mem_id, mem_name, comm_era, reply, senior_id, \
+            like_cnt, reply_cnt, comm_utc, comm_offset, crawl_era) \
+         values {} \
+         on conflict(mid, comm_mid) do update set \
+           text = excluded.text, \
+           mem_name = excluded.mem_name, \
+           like_cnt = excluded.like_cnt, \
+           reply_cnt = excluded.reply_cnt, \
+           comm_utc = case when excluded.comm_utc = '' \
+             then weibo_hot_timeline_comm.comm_utc else excluded.comm_utc end, \
+           comm_offset = case when excluded.comm_utc = '' \
+             then weibo_hot_timeline_comm.comm_offset else excluded.comm_offset end, \
+           crawl_era = excluded.crawl_era",
+        weibo_hot_timeline_comm_ques.join(", "));
+
+      weibo_db_rb_conn.exec(&weibo_hot_timeline_comm_sent, weibo_hot_timeline_comm_pars).await.
+        map_err(|flaw| {
+          WeiboError::RbatisError(flaw.to_string())
+        })?;
+    }
+    Ok(())
   }
 
   /// 删除微博热门推荐评论WeiboHotTimelineComm数据
@@ -617,7 +1533,71 @@ impl WeiboHotTimelineComm {
                                          but no_sieve guarantee isn't provided.".to_string()));
     }
 
-    Self::delete_by_map(weibo_db_rb_conn, weibo_hot_timeline_comm_d_qry).await.map(|_| ()).map_err(
+    Self::delete_by_map(weibo_db_rb_conn, weibo_hot_timeline_comm_d_qry).await.map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+    // 外键的级联删除依赖每个连接的foreign_keys设置，显式删除已失去评论的实体
+    weibo_db_rb_conn.exec(
+      "delete from weibo_hot_timeline_comm_ent where not exists \
+       (select 1 from weibo_hot_timeline_comm where \
+        weibo_hot_timeline_comm.mid = weibo_hot_timeline_comm_ent.mid and \
+        weibo_hot_timeline_comm.comm_mid = weibo_hot_timeline_comm_ent.comm_mid)",
+      vec![]).await.map(|_| ()).map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+}
+
+/// 微博热门推荐正文中的话题、提及与链接
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotTimelineEnt {
+  pub id: Option<usize>,
+  // 热门推荐的mid
+  pub mid: String,
+  // 实体类型：topic、mention、url
+  pub ent_ilk: String,
+  // 话题不含`#`，提及不含`@`
+  pub ent: String,
+}
+rbatis::crud!(WeiboHotTimelineEnt {}, "weibo_hot_timeline_ent");
+
+impl WeiboHotTimelineEnt {
+  /// 创建一个微博热门推荐实体WeiboHotTimelineEnt对象
+  ///
+  /// ## 参数
+  /// - `timeline_mid`: 热门推荐的mid
+  /// - `weibo_ent`: 从正文中提取的实体
+  pub fn weibo_hot_timeline_ent_c(timeline_mid: String, weibo_ent: WeiboEnt) -> Self {
+    Self {
+      id: None,
+      mid: timeline_mid,
+      ent_ilk: weibo_ent.ilk.ent_ilk().to_string(),
+      ent: weibo_ent.ent,
+    }
+  }
+
+  /// 替换微博热门推荐的实体：先删除这些热门推荐已有的实体，再插入新的实体。
+  /// 需要在热门推荐存入数据库之后调用。
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `timeline_mid_arrs`: 本次更新的热门推荐的mid
+  /// - `hot_timeline_ent_arrs`: 这些热门推荐的全部实体
+  pub async fn weibo_hot_timeline_ent_u(
+    weibo_db_rb_conn: &RBatis, timeline_mid_arrs: &Vec<String>, hot_timeline_ent_arrs: Vec<Self>,
+  ) -> Result<(), WeiboError> {
+    if timeline_mid_arrs.is_empty() {
+      return Ok(());
+    }
+
+    Self::delete_by_map(weibo_db_rb_conn, rbs::value! {"mid": timeline_mid_arrs}).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    if hot_timeline_ent_arrs.is_empty() {
+      return Ok(());
+    }
+    Self::insert_batch(weibo_db_rb_conn, &hot_timeline_ent_arrs, 50).await.map(|_| ()).map_err(
       |flaw| {
         WeiboError::RbatisError(flaw.to_string())
       }
@@ -625,6 +1605,540 @@ impl WeiboHotTimelineComm {
   }
 }
 
+/// 微博热门推荐评论中的话题、提及与链接
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotTimelineCommEnt {
+  pub id: Option<usize>,
+  // 热门推荐的mid
+  pub mid: String,
+  // 评论的mid
+  pub comm_mid: String,
+  // 实体类型：topic、mention、url
+  pub ent_ilk: String,
+  // 话题不含`#`，提及不含`@`
+  pub ent: String,
+}
+rbatis::crud!(WeiboHotTimelineCommEnt {}, "weibo_hot_timeline_comm_ent");
+
+impl WeiboHotTimelineCommEnt {
+  /// 创建一个微博热门推荐评论实体WeiboHotTimelineCommEnt对象
+  ///
+  /// ## 参数
+  /// - `timeline_mid`: 热门推荐的mid
+  /// - `timeline_comm_mid`: 评论的mid
+  /// - `weibo_ent`: 从评论中提取的实体
+  pub fn weibo_hot_timeline_comm_ent_c(timeline_mid: String, timeline_comm_mid: String,
+                                       weibo_ent: WeiboEnt) -> Self {
+    Self {
+      id: None,
+      mid: timeline_mid,
+      comm_mid: timeline_comm_mid,
+      ent_ilk: weibo_ent.ilk.ent_ilk().to_string(),
+      ent: weibo_ent.ent,
+    }
+  }
+
+  /// 替换微博热门推荐评论的实体：先删除这些评论已有的实体，再插入新的实体。
+  /// 需要在评论存入数据库之后调用。
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `comm_key_arrs`: 本次更新的评论的(mid, comm_mid)
+  /// - `hot_timeline_comm_ent_arrs`: 这些评论的全部实体
+  pub async fn weibo_hot_timeline_comm_ent_u(
+    weibo_db_rb_conn: &RBatis, comm_key_arrs: &[(String, String)],
+    hot_timeline_comm_ent_arrs: Vec<Self>) -> Result<(), WeiboError> {
+    for comm_key_chks in comm_key_arrs.chunks(prefs().db_upsert_chk) {
+      let mut weibo_comm_ent_pars = vec![];
+      for (timeline_mid, comm_mid) in comm_key_chks.iter() {
+        weibo_comm_ent_pars.push(rbs::value!(timeline_mid.clone()));
+        weibo_comm_ent_pars.push(rbs::value!(comm_mid.clone()));
+      }
+      let weibo_comm_ent_sent = format!(
+        "delete from weibo_hot_timeline_comm_ent where (mid, comm_mid) in (values {})",
+        vec!["(?, ?)"; comm_key_chks.len()].join(", "));
+      weibo_db_rb_conn.exec(&weibo_comm_ent_sent, weibo_comm_ent_pars).await.map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    }
+
+    // 同一条评论可能在本次爬取中出现多次
+    let mut comm_ent_keys: HashSet<(String, String, String, String)> = HashSet::new();
+    let hot_timeline_comm_ent_arrs: Vec<Self> = hot_timeline_comm_ent_arrs.into_iter().
+      filter(|comm_ent_arri| {
+        comm_ent_keys.insert((comm_ent_arri.mid.clone(), comm_ent_arri.comm_mid.clone(),
+                              comm_ent_arri.ent_ilk.clone(), comm_ent_arri.ent.clone()))
+      }).
+      collect();
+    if hot_timeline_comm_ent_arrs.is_empty() {
+      return Ok(());
+    }
+    Self::insert_batch(weibo_db_rb_conn, &hot_timeline_comm_ent_arrs, 50).await.map(|_| ()).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+}
+
+/// 话题或提及的每日出现次数
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboHotTimelineEntCnt {
+  // 话题或提及
+  pub ent: String,
+  // 发布的日期，格式为YYYY-MM-DD
+  pub occur_era: String,
+  // 当天提到的热门推荐数
+  pub timeline_cnt: u32,
+  // 当天提到的评论数
+  pub comm_cnt: u32,
+}
+
+impl From<WeiboHotTimelineEntCnt> for JsonValue {
+  fn from(weibo_hot_timeline_ent_cnt: WeiboHotTimelineEntCnt) -> Self {
+    object! {
+      ent: weibo_hot_timeline_ent_cnt.ent,
+      occur_era: weibo_hot_timeline_ent_cnt.occur_era,
+      timeline_cnt: weibo_hot_timeline_ent_cnt.timeline_cnt,
+      comm_cnt: weibo_hot_timeline_ent_cnt.comm_cnt
+    }
+  }
+}
+
+impl WeiboHotTimelineEntCnt {
+  /// 按天统计话题或提及在热门推荐与评论中出现的次数，按日期升序、次数降序排列
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `ent_ilk`: 实体类型
+  /// - `weibo_ent`: 话题或提及，不含`#`与`@`，可选
+  /// - `occur_span`: 发布的日期范围，只比较日期部分
+  ///
+  /// ## 返回
+  /// 成功则返回每个话题或提及每天的出现次数
+  pub async fn weibo_hot_timeline_ent_cnt_r(
+    weibo_db_rb_conn: &RBatis, ent_ilk: WeiboEntIlk, weibo_ent: Option<String>,
+    occur_span: &WeiboEraSpan) -> Result<Vec<Self>, WeiboError> {
+    let occur_span = occur_span.era_span_date();
+    let mut weibo_ent_cnt_pars = vec![];
+
+    let mut timeline_ent_sent =
+      "select e.ent, t.occur_era, count(1) as timeline_cnt, 0 as comm_cnt \
+       from weibo_hot_timeline_ent e join weibo_hot_timeline t on t.mid = e.mid \
+       where e.ent_ilk = ?".to_string();
+    weibo_ent_cnt_pars.push(rbs::value!(ent_ilk.ent_ilk()));
+    if let Some(weibo_ent) = &weibo_ent {
+      timeline_ent_sent.push_str(" and e.ent = ?");
+      weibo_ent_cnt_pars.push(rbs::value!(weibo_ent.clone()));
+    }
+    occur_span.era_span_sent(&mut timeline_ent_sent, &mut weibo_ent_cnt_pars, "t.occur_era");
+    timeline_ent_sent.push_str(" group by e.ent, t.occur_era");
+
+    let mut comm_ent_sent =
+      "select e.ent, c.comm_era as occur_era, 0 as timeline_cnt, count(1) as comm_cnt \
+       from weibo_hot_timeline_comm_ent e join weibo_hot_timeline_comm c \
+         on c.mid = e.mid and c.comm_mid = e.comm_mid \
+       where e.ent_ilk = ?".to_string();
+    weibo_ent_cnt_pars.push(rbs::value!(ent_ilk.ent_ilk()));
+    if let Some(weibo_ent) = weibo_ent {
+      comm_ent_sent.push_str(" and e.ent = ?");
+      weibo_ent_cnt_pars.push(rbs::value!(weibo_ent));
+    }
+    occur_span.era_span_sent(&mut comm_ent_sent, &mut weibo_ent_cnt_pars, "c.comm_era");
+    comm_ent_sent.push_str(" group by e.ent, c.comm_era");
+
+    let weibo_ent_cnt_sent = format!(
+      "select ent, occur_era, sum(timeline_cnt) as timeline_cnt, sum(comm_cnt) as comm_cnt \
+       from ({timeline_ent_sent} union all {comm_ent_sent}) \
+       group by ent, occur_era \
+       order by occur_era, sum(timeline_cnt) + sum(comm_cnt) desc, ent");
+
+    weibo_db_rb_conn.query_decode(&weibo_ent_cnt_sent, weibo_ent_cnt_pars).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+}
+
+/// 关键词监控的关键词
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboKeyword {
+  pub id: Option<usize>,
+  // 关键词所属的列表
+  pub keyword_list: String,
+  // 关键词，按keyword_fold_*配置规范化后匹配
+  pub keyword: String,
+  // 加入的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub add_era: String,
+}
+rbatis::crud!(WeiboKeyword {}, "weibo_keyword");
+
+impl From<WeiboKeyword> for JsonValue {
+  fn from(weibo_keyword: WeiboKeyword) -> Self {
+    object! {
+      id: weibo_keyword.id,
+      keyword_list: weibo_keyword.keyword_list,
+      keyword: weibo_keyword.keyword,
+      add_era: weibo_keyword.add_era
+    }
+  }
+}
+
+impl WeiboKeyword {
+  /// 获取关键词监控的WeiboKeyword对象
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `keyword_list`: 关键词所属的列表，可选
+  ///
+  /// ## 返回
+  /// 成功则返回符合查询条件的关键词
+  pub async fn weibo_keyword_r(weibo_db_rb_conn: &RBatis,
+                               keyword_list: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_keyword_r_qry = rbs::value! {};
+    if let Some(keyword_list) = keyword_list {
+      weibo_keyword_r_qry.insert(rbs::value!("keyword_list"), rbs::value!(keyword_list));
+    }
+
+    Self::select_by_map(weibo_db_rb_conn, weibo_keyword_r_qry).await.map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+
+  /// 将关键词加入列表，列表中已有的关键词不做处理。加入后需要重新编译匹配器
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `keyword_list`: 关键词所属的列表
+  /// - `keyword_arrs`: 新的关键词
+  /// - `add_era`: 加入的时间
+  pub async fn weibo_keyword_u(weibo_db_rb_conn: &RBatis, keyword_list: &str,
+                               keyword_arrs: &[String], add_era: &str) -> Result<(), WeiboError> {
+    for keyword_chks in keyword_arrs.chunks(prefs().db_upsert_chk) {
+      let mut weibo_keyword_pars = vec![];
+      for keyword in keyword_chks.iter() {
+        weibo_keyword_pars.push(rbs::value!(keyword_list));
+        weibo_keyword_pars.push(rbs::value!(keyword.clone()));
+        weibo_keyword_pars.push(rbs::value!(add_era));
+      }
+      let weibo_keyword_sent = format!(
+        "insert or ignore into weibo_keyword (keyword_list, keyword, add_era) values {}",
+        vec!["(?, ?, ?)"; keyword_chks.len()].join(", "));
+      weibo_db_rb_conn.exec(&weibo_keyword_sent, weibo_keyword_pars).await.map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    }
+    Ok(())
+  }
+
+  /// 从列表中删除关键词，已记录的命中保留。删除后需要重新编译匹配器
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `keyword_list`: 关键词所属的列表
+  /// - `keyword`: 关键词，未提供时删除整个列表
+  ///
+  /// ## 返回
+  /// 删除的关键词数量，没有删除任何关键词时返回错误
+  pub async fn weibo_keyword_d(weibo_db_rb_conn: &RBatis, keyword_list: String,
+                               keyword: Option<String>) -> Result<u64, WeiboError> {
+    let mut weibo_keyword_d_qry = rbs::value! {"keyword_list": keyword_list};
+    if let Some(keyword) = keyword {
+      weibo_keyword_d_qry.insert(rbs::value!("keyword"), rbs::value!(keyword));
+    }
+
+    let weibo_keyword_ext = Self::delete_by_map(weibo_db_rb_conn, weibo_keyword_d_qry).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    if weibo_keyword_ext.rows_affected == 0 {
+      return Err(WeiboError::RbatisError("no keyword matches the condition".to_string()));
+    }
+    Ok(weibo_keyword_ext.rows_affected)
+  }
+}
+
+/// 热搜标题、热门推荐或评论中命中的关键词
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboKeywordHit {
+  pub id: Option<usize>,
+  // 命中的关键词
+  pub keyword: String,
+  // 命中的文本类型：hot_search、timeline、comm
+  pub hit_ilk: String,
+  // 命中的文本：热搜为标题，热门推荐为mid，评论为评论的mid
+  pub hit_key: String,
+  // 热门推荐的mid，热搜为空
+  pub mid: String,
+  // 命中位置在原文中的起始字节偏移
+  pub hit_start: usize,
+  // 命中位置在原文中的结束字节偏移，不含
+  pub hit_finish: usize,
+  // 命中位置在原文中的起始字符偏移，迁移前原文已删除的命中为-1
+  pub hit_char_start: i64,
+  // 命中位置在原文中的结束字符偏移，不含
+  pub hit_char_finish: i64,
+  // 第一次命中的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub hit_era: String,
+}
+rbatis::crud!(WeiboKeywordHit {}, "weibo_keyword_hit");
+
+impl From<WeiboKeywordHit> for JsonValue {
+  fn from(weibo_keyword_hit: WeiboKeywordHit) -> Self {
+    object! {
+      id: weibo_keyword_hit.id,
+      keyword: weibo_keyword_hit.keyword,
+      hit_ilk: weibo_keyword_hit.hit_ilk,
+      hit_key: weibo_keyword_hit.hit_key,
+      mid: weibo_keyword_hit.mid,
+      hit_start: weibo_keyword_hit.hit_start,
+      hit_finish: weibo_keyword_hit.hit_finish,
+      hit_char_start: weibo_keyword_hit.hit_char_start,
+      hit_char_finish: weibo_keyword_hit.hit_char_finish,
+      hit_era: weibo_keyword_hit.hit_era
+    }
+  }
+}
+
+impl WeiboKeywordHit {
+  /// 创建一个关键词命中WeiboKeywordHit对象
+  ///
+  /// ## 参数
+  /// - `keyword`: 命中的关键词
+  /// - `hit_ilk`: 命中的文本类型
+  /// - `hit_key`: 命中的文本：热搜为标题，热门推荐为mid，评论为评论的mid
+  /// - `timeline_mid`: 热门推荐的mid，热搜为空
+  /// - `hit_site`: 命中位置的字节偏移与字符偏移
+  /// - `hit_era`: 命中的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub fn weibo_keyword_hit_c(keyword: String, hit_ilk: WeiboHitIlk, hit_key: String,
+                             timeline_mid: String, hit_site: WmSite, hit_era: String) -> Self {
+    Self {
+      id: None,
+      keyword,
+      hit_ilk: hit_ilk.hit_ilk().to_string(),
+      hit_key,
+      mid: timeline_mid,
+      hit_start: hit_site.byte_start,
+      hit_finish: hit_site.byte_finish,
+      hit_char_start: hit_site.char_start as i64,
+      hit_char_finish: hit_site.char_finish as i64,
+      hit_era,
+    }
+  }
+
+  /// 获取关键词命中WeiboKeywordHit对象，按命中时间降序排列
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `keyword`: 命中的关键词，可选
+  /// - `keyword_list`: 只返回当前属于该列表的关键词的命中，可选
+  /// - `hit_ilk`: 命中的文本类型，可选
+  /// - `timeline_mid`: 热门推荐的mid，同时返回正文与评论中的命中，可选
+  /// - `hit_span`: 命中的时间范围
+  ///
+  /// ## 返回
+  /// 成功则返回符合查询条件的关键词命中
+  pub async fn weibo_keyword_hit_r(
+    weibo_db_rb_conn: &RBatis, keyword: Option<String>, keyword_list: Option<String>,
+    hit_ilk: Option<WeiboHitIlk>, timeline_mid: Option<String>,
+    hit_span: &WeiboEraSpan) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_keyword_hit_sent = "select * from weibo_keyword_hit where 1 = 1".to_string();
+    let mut weibo_keyword_hit_pars = vec![];
+    if let Some(keyword) = keyword {
+      weibo_keyword_hit_sent.push_str(" and keyword = ?");
+      weibo_keyword_hit_pars.push(rbs::value!(keyword));
+    }
+    if let Some(keyword_list) = keyword_list {
+      weibo_keyword_hit_sent.push_str(
+        " and keyword in (select keyword from weibo_keyword where keyword_list = ?)");
+      weibo_keyword_hit_pars.push(rbs::value!(keyword_list));
+    }
+    if let Some(hit_ilk) = hit_ilk {
+      weibo_keyword_hit_sent.push_str(" and hit_ilk = ?");
+      weibo_keyword_hit_pars.push(rbs::value!(hit_ilk.hit_ilk()));
+    }
+    if let Some(timeline_mid) = timeline_mid {
+      weibo_keyword_hit_sent.push_str(" and mid = ?");
+      weibo_keyword_hit_pars.push(rbs::value!(timeline_mid));
+    }
+    hit_span.era_span_sent(&mut weibo_keyword_hit_sent, &mut weibo_keyword_hit_pars, "hit_era");
+    weibo_keyword_hit_sent.push_str(" order by hit_era desc, id desc");
+
+    weibo_db_rb_conn.query_decode(&weibo_keyword_hit_sent, weibo_keyword_hit_pars).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 插入关键词命中WeiboKeywordHit数据。同一文本同一位置的命中已存在时不做处理，
+  /// 重复爬取到的热搜与热门推荐只保留第一次命中的时间
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `keyword_hit_arrs`: 新的关键词命中数据
+  pub async fn weibo_keyword_hit_u(
+    weibo_db_rb_conn: &RBatis, keyword_hit_arrs: Vec<Self>) -> Result<(), WeiboError> {
+    for keyword_hit_chks in keyword_hit_arrs.chunks(prefs().db_upsert_chk) {
+      let mut weibo_keyword_hit_pars = vec![];
+      for keyword_hit_arri in keyword_hit_chks.iter() {
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.keyword.clone()));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_ilk.clone()));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_key.clone()));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.mid.clone()));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_start));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_finish));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_char_start));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_char_finish));
+        weibo_keyword_hit_pars.push(rbs::value!(keyword_hit_arri.hit_era.clone()));
+      }
+      let weibo_keyword_hit_sent = format!(
+        "insert or ignore into weibo_keyword_hit \
+           (keyword, hit_ilk, hit_key, mid, hit_start, hit_finish, hit_char_start, \
+            hit_char_finish, hit_era) values {}",
+        vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?)"; keyword_hit_chks.len()].join(", "));
+      weibo_db_rb_conn.exec(&weibo_keyword_hit_sent, weibo_keyword_hit_pars).await.
+        map_err(|flaw| {
+          WeiboError::RbatisError(flaw.to_string())
+        })?;
+    }
+    Ok(())
+  }
+}
+
+/// 定时爬取任务
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboSched {
+  pub id: Option<usize>,
+  // 任务名称：hot_search、hot_timeline、hot_timeline_comm
+  pub job: String,
+  // 是否启用
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub enabled: bool,
+  // 固定间隔的秒数，cron为空时生效
+  pub interval_secs: u32,
+  // cron表达式（分 时 日 月 周，按UTC计算），非空时优先于interval_secs
+  pub cron: String,
+  // 随机抖动的最大秒数
+  pub jitter_secs: u32,
+  // hot_timeline任务是否爬取图片
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub pic: bool,
+  // hot_timeline任务是否爬取评论
+  #[serde(deserialize_with = "deserialize_num2b")]
+  pub comm: bool,
+  // 上次运行的开始时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub last_era: String,
+  // 下次运行的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub next_era: String,
+  // 上次运行的状态：ok、flaw
+  pub last_sta: String,
+  // 上次运行失败的原因
+  pub last_flaw: String,
+}
+rbatis::crud!(WeiboSched {}, "weibo_sched");
+
+impl From<WeiboSched> for JsonValue {
+  fn from(weibo_sched: WeiboSched) -> Self {
+    object! {
+      id: weibo_sched.id,
+      job: weibo_sched.job,
+      enabled: weibo_sched.enabled,
+      interval_secs: weibo_sched.interval_secs,
+      cron: weibo_sched.cron,
+      jitter_secs: weibo_sched.jitter_secs,
+      pic: weibo_sched.pic,
+      comm: weibo_sched.comm,
+      last_era: weibo_sched.last_era,
+      next_era: weibo_sched.next_era,
+      last_sta: weibo_sched.last_sta,
+      last_flaw: weibo_sched.last_flaw
+    }
+  }
+}
+
+impl WeiboSched {
+  /// 获取定时爬取任务WeiboSched对象
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `sched_job`: 任务名称，可选
+  ///
+  /// ## 返回
+  /// 成功则返回符合查询条件的定时爬取任务
+  pub async fn weibo_sched_r(weibo_db_rb_conn: &RBatis,
+                             sched_job: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_sched_r_qry = rbs::value! {};
+    if let Some(sched_job) = sched_job {
+      weibo_sched_r_qry.insert(rbs::value!("job"), rbs::value!(sched_job));
+    }
+
+    Self::select_by_map(weibo_db_rb_conn, weibo_sched_r_qry).await.map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+
+  /// 更新定时爬取任务WeiboSched的配置，同时清空下次运行时间，由调度器重新计算。
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `weibo_sched`: 新的任务配置，以job为准
+  pub async fn weibo_sched_u(
+    weibo_db_rb_conn: &RBatis, weibo_sched: &WeiboSched) -> Result<(), WeiboError> {
+    let weibo_sched_sent = "update weibo_sched set \
+                              enabled = ?, interval_secs = ?, cron = ?, jitter_secs = ?, \
+                              pic = ?, comm = ?, \
+                              next_era = '' \
+                            where job = ?";
+    let weibo_sched_pars = vec![
+      rbs::value!(weibo_sched.enabled),
+      rbs::value!(weibo_sched.interval_secs),
+      rbs::value!(weibo_sched.cron.clone()),
+      rbs::value!(weibo_sched.jitter_secs),
+      rbs::value!(weibo_sched.pic),
+      rbs::value!(weibo_sched.comm),
+      rbs::value!(weibo_sched.job.clone()),
+    ];
+
+    let weibo_sched_ext = weibo_db_rb_conn.exec(weibo_sched_sent, weibo_sched_pars).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    if weibo_sched_ext.rows_affected == 0 {
+      return Err(WeiboError::RbatisError(format!("no sched job named {}", weibo_sched.job)));
+    }
+    Ok(())
+  }
+
+  /// 记录定时爬取任务WeiboSched的运行情况
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `sched_job`: 任务名称
+  /// - `last_era`: 上次运行的开始时间，为None时不更新
+  /// - `next_era`: 下次运行的时间
+  /// - `last_sta`: 上次运行的状态，为None时不更新
+  /// - `last_flaw`: 上次运行失败的原因，为None时不更新
+  pub async fn weibo_sched_era_u(
+    weibo_db_rb_conn: &RBatis, sched_job: &str, last_era: Option<&str>, next_era: &str,
+    last_sta: Option<&str>, last_flaw: Option<&str>) -> Result<(), WeiboError> {
+    let weibo_sched_sent = "update weibo_sched set \
+                              last_era = coalesce(?, last_era), next_era = ?, \
+                              last_sta = coalesce(?, last_sta), \
+                              last_flaw = coalesce(?, last_flaw) \
+                            where job = ?";
+    let weibo_sched_pars = vec![
+      rbs::value!(last_era),
+      rbs::value!(next_era),
+      rbs::value!(last_sta),
+      rbs::value!(last_flaw),
+      rbs::value!(sched_job),
+    ];
+
+    weibo_db_rb_conn.exec(weibo_sched_sent, weibo_sched_pars).await.map(|_| ()).map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+}
+
 fn deserialize_num2b<'de, D>(deserializer: D) -> Result<bool, D::Error>
                              where
                                D: Deserializer<'de>,
@@ -632,3 +2146,258 @@ fn deserialize_num2b<'de, D>(deserializer: D) -> Result<bool, D::Error>
   let reply_num = i32::deserialize(deserializer)?;
   Ok(reply_num != 0)
 }
+
+/// cookie池中的cookie
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WeiboCok {
+  pub id: Option<usize>,
+  // cookie内容
+  pub cok: String,
+  // 备注
+  pub label: String,
+  // 状态：active、quarantined、retired
+  pub sta: String,
+  // 被选中使用的次数
+  pub use_cnt: u32,
+  // 被判定为失效或被封禁的次数
+  pub flaw_cnt: u32,
+  // 最近一次被判定为失效或被封禁的原因
+  pub last_flaw: String,
+  // 最近一次被选中的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub used_era: String,
+  // 隔离截止的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub until_era: String,
+  // 加入cookie池的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+  pub add_era: String,
+}
+rbatis::crud!(WeiboCok {}, "weibo_cok");
+
+impl From<WeiboCok> for JsonValue {
+  fn from(weibo_cok: WeiboCok) -> Self {
+    // cookie内容只返回开头的部分，避免通过接口泄露
+    let cok_mask: String = weibo_cok.cok.chars().take(12).collect::<String>() + "…";
+    object! {
+      id: weibo_cok.id,
+      cok: cok_mask,
+      label: weibo_cok.label,
+      sta: weibo_cok.sta,
+      use_cnt: weibo_cok.use_cnt,
+      flaw_cnt: weibo_cok.flaw_cnt,
+      last_flaw: weibo_cok.last_flaw,
+      used_era: weibo_cok.used_era,
+      until_era: weibo_cok.until_era,
+      add_era: weibo_cok.add_era
+    }
+  }
+}
+
+impl WeiboCok {
+  /// 获取cookie池中的WeiboCok对象
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `cok_sta`: cookie状态，可选
+  ///
+  /// ## 返回
+  /// 成功则返回符合查询条件的cookie
+  pub async fn weibo_cok_r(weibo_db_rb_conn: &RBatis,
+                           cok_sta: Option<String>) -> Result<Vec<Self>, WeiboError> {
+    let mut weibo_cok_r_qry = rbs::value! {};
+    if let Some(cok_sta) = cok_sta {
+      weibo_cok_r_qry.insert(rbs::value!("sta"), rbs::value!(cok_sta));
+    }
+
+    Self::select_by_map(weibo_db_rb_conn, weibo_cok_r_qry).await.map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+
+  /// 将cookie加入cookie池；已存在的cookie更新备注并重新启用。
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `cok`: cookie内容
+  /// - `cok_label`: 备注
+  /// - `add_era`: 加入的时间
+  pub async fn weibo_cok_u(weibo_db_rb_conn: &RBatis, cok: &str, cok_label: &str,
+                           add_era: &str) -> Result<(), WeiboError> {
+    let weibo_cok_sent = "insert into weibo_cok (cok, label, add_era) values (?, ?, ?) \
+                          on conflict(cok) do update set \
+                            label = excluded.label, sta = 'active', until_era = ''";
+    let weibo_cok_pars = vec![rbs::value!(cok), rbs::value!(cok_label), rbs::value!(add_era)];
+
+    weibo_db_rb_conn.exec(weibo_cok_sent, weibo_cok_pars).await.map(|_| ()).map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+
+  /// 选取最久未使用的可用cookie，并记录本次使用。隔离已到期的cookie也可被选中，
+  /// 请求成功后才恢复为active。
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `used_era`: 本次使用的时间
+  ///
+  /// ## 返回
+  /// 成功则返回选中的cookie，cookie池中没有可用cookie时返回None
+  pub async fn weibo_cok_pick(weibo_db_rb_conn: &RBatis,
+                              used_era: &str) -> Result<Option<Self>, WeiboError> {
+    // 选取与更新在同一条语句中完成，并发请求不会拿到同一个cookie
+    let weibo_cok_sent = "update weibo_cok set used_era = ?, use_cnt = use_cnt + 1 \
+                          where id = (select id from weibo_cok \
+                                      where sta = 'active' or \
+                                        (sta = 'quarantined' and until_era <= ?) \
+                                      order by used_era, id limit 1) \
+                          returning *";
+    let weibo_cok_arrs: Vec<Self> = weibo_db_rb_conn.query_decode(
+      weibo_cok_sent, vec![rbs::value!(used_era), rbs::value!(used_era)]).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    Ok(weibo_cok_arrs.into_iter().next())
+  }
+
+  /// 请求成功后将隔离到期的cookie恢复为active
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `cok_id`: cookie的id
+  pub async fn weibo_cok_suc_u(weibo_db_rb_conn: &RBatis,
+                               cok_id: usize) -> Result<(), WeiboError> {
+    let weibo_cok_sent = "update weibo_cok set sta = 'active', until_era = '' \
+                          where id = ? and sta = 'quarantined'";
+
+    weibo_db_rb_conn.exec(weibo_cok_sent, vec![rbs::value!(cok_id)]).await.map(|_| ()).
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })
+  }
+
+  /// 隔离失效或被封禁的cookie，隔离到期前不会再被选中
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `cok_id`: cookie的id
+  /// - `until_era`: 隔离截止的时间
+  /// - `last_flaw`: 被判定为失效或被封禁的原因
+  pub async fn weibo_cok_flaw_u(weibo_db_rb_conn: &RBatis, cok_id: usize, until_era: &str,
+                                last_flaw: &str) -> Result<(), WeiboError> {
+    let weibo_cok_sent = "update weibo_cok set \
+                            sta = 'quarantined', until_era = ?, \
+                            flaw_cnt = flaw_cnt + 1, last_flaw = ? \
+                          where id = ? and sta != 'retired'";
+    let weibo_cok_pars = vec![rbs::value!(until_era), rbs::value!(last_flaw), rbs::value!(cok_id)];
+
+    weibo_db_rb_conn.exec(weibo_cok_sent, weibo_cok_pars).await.map(|_| ()).map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })
+  }
+
+  /// 停用cookie，停用后不会再被选中，记录保留以便追溯
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  /// - `cok_id`: cookie的id
+  pub async fn weibo_cok_d(weibo_db_rb_conn: &RBatis, cok_id: usize) -> Result<(), WeiboError> {
+    let weibo_cok_ext = weibo_db_rb_conn.exec(
+      "update weibo_cok set sta = 'retired', until_era = '' where id = ?",
+      vec![rbs::value!(cok_id)]).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    if weibo_cok_ext.rows_affected == 0 {
+      return Err(WeiboError::RbatisError(format!("no cookie with id {}", cok_id)));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use rbdc_sqlite::SqliteConnectOptions;
+  use rbdc_sqlite::SqliteDriver;
+  use crate::ents::anly_text_ents;
+  use crate::migr::migr_apply;
+  use super::*;
+
+  /// 在临时目录中创建已迁移的数据库。关闭外键约束，删除时不依赖级联删除
+  ///
+  /// ## 参数
+  /// - `db_name`：数据库文件名的一部分，各测试使用不同的名称
+  pub(crate) async fn dbs_test_conn(db_name: &str) -> RBatis {
+    let weibo_db_rb_conn = dbs_test_raw_conn(db_name);
+    migr_apply(&weibo_db_rb_conn).await.unwrap();
+    weibo_db_rb_conn
+  }
+
+  /// 未执行迁移的临时数据库
+  pub(crate) fn dbs_test_raw_conn(db_name: &str) -> RBatis {
+    let db_pth = std::env::temp_dir().
+      join(format!("rs-salvo-{}-{}.db", db_name, std::process::id()));
+    for db_sfx in ["", "-wal", "-shm"] {
+      let _ = std::fs::remove_file(format!("{}{}", db_pth.display(), db_sfx));
+    }
+    let weibo_db_rb_conn = RBatis::new();
+    weibo_db_rb_conn.init_option::<SqliteDriver, SqliteConnectOptions, rbatis::DefaultPool>(
+      SqliteDriver {},
+      SqliteConnectOptions::new().filename(&db_pth).create_if_missing(true).foreign_keys(false)).
+      unwrap();
+    weibo_db_rb_conn
+  }
+
+  /// 插入只有必填字段的热门推荐
+  pub(crate) async fn dbs_test_timeline(weibo_db_rb_conn: &RBatis, timeline_mid: &str,
+                                        timeline_text: &str) {
+    weibo_db_rb_conn.exec(
+      "insert into weibo_hot_timeline (mid, mblogid, text, mem_id, mem_name, occur_era) \
+       values (?, ?, ?, 'u1', 'n', '2026-10-18')",
+      vec![rbs::value!(timeline_mid), rbs::value!(timeline_mid), rbs::value!(timeline_text)]).
+      await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn weibo_hot_timeline_d_removes_ents() {
+    let weibo_db_rb_conn = dbs_test_conn("timeline-ent-d").await;
+    let fk_flags: Vec<HashMap<String, i64>> =
+      weibo_db_rb_conn.query_decode("pragma foreign_keys", vec![]).await.unwrap();
+    assert_eq!(fk_flags[0]["foreign_keys"], 0);
+
+    for (timeline_mid, comm_mid) in [("m1", "c1"), ("m2", "c2")] {
+      dbs_test_timeline(&weibo_db_rb_conn, timeline_mid, "#热点# @张三").await;
+      weibo_db_rb_conn.exec(
+        "insert into weibo_hot_timeline_comm \
+         (mid, comm_mid, text, mem_id, mem_name, comm_era, reply, senior_id) \
+         values (?, ?, '#热点#', 'u9', 'c', '2026-10-18', 0, ?)",
+        vec![rbs::value!(timeline_mid), rbs::value!(comm_mid), rbs::value!(comm_mid)]).
+        await.unwrap();
+      let timeline_ent_arrs = anly_text_ents("#热点# @张三").into_iter().
+        map(|weibo_ent| WeiboHotTimelineEnt::weibo_hot_timeline_ent_c(
+          timeline_mid.to_string(), weibo_ent)).
+        collect();
+      WeiboHotTimelineEnt::weibo_hot_timeline_ent_u(
+        &weibo_db_rb_conn, &vec![timeline_mid.to_string()], timeline_ent_arrs).await.unwrap();
+      let comm_ent_arrs = anly_text_ents("#热点#").into_iter().
+        map(|weibo_ent| WeiboHotTimelineCommEnt::weibo_hot_timeline_comm_ent_c(
+          timeline_mid.to_string(), comm_mid.to_string(), weibo_ent)).
+        collect();
+      WeiboHotTimelineCommEnt::weibo_hot_timeline_comm_ent_u(
+        &weibo_db_rb_conn, &[(timeline_mid.to_string(), comm_mid.to_string())], comm_ent_arrs).
+        await.unwrap();
+    }
+
+    WeiboHotTimeline::weibo_hot_timeline_d(
+      &weibo_db_rb_conn, false, Some("m1".to_string()), None, None, None).await.unwrap();
+    WeiboHotTimelineComm::weibo_hot_timeline_comm_d(
+      &weibo_db_rb_conn, false, Some("m1".to_string()), Some("c1".to_string()), None).
+      await.unwrap();
+
+    let timeline_ent_mids: Vec<String> = WeiboHotTimelineEnt::select_all(&weibo_db_rb_conn).
+      await.unwrap().into_iter().map(|timeline_ent| timeline_ent.mid).collect();
+    assert_eq!(timeline_ent_mids, ["m2", "m2"]);
+    let comm_ent_keys: Vec<(String, String)> =
+      WeiboHotTimelineCommEnt::select_all(&weibo_db_rb_conn).await.unwrap().into_iter().
+        map(|comm_ent| (comm_ent.mid, comm_ent.comm_mid)).
+        collect();
+    assert_eq!(comm_ent_keys, [("m2".to_string(), "c2".to_string())]);
+  }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+/// 话题的最大字符数，超过时视为两个不相关的`#`
+const TOPIC_CHAR_MAX: usize = 64;
+/// 微博昵称的最大字符数
+const MENTION_CHAR_MAX: usize = 30;
+/// 链接末尾不属于链接的标点
+const URL_TAIL_CHARS: [char; 13] =
+  ['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'', '"', '<', '>'];
+
+/// 正文中的实体类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WeiboEntIlk {
+  // #话题#
+  Topic,
+  // @用户
+  Mention,
+  // 链接，包括t.cn短链接
+  Url,
+}
+
+impl WeiboEntIlk {
+  /// 数据库中存储的类型名称
+  pub fn ent_ilk(&self) -> &'static str {
+    match self {
+      WeiboEntIlk::Topic => "topic",
+      WeiboEntIlk::Mention => "mention",
+      WeiboEntIlk::Url => "url",
+    }
+  }
+}
+
+/// 从正文中提取的实体
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WeiboEnt {
+  // 实体类型
+  pub ilk: WeiboEntIlk,
+  // 话题不含`#`，提及不含`@`，链接保留原文，不带协议的t.cn短链接补全为http://
+  pub ent: String,
+}
+
+/// 从微博正文或评论的text_raw中提取话题、提及与链接
+///
+/// ## 参数
+/// - `text`：text_raw
+///
+/// ## 返回
+/// 按出现顺序排列的实体，相同的实体只保留第一个
+pub fn anly_text_ents(text: &str) -> Vec<WeiboEnt> {
+  let mut ent_arrs = vec![];
+  let mut ent_sets: HashSet<WeiboEnt> = HashSet::new();
+  let mut text_pos = 0;
+  let mut prev_char: Option<char> = None;
+
+  while let Some(text_char) = text[text_pos..].chars().next() {
+    let text_rest = &text[text_pos..];
+    let ent_found = match text_char {
+      '#' => anly_topic(text_rest),
+      '@' if !prev_char.is_some_and(|prev_char| prev_char.is_ascii_alphanumeric()) => {
+        anly_mention(text_rest)
+      }
+      'h' | 'H' | 't' | 'T' if !prev_char.is_some_and(anly_url_char) => anly_url(text_rest),
+      _ => None,
+    };
+
+    match ent_found {
+      Some((weibo_ent, ent_len)) => {
+        if ent_sets.insert(weibo_ent.clone()) {
+          ent_arrs.push(weibo_ent);
+        }
+        prev_char = text[..text_pos + ent_len].chars().next_back();
+        text_pos += ent_len;
+      }
+      None => {
+        prev_char = Some(text_char);
+        text_pos += text_char.len_utf8();
+      }
+    }
+  }
+
+  ent_arrs
+}
+
+/// 解析以`#`开头的话题，话题不能跨行，也不能为空
+///
+/// ## 返回
+/// 话题与其在原文中占用的字节数
+fn anly_topic(text_rest: &str) -> Option<(WeiboEnt, usize)> {
+  let topic_len = text_rest[1..].find('#')?;
+  let topic = &text_rest[1..1 + topic_len];
+  if topic.trim().is_empty() || topic.contains(['\n', '\r']) ||
+    topic.chars().count() > TOPIC_CHAR_MAX {
+    return None;
+  }
+  Some((WeiboEnt { ilk: WeiboEntIlk::Topic, ent: topic.trim().to_string() }, topic_len + 2))
+}
+
+/// 解析以`@`开头的提及，昵称由文字、数字、`_`与`-`组成
+///
+/// ## 返回
+/// 昵称与其在原文中占用的字节数
+fn anly_mention(text_rest: &str) -> Option<(WeiboEnt, usize)> {
+  let mention: String = text_rest[1..].chars().
+    take_while(|mention_char| {
+      mention_char.is_alphanumeric() || *mention_char == '_' || *mention_char == '-'
+    }).
+    take(MENTION_CHAR_MAX).
+    collect();
+  if mention.is_empty() {
+    return None;
+  }
+  let mention_len = mention.len() + 1;
+  Some((WeiboEnt { ilk: WeiboEntIlk::Mention, ent: mention }, mention_len))
+}
+
+/// 解析以`http://`、`https://`或`t.cn/`开头的链接，链接在空白、非ASCII字符处结束，
+/// 末尾的标点不属于链接
+///
+/// ## 返回
+/// 链接与其在原文中占用的字节数
+fn anly_url(text_rest: &str) -> Option<(WeiboEnt, usize)> {
+  let url_head = text_rest.as_bytes()[..text_rest.len().min(8)].to_ascii_lowercase();
+  let url_scheme = if url_head.starts_with(b"http://") || url_head.starts_with(b"https://") {
+    true
+  } else if url_head.starts_with(b"t.cn/") {
+    false
+  } else {
+    return None;
+  };
+
+  let url_len = text_rest.find(|url_char: char| !url_char.is_ascii_graphic()).
+    unwrap_or(text_rest.len());
+  let url = text_rest[..url_len].trim_end_matches(URL_TAIL_CHARS);
+  let url_host = url.split_once("://").map_or(url, |(_, url_host)| url_host);
+  if url_host.is_empty() || url_host.ends_with("t.cn/") {
+    return None;
+  }
+
+  let url_ent = if url_scheme { url.to_string() } else { format!("http://{url}") };
+  Some((WeiboEnt { ilk: WeiboEntIlk::Url, ent: url_ent }, url.len()))
+}
+
+/// 可能是链接一部分的字符，出现在`h`或`t`之前时不从此处开始解析链接
+fn anly_url_char(url_char: char) -> bool {
+  url_char.is_ascii_alphanumeric() || matches!(url_char, '.' | '/' | '-' | '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ent_pairs(text: &str) -> Vec<(WeiboEntIlk, String)> {
+    anly_text_ents(text).into_iter().map(|weibo_ent| (weibo_ent.ilk, weibo_ent.ent)).collect()
+  }
+
+  #[test]
+  fn anly_text_ents_topics() {
+    assert_eq!(ent_pairs("#今日热点# 转发 # 空白 # #跨\n行# ##"), [
+      (WeiboEntIlk::Topic, "今日热点".to_string()),
+      (WeiboEntIlk::Topic, "空白".to_string()),
+    ]);
+    // 重复的话题只保留第一个
+    assert_eq!(ent_pairs("#热点#和#热点#"), [(WeiboEntIlk::Topic, "热点".to_string())]);
+  }
+
+  #[test]
+  fn anly_text_ents_mentions_before_cjk_punct() {
+    assert_eq!(ent_pairs("回复@张三_zs：好的，@李四。@王五、@赵六」a@b.com"), [
+      (WeiboEntIlk::Mention, "张三_zs".to_string()),
+      (WeiboEntIlk::Mention, "李四".to_string()),
+      (WeiboEntIlk::Mention, "王五".to_string()),
+      (WeiboEntIlk::Mention, "赵六".to_string()),
+    ]);
+    assert_eq!(ent_pairs("@ 空格"), []);
+  }
+
+  #[test]
+  fn anly_text_ents_urls() {
+    assert_eq!(ent_pairs("看看http://t.cn/A6abcD。链接t.cn/XyZ12,以及(https://weibo.com/a?b=1) \
+                          HTTPS://Example.com/ http:// xt.cn/no"), [
+      (WeiboEntIlk::Url, "http://t.cn/A6abcD".to_string()),
+      (WeiboEntIlk::Url, "http://t.cn/XyZ12".to_string()),
+      (WeiboEntIlk::Url, "https://weibo.com/a?b=1".to_string()),
+      (WeiboEntIlk::Url, "HTTPS://Example.com/".to_string()),
+    ]);
+  }
+
+  #[test]
+  fn anly_text_ents_empty() {
+    assert_eq!(ent_pairs(""), []);
+    assert_eq!(ent_pairs("没有实体的文本"), []);
+  }
+}
@@ -3,11 +3,16 @@ use std::fmt;
 use salvo::http::ParseError;
 
 #[derive(fmt::Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum WeiboError {
   NyquestError(String),
   JzonError(String),
   SalvoError(String),
   RbatisError(String),
+  SchedError(String),
+  MigrError(String),
+  PrefsError(String),
+  IoError(String),
 }
 
 impl fmt::Display for WeiboError {
@@ -17,6 +22,10 @@ impl fmt::Display for WeiboError {
       WeiboError::JzonError(err) => write!(f, "JzonError: {}", err),
       WeiboError::SalvoError(err) => write!(f, "SalvoError: {}", err),
       WeiboError::RbatisError(err) => write!(f, "RbatisError: {}", err),
+      WeiboError::SchedError(err) => write!(f, "SchedError: {}", err),
+      WeiboError::MigrError(err) => write!(f, "MigrError: {}", err),
+      WeiboError::PrefsError(err) => write!(f, "PrefsError: {}", err),
+      WeiboError::IoError(err) => write!(f, "IoError: {}", err),
     }
   }
 }
@@ -25,6 +34,10 @@ impl Error for WeiboError {}
 
 impl From<nyquest::Error> for WeiboError {
   fn from(err: nyquest::Error) -> Self {
+    // IO错误的Display不含原因，TLS校验失败等信息在内层错误中
+    if let nyquest::Error::Io(io_err) = &err {
+      return WeiboError::NyquestError(format!("{}: {}", err, io_err));
+    }
     WeiboError::NyquestError(err.to_string())
   }
 }
@@ -66,3 +79,31 @@ macro_rules! weibo_jzon_err {
     WeiboError::JzonError($msg.to_string())
   };
 }
+
+#[macro_export]
+macro_rules! weibo_sched_err {
+  ($msg:expr) => {
+    WeiboError::SchedError($msg.to_string())
+  };
+}
+
+#[macro_export]
+macro_rules! weibo_migr_err {
+  ($msg:expr) => {
+    WeiboError::MigrError($msg.to_string())
+  };
+}
+
+#[macro_export]
+macro_rules! weibo_prefs_err {
+  ($msg:expr) => {
+    WeiboError::PrefsError($msg.to_string())
+  };
+}
+
+#[macro_export]
+macro_rules! weibo_io_err {
+  ($msg:expr) => {
+    WeiboError::IoError($msg.to_string())
+  };
+}
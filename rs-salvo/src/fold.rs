@@ -0,0 +1,211 @@
+/// 匹配前对模式与原文的规范化选项，模式与原文使用相同的选项规范化。
+///
+/// 规范化后的每个字符都记录其在原文中的字符位置，命中位置据此换算回原文。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WmFold {
+  // 大小写折叠，ASCII与其他Unicode字母统一为小写
+  pub case: bool,
+  // 全角折叠，全角ASCII字符与全角空格统一为半角
+  pub width: bool,
+  // 繁简折叠，常用繁体字统一为简体字
+  pub trad: bool,
+  // 删除零宽字符与插在文字之间的空白、分隔符号
+  pub strip: bool,
+}
+
+/// 规范化后的文本
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WmFolded {
+  // 规范化后的字符
+  pub chars: Vec<char>,
+  // 每个规范化后的字符在原文中的字符位置，大小写折叠展开的多个字符对应同一位置
+  pub char_srcs: Vec<usize>,
+}
+
+/// 零宽字符与其他不可见的格式字符
+const FOLD_ZERO_WIDTHS: [char; 9] = [
+  '\u{00AD}', '\u{180E}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{200E}', '\u{200F}',
+  '\u{2060}', '\u{FEFF}',
+];
+
+/// 插在文字之间用于规避关键词的分隔符号，全角符号在全角折叠前也会被删除
+const FOLD_SEPS: [char; 28] = [
+  '.', ',', '*', '_', '-', '|', '/', '\\', '~', '`', '\'', '"', '+', '=', '^', '#',
+  '·', '•', '・', '。', '，', '、', '．', '＊', '＿', '－', '｜', '～',
+];
+
+/// 常用繁体字到简体字的一对一映射，按繁体字的码位升序排列。
+/// 只收录一对一的字，一繁对多简的字（如乾、著）不做折叠
+const FOLD_TRADS: &[(char, char)] = &[
+  ('亂', '乱'), ('亞', '亚'), ('來', '来'), ('俠', '侠'), ('倆', '俩'), ('倉', '仓'), ('個', '个'), ('們', '们'),
+  ('倫', '伦'), ('偉', '伟'), ('側', '侧'), ('偵', '侦'), ('偽', '伪'), ('傑', '杰'), ('傘', '伞'), ('備', '备'),
+  ('傳', '传'), ('債', '债'), ('傷', '伤'), ('傾', '倾'), ('僅', '仅'), ('僑', '侨'), ('僕', '仆'), ('價', '价'),
+  ('儀', '仪'), ('億', '亿'), ('儉', '俭'), ('儘', '尽'), ('償', '偿'), ('優', '优'), ('儲', '储'), ('兌', '兑'),
+  ('兒', '儿'), ('兩', '两'), ('冊', '册'), ('凍', '冻'), ('凱', '凯'), ('別', '别'), ('刪', '删'), ('則', '则'),
+  ('剛', '刚'), ('創', '创'), ('劃', '划'), ('劇', '剧'), ('劉', '刘'), ('劍', '剑'), ('劑', '剂'), ('勁', '劲'),
+  ('動', '动'), ('務', '务'), ('勞', '劳'), ('勢', '势'), ('勵', '励'), ('勸', '劝'), ('匯', '汇'), ('區', '区'),
+  ('協', '协'), ('卻', '却'), ('厭', '厌'), ('厲', '厉'), ('參', '参'), ('叢', '丛'), ('吳', '吴'), ('呂', '吕'),
+  ('員', '员'), ('問', '问'), ('啓', '启'), ('啞', '哑'), ('啟', '启'), ('喚', '唤'), ('喪', '丧'), ('喬', '乔'),
+  ('單', '单'), ('嗆', '呛'), ('嗎', '吗'), ('嗚', '呜'), ('嘆', '叹'), ('嘔', '呕'), ('嘗', '尝'), ('嘩', '哗'),
+  ('嘯', '啸'), ('噴', '喷'), ('噸', '吨'), ('嚇', '吓'), ('嚨', '咙'), ('嚴', '严'), ('囑', '嘱'), ('國', '国'),
+  ('圍', '围'), ('園', '园'), ('圓', '圆'), ('圖', '图'), ('團', '团'), ('執', '执'), ('堅', '坚'), ('場', '场'),
+  ('塊', '块'), ('塵', '尘'), ('墊', '垫'), ('墜', '坠'), ('墳', '坟'), ('墾', '垦'), ('壇', '坛'), ('壓', '压'),
+  ('壘', '垒'), ('壞', '坏'), ('壟', '垄'), ('壩', '坝'), ('壯', '壮'), ('壺', '壶'), ('壽', '寿'), ('夠', '够'),
+  ('夢', '梦'), ('夥', '伙'), ('奪', '夺'), ('奮', '奋'), ('婦', '妇'), ('媽', '妈'), ('嫵', '妩'), ('嬌', '娇'),
+  ('孫', '孙'), ('學', '学'), ('宮', '宫'), ('寢', '寝'), ('實', '实'), ('寧', '宁'), ('審', '审'), ('寫', '写'),
+  ('寬', '宽'), ('寶', '宝'), ('將', '将'), ('專', '专'), ('尋', '寻'), ('對', '对'), ('導', '导'), ('層', '层'),
+  ('屬', '属'), ('岡', '冈'), ('島', '岛'), ('峽', '峡'), ('崗', '岗'), ('嶺', '岭'), ('嶼', '屿'), ('師', '师'),
+  ('帳', '帐'), ('帶', '带'), ('幣', '币'), ('幫', '帮'), ('幹', '干'), ('幾', '几'), ('庫', '库'), ('廁', '厕'),
+  ('廈', '厦'), ('廚', '厨'), ('廠', '厂'), ('廢', '废'), ('廣', '广'), ('廬', '庐'), ('廳', '厅'), ('張', '张'),
+  ('強', '强'), ('彈', '弹'), ('彌', '弥'), ('彎', '弯'), ('後', '后'), ('徑', '径'), ('從', '从'), ('復', '复'),
+  ('徹', '彻'), ('悅', '悦'), ('惡', '恶'), ('愛', '爱'), ('態', '态'), ('慘', '惨'), ('慣', '惯'), ('慶', '庆'),
+  ('憂', '忧'), ('憐', '怜'), ('憑', '凭'), ('憲', '宪'), ('憶', '忆'), ('應', '应'), ('懲', '惩'), ('懷', '怀'),
+  ('戀', '恋'), ('戰', '战'), ('戲', '戏'), ('戶', '户'), ('掃', '扫'), ('換', '换'), ('揮', '挥'), ('損', '损'),
+  ('搖', '摇'), ('搶', '抢'), ('撥', '拨'), ('擁', '拥'), ('擇', '择'), ('擊', '击'), ('擔', '担'), ('據', '据'),
+  ('擠', '挤'), ('擬', '拟'), ('擴', '扩'), ('擺', '摆'), ('攜', '携'), ('攝', '摄'), ('攤', '摊'), ('敗', '败'),
+  ('敘', '叙'), ('敵', '敌'), ('數', '数'), ('斷', '断'), ('於', '于'), ('時', '时'), ('晉', '晋'), ('暈', '晕'),
+  ('暫', '暂'), ('曆', '历'), ('曉', '晓'), ('書', '书'), ('會', '会'), ('東', '东'), ('條', '条'), ('棄', '弃'),
+  ('業', '业'), ('極', '极'), ('構', '构'), ('槍', '枪'), ('樂', '乐'), ('樓', '楼'), ('標', '标'), ('樣', '样'),
+  ('樹', '树'), ('橋', '桥'), ('機', '机'), ('檢', '检'), ('檯', '台'), ('櫃', '柜'), ('權', '权'), ('歡', '欢'),
+  ('歲', '岁'), ('歷', '历'), ('歸', '归'), ('殘', '残'), ('殺', '杀'), ('殼', '壳'), ('氣', '气'), ('決', '决'),
+  ('沒', '没'), ('況', '况'), ('涼', '凉'), ('淒', '凄'), ('淚', '泪'), ('淨', '净'), ('淺', '浅'), ('減', '减'),
+  ('測', '测'), ('湯', '汤'), ('準', '准'), ('溝', '沟'), ('溫', '温'), ('滅', '灭'), ('滾', '滚'), ('滿', '满'),
+  ('漢', '汉'), ('漲', '涨'), ('潑', '泼'), ('潔', '洁'), ('潛', '潜'), ('澤', '泽'), ('濃', '浓'), ('濕', '湿'),
+  ('濟', '济'), ('濤', '涛'), ('灑', '洒'), ('灣', '湾'), ('為', '为'), ('烏', '乌'), ('無', '无'), ('煙', '烟'),
+  ('熱', '热'), ('燈', '灯'), ('燒', '烧'), ('燦', '灿'), ('爐', '炉'), ('爭', '争'), ('爺', '爷'), ('爾', '尔'),
+  ('牀', '床'), ('牆', '墙'), ('犧', '牺'), ('狀', '状'), ('狹', '狭'), ('猶', '犹'), ('獄', '狱'), ('獎', '奖'),
+  ('獨', '独'), ('獲', '获'), ('獸', '兽'), ('獻', '献'), ('現', '现'), ('瑣', '琐'), ('瑪', '玛'), ('環', '环'),
+  ('產', '产'), ('甦', '苏'), ('畝', '亩'), ('畢', '毕'), ('畫', '画'), ('異', '异'), ('當', '当'), ('疊', '叠'),
+  ('瘋', '疯'), ('療', '疗'), ('癢', '痒'), ('癥', '症'), ('發', '发'), ('盜', '盗'), ('盡', '尽'), ('監', '监'),
+  ('盤', '盘'), ('盧', '卢'), ('眾', '众'), ('睜', '睁'), ('矯', '矫'), ('碩', '硕'), ('確', '确'), ('碼', '码'),
+  ('礦', '矿'), ('祕', '秘'), ('禍', '祸'), ('禪', '禅'), ('禮', '礼'), ('稅', '税'), ('種', '种'), ('稱', '称'),
+  ('穀', '谷'), ('積', '积'), ('穩', '稳'), ('窩', '窝'), ('窮', '穷'), ('竊', '窃'), ('競', '竞'), ('筆', '笔'),
+  ('節', '节'), ('範', '范'), ('築', '筑'), ('簡', '简'), ('籃', '篮'), ('糧', '粮'), ('糾', '纠'), ('紀', '纪'),
+  ('約', '约'), ('紅', '红'), ('納', '纳'), ('紙', '纸'), ('級', '级'), ('紛', '纷'), ('紡', '纺'), ('紮', '扎'),
+  ('細', '细'), ('終', '终'), ('組', '组'), ('結', '结'), ('絕', '绝'), ('給', '给'), ('統', '统'), ('絲', '丝'),
+  ('綁', '绑'), ('經', '经'), ('綠', '绿'), ('綫', '线'), ('維', '维'), ('網', '网'), ('線', '线'), ('練', '练'),
+  ('縣', '县'), ('縮', '缩'), ('總', '总'), ('績', '绩'), ('織', '织'), ('繩', '绳'), ('繪', '绘'), ('繼', '继'),
+  ('續', '续'), ('纜', '缆'), ('罰', '罚'), ('罷', '罢'), ('羅', '罗'), ('義', '义'), ('習', '习'), ('聖', '圣'),
+  ('聞', '闻'), ('聯', '联'), ('聲', '声'), ('聳', '耸'), ('職', '职'), ('聽', '听'), ('肅', '肃'), ('脅', '胁'),
+  ('脫', '脱'), ('腦', '脑'), ('腳', '脚'), ('膚', '肤'), ('膽', '胆'), ('臉', '脸'), ('臥', '卧'), ('臨', '临'),
+  ('臺', '台'), ('與', '与'), ('興', '兴'), ('舉', '举'), ('舊', '旧'), ('艦', '舰'), ('艱', '艰'), ('莊', '庄'),
+  ('華', '华'), ('萬', '万'), ('葉', '叶'), ('蓋', '盖'), ('薦', '荐'), ('藍', '蓝'), ('藝', '艺'), ('藥', '药'),
+  ('蘇', '苏'), ('蘋', '苹'), ('蘭', '兰'), ('處', '处'), ('號', '号'), ('虧', '亏'), ('蝦', '虾'), ('蟲', '虫'),
+  ('衆', '众'), ('術', '术'), ('衛', '卫'), ('衝', '冲'), ('裊', '袅'), ('裏', '里'), ('補', '补'), ('裝', '装'),
+  ('裡', '里'), ('製', '制'), ('複', '复'), ('襲', '袭'), ('見', '见'), ('規', '规'), ('視', '视'), ('親', '亲'),
+  ('覺', '觉'), ('觀', '观'), ('觸', '触'), ('訂', '订'), ('計', '计'), ('訊', '讯'), ('討', '讨'), ('訓', '训'),
+  ('記', '记'), ('訪', '访'), ('設', '设'), ('許', '许'), ('評', '评'), ('詞', '词'), ('詠', '咏'), ('詢', '询'),
+  ('試', '试'), ('詩', '诗'), ('話', '话'), ('該', '该'), ('詳', '详'), ('誇', '夸'), ('認', '认'), ('誕', '诞'),
+  ('誘', '诱'), ('語', '语'), ('誠', '诚'), ('誤', '误'), ('說', '说'), ('誰', '谁'), ('課', '课'), ('調', '调'),
+  ('談', '谈'), ('請', '请'), ('論', '论'), ('諸', '诸'), ('諾', '诺'), ('謀', '谋'), ('謂', '谓'), ('謊', '谎'),
+  ('謎', '谜'), ('講', '讲'), ('謝', '谢'), ('謹', '谨'), ('證', '证'), ('識', '识'), ('譯', '译'), ('議', '议'),
+  ('護', '护'), ('讀', '读'), ('變', '变'), ('讓', '让'), ('讚', '赞'), ('豈', '岂'), ('豎', '竖'), ('豐', '丰'),
+  ('豬', '猪'), ('貓', '猫'), ('貝', '贝'), ('負', '负'), ('財', '财'), ('貢', '贡'), ('貧', '贫'), ('貨', '货'),
+  ('販', '贩'), ('貪', '贪'), ('責', '责'), ('貴', '贵'), ('買', '买'), ('費', '费'), ('貼', '贴'), ('貿', '贸'),
+  ('賀', '贺'), ('資', '资'), ('賊', '贼'), ('賓', '宾'), ('賞', '赏'), ('賠', '赔'), ('賢', '贤'), ('賣', '卖'),
+  ('賦', '赋'), ('質', '质'), ('賭', '赌'), ('購', '购'), ('賽', '赛'), ('贈', '赠'), ('贊', '赞'), ('贏', '赢'),
+  ('趕', '赶'), ('趙', '赵'), ('趨', '趋'), ('跡', '迹'), ('踐', '践'), ('蹤', '踪'), ('躍', '跃'), ('車', '车'),
+  ('軌', '轨'), ('軍', '军'), ('軟', '软'), ('軸', '轴'), ('較', '较'), ('載', '载'), ('輔', '辅'), ('輕', '轻'),
+  ('輛', '辆'), ('輝', '辉'), ('輪', '轮'), ('輸', '输'), ('轄', '辖'), ('轉', '转'), ('轟', '轰'), ('辦', '办'),
+  ('辭', '辞'), ('辯', '辩'), ('農', '农'), ('迴', '回'), ('這', '这'), ('連', '连'), ('週', '周'), ('進', '进'),
+  ('遊', '游'), ('運', '运'), ('過', '过'), ('達', '达'), ('違', '违'), ('遙', '遥'), ('遞', '递'), ('遠', '远'),
+  ('適', '适'), ('遲', '迟'), ('遷', '迁'), ('選', '选'), ('遺', '遗'), ('遼', '辽'), ('還', '还'), ('邊', '边'),
+  ('邏', '逻'), ('郵', '邮'), ('鄉', '乡'), ('鄧', '邓'), ('鄭', '郑'), ('鄰', '邻'), ('醜', '丑'), ('醞', '酝'),
+  ('醫', '医'), ('醬', '酱'), ('釀', '酿'), ('釋', '释'), ('針', '针'), ('鈔', '钞'), ('銀', '银'), ('銷', '销'),
+  ('鋒', '锋'), ('鋼', '钢'), ('錄', '录'), ('錢', '钱'), ('錯', '错'), ('錶', '表'), ('鍊', '炼'), ('鍋', '锅'),
+  ('鍵', '键'), ('鍾', '钟'), ('鎖', '锁'), ('鎮', '镇'), ('鏈', '链'), ('鏡', '镜'), ('鏽', '锈'), ('鐘', '钟'),
+  ('鐵', '铁'), ('長', '长'), ('門', '门'), ('閃', '闪'), ('閉', '闭'), ('開', '开'), ('閑', '闲'), ('間', '间'),
+  ('閣', '阁'), ('閱', '阅'), ('闆', '板'), ('闊', '阔'), ('關', '关'), ('陣', '阵'), ('陰', '阴'), ('陳', '陈'),
+  ('陸', '陆'), ('陽', '阳'), ('隊', '队'), ('階', '阶'), ('際', '际'), ('隨', '随'), ('險', '险'), ('隱', '隐'),
+  ('隻', '只'), ('雖', '虽'), ('雙', '双'), ('雜', '杂'), ('雞', '鸡'), ('離', '离'), ('難', '难'), ('雲', '云'),
+  ('電', '电'), ('靂', '雳'), ('靈', '灵'), ('靜', '静'), ('韓', '韩'), ('響', '响'), ('頁', '页'), ('頂', '顶'),
+  ('項', '项'), ('順', '顺'), ('須', '须'), ('預', '预'), ('頓', '顿'), ('頗', '颇'), ('領', '领'), ('頭', '头'),
+  ('頸', '颈'), ('頹', '颓'), ('頻', '频'), ('顆', '颗'), ('題', '题'), ('額', '额'), ('顏', '颜'), ('願', '愿'),
+  ('類', '类'), ('顧', '顾'), ('顫', '颤'), ('顯', '显'), ('風', '风'), ('颱', '台'), ('飄', '飘'), ('飛', '飞'),
+  ('飯', '饭'), ('飲', '饮'), ('飽', '饱'), ('飾', '饰'), ('餅', '饼'), ('養', '养'), ('餓', '饿'), ('餘', '余'),
+  ('館', '馆'), ('饒', '饶'), ('馬', '马'), ('馮', '冯'), ('駐', '驻'), ('駕', '驾'), ('騎', '骑'), ('騙', '骗'),
+  ('騷', '骚'), ('驅', '驱'), ('驗', '验'), ('驚', '惊'), ('驢', '驴'), ('體', '体'), ('髮', '发'), ('鬆', '松'),
+  ('鬍', '胡'), ('鬥', '斗'), ('鬧', '闹'), ('鬱', '郁'), ('魚', '鱼'), ('魯', '鲁'), ('鮮', '鲜'), ('鯨', '鲸'),
+  ('鳥', '鸟'), ('鳳', '凤'), ('鳴', '鸣'), ('鴨', '鸭'), ('鵝', '鹅'), ('鷹', '鹰'), ('鹽', '盐'), ('麗', '丽'),
+  ('麥', '麦'), ('麪', '面'), ('麵', '面'), ('麼', '么'), ('黃', '黄'), ('點', '点'), ('黨', '党'), ('黴', '霉'),
+  ('齊', '齐'), ('齒', '齿'), ('齡', '龄'), ('龍', '龙'), ('龜', '龟'),
+];
+
+impl WmFold {
+  /// 规范化文本
+  ///
+  /// ## 参数
+  /// - `text`：原文
+  ///
+  /// ## 返回
+  /// 规范化后的字符与每个字符在原文中的字符位置
+  pub fn fold_text(&self, text: &str) -> WmFolded {
+    let mut text_folded = WmFolded::default();
+    for (char_src, text_char) in text.chars().enumerate() {
+      if self.strip && (FOLD_ZERO_WIDTHS.contains(&text_char) || fold_sep(text_char)) {
+        continue;
+      }
+      let mut fold_char = text_char;
+      if self.width {
+        fold_char = fold_width(fold_char);
+      }
+      if self.trad {
+        fold_char = fold_trad(fold_char);
+      }
+      if self.case {
+        for case_char in fold_char.to_lowercase() {
+          text_folded.chars.push(case_char);
+          text_folded.char_srcs.push(char_src);
+        }
+      } else {
+        text_folded.chars.push(fold_char);
+        text_folded.char_srcs.push(char_src);
+      }
+    }
+    text_folded
+  }
+}
+
+/// 是否为空白或分隔符号
+fn fold_sep(text_char: char) -> bool {
+  text_char.is_whitespace() || FOLD_SEPS.contains(&text_char)
+}
+
+/// 全角ASCII字符（U+FF01至U+FF5E）与全角空格折叠为半角
+fn fold_width(text_char: char) -> char {
+  match text_char {
+    '\u{3000}' => ' ',
+    '\u{FF01}'..='\u{FF5E}' => char::from_u32(text_char as u32 - 0xFEE0).unwrap_or(text_char),
+    _ => text_char,
+  }
+}
+
+/// 常用繁体字折叠为简体字，其他字符不变
+fn fold_trad(text_char: char) -> char {
+  FOLD_TRADS.binary_search_by_key(&text_char, |&(trad_char, _)| trad_char).
+    map_or(text_char, |trad_idx| FOLD_TRADS[trad_idx].1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fold_trads_sorted_and_not_chained() {
+    assert!(FOLD_TRADS.windows(2).all(|trad_pair| trad_pair[0].0 < trad_pair[1].0));
+    for (trad_char, simp_char) in FOLD_TRADS {
+      assert_ne!(trad_char, simp_char);
+      assert_eq!(fold_trad(*simp_char), *simp_char, "{trad_char}");
+    }
+  }
+
+  #[test]
+  fn fold_text_maps_back_to_original() {
+    let wm_fold = WmFold { case: true, width: true, trad: true, strip: true };
+    let text_folded = wm_fold.fold_text("Ｈｅｌｌｏ 微\u{200B}博·熱點 İ");
+    assert_eq!(text_folded.chars.iter().collect::<String>(), "hello微博热点i\u{307}");
+    assert_eq!(text_folded.char_srcs, vec![0, 1, 2, 3, 4, 6, 8, 10, 11, 13, 13]);
+
+    assert_eq!(WmFold::default().fold_text("熱 Ａ").chars.iter().collect::<String>(), "熱 Ａ");
+  }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use std::time::Instant;
+use hifitime::prelude::Epoch;
+use log::debug;
+use log::info;
+use log::warn;
+use nyquest::AsyncClient;
+use nyquest::r#async::Request;
+use nyquest::r#async::Response;
+use rand::Rng;
+use tokio::sync::Mutex;
+use crate::exceptions::WeiboError;
+use crate::prefs::prefs;
+use crate::utils::http_era_secs;
+
+/// 全局令牌桶，所有访问微博的请求共享
+static WEIBO_GAIN_BUCKET: Mutex<Option<WeiboGainBucket>> = Mutex::const_new(None);
+
+/// 每个接口的请求统计，以接口名称为键
+static WEIBO_GAIN_STATS: LazyLock<std::sync::Mutex<HashMap<String, WeiboGainStat>>> =
+  LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// 令牌桶，按`gain_rate_per_sec`匀速补充令牌，最多积累`gain_burst`个
+struct WeiboGainBucket {
+  // 当前的令牌数
+  toks: f64,
+  // 上次补充令牌的时间
+  last_ins: Instant,
+}
+
+/// 单个接口的累计请求统计
+#[derive(Default)]
+struct WeiboGainStat {
+  // 请求次数，重试不计入
+  reqs: u64,
+  // 实际发出的次数，包括重试
+  attempts: u64,
+  // 重试后仍然失败的次数
+  flaws: u64,
+}
+
+/// 共享的请求层，weibo.rs中所有访问微博的请求都经过此函数。
+///
+/// 每次发出请求前从全局令牌桶取得令牌；超时、IO错误与5xx、429状态码视为暂时性失败，
+/// 按指数退避加随机抖动重试，响应带有`Retry-After`时以其为准，最多发出`gain_attempt_max`次。
+/// 418表示cookie被封禁，用同一cookie重试无用，直接返回由调用方更换cookie。
+/// 重试用尽后，状态码类的失败返回最后一次的响应，由调用方按状态码处理。
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `gain_des`：接口名称，用于统计与日志
+/// - `gain_info_fn`：构造请求，每次重试都会重新构造
+pub async fn gain_reap<F>(weibo_clt: &AsyncClient, gain_des: &str,
+                          gain_info_fn: F) -> Result<Response, WeiboError>
+where
+  F: Fn() -> Request,
+{
+  let attempt_max = prefs().gain_attempt_max;
+  let mut attempt_cnt = 0;
+  loop {
+    attempt_cnt += 1;
+    gain_bucket_take().await;
+
+    let (reap_flaw, retry_after) = match weibo_clt.request(gain_info_fn()).await {
+      Ok(reap) => {
+        let reap_code = reap.status().code();
+        if !gain_code_transient(reap_code) {
+          gain_stat_u(gain_des, attempt_cnt, false);
+          return Ok(reap);
+        }
+        if attempt_cnt >= attempt_max {
+          gain_stat_u(gain_des, attempt_cnt, true);
+          return Ok(reap);
+        }
+        (format!("status code is {}", reap_code), anly_retry_after(&reap))
+      }
+      Err(flaw @ (nyquest::Error::RequestTimeout | nyquest::Error::Io(_)))
+      if attempt_cnt < attempt_max => (WeiboError::from(flaw).to_string(), None),
+      Err(flaw) => {
+        gain_stat_u(gain_des, attempt_cnt, true);
+        return Err(flaw.into());
+      }
+    };
+
+    let retry_max = Duration::from_secs(prefs().gain_backoff_max_secs);
+    let retry_wait = retry_after.unwrap_or_else(|| gain_backoff(attempt_cnt)).min(retry_max);
+    warn!("{} attempt {}/{} failed, {}, retrying in {}ms",
+          gain_des, attempt_cnt, attempt_max, reap_flaw, retry_wait.as_millis());
+    tokio::time::sleep(retry_wait).await;
+  }
+}
+
+/// 状态码是否为暂时性失败，可以原样重试
+///
+/// ## 参数
+/// - `reap_code`：响应的状态码
+fn gain_code_transient(reap_code: u16) -> bool {
+  reap_code >= 500 || reap_code == 429
+}
+
+/// 从全局令牌桶取得一个令牌，令牌不足时等待补充
+async fn gain_bucket_take() {
+  let gain_rate = prefs().gain_rate_per_sec;
+  let gain_burst = prefs().gain_burst as f64;
+  loop {
+    let bucket_wait = {
+      let mut gain_bucket = WEIBO_GAIN_BUCKET.lock().await;
+      let nub_ins = Instant::now();
+      let gain_bucket = gain_bucket.get_or_insert(WeiboGainBucket {
+        toks: gain_burst,
+        last_ins: nub_ins,
+      });
+      let bucket_elapsed = nub_ins.duration_since(gain_bucket.last_ins).as_secs_f64();
+      gain_bucket.toks = (gain_bucket.toks + bucket_elapsed * gain_rate).min(gain_burst);
+      gain_bucket.last_ins = nub_ins;
+      if gain_bucket.toks >= 1.0 {
+        gain_bucket.toks -= 1.0;
+        return;
+      }
+      Duration::from_secs_f64((1.0 - gain_bucket.toks) / gain_rate)
+    };
+    tokio::time::sleep(bucket_wait).await;
+  }
+}
+
+/// 第`attempt_cnt`次失败后的退避时长：`gain_backoff_ms * 2^(attempt_cnt - 1)`，
+/// 取其一半加上不超过另一半的随机抖动
+///
+/// ## 参数
+/// - `attempt_cnt`：已经失败的次数
+fn gain_backoff(attempt_cnt: u32) -> Duration {
+  let backoff_ms = gain_backoff_cap_ms(attempt_cnt, prefs().gain_backoff_ms,
+                                       prefs().gain_backoff_max_secs * 1000);
+  let jitter_ms = rand::rng().random_range(0..=backoff_ms / 2);
+  Duration::from_millis(backoff_ms - backoff_ms / 2 + jitter_ms)
+}
+
+/// 未加抖动的退避时长上限，按失败次数指数增长，不超过`backoff_max_ms`
+///
+/// ## 参数
+/// - `attempt_cnt`：已经失败的次数
+/// - `backoff_ms`：首次失败的退避时长
+/// - `backoff_max_ms`：退避时长的上限
+fn gain_backoff_cap_ms(attempt_cnt: u32, backoff_ms: u64, backoff_max_ms: u64) -> u64 {
+  backoff_ms.
+    saturating_mul(1u64 << attempt_cnt.saturating_sub(1).min(20)).
+    min(backoff_max_ms)
+}
+
+/// 解析响应中的Retry-After，支持秒数与HTTP日期
+///
+/// ## 参数
+/// - `reap`：响应
+fn anly_retry_after(reap: &Response) -> Option<Duration> {
+  let retry_after = reap.get_header("retry-after").ok()?.into_iter().next()?;
+  let nub_secs = Epoch::now().ok()?.to_unix_seconds() as i64;
+  anly_retry_after_val(&retry_after, nub_secs)
+}
+
+/// 解析Retry-After的值，HTTP日期早于当前时间时返回零
+///
+/// ## 参数
+/// - `retry_after`：Retry-After的值
+/// - `nub_secs`：当前的unix秒数
+fn anly_retry_after_val(retry_after: &str, nub_secs: i64) -> Option<Duration> {
+  let retry_after = retry_after.trim();
+  if let Ok(retry_secs) = retry_after.parse::<u64>() {
+    return Some(Duration::from_secs(retry_secs));
+  }
+  let retry_secs = http_era_secs(retry_after)? - nub_secs;
+  Some(Duration::from_secs(retry_secs.max(0) as u64))
+}
+
+/// 记录一次请求的统计并输出日志
+///
+/// ## 参数
+/// - `gain_des`：接口名称
+/// - `attempt_cnt`：本次请求实际发出的次数
+/// - `gain_flaw`：重试后是否仍然失败
+fn gain_stat_u(gain_des: &str, attempt_cnt: u32, gain_flaw: bool) {
+  let Ok(mut gain_stats) = WEIBO_GAIN_STATS.lock() else {
+    return;
+  };
+  let gain_stat = gain_stats.entry(gain_des.to_string()).or_default();
+  gain_stat.reqs += 1;
+  gain_stat.attempts += attempt_cnt as u64;
+  if gain_flaw {
+    gain_stat.flaws += 1;
+  }
+
+  if attempt_cnt > 1 || gain_flaw {
+    info!("{} finished after {} attempts{}, totals: {} requests, {} attempts, {} failed",
+          gain_des, attempt_cnt, if gain_flaw { " without success" } else { "" },
+          gain_stat.reqs, gain_stat.attempts, gain_stat.flaws);
+  } else {
+    debug!("{} totals: {} requests, {} attempts, {} failed",
+           gain_des, gain_stat.reqs, gain_stat.attempts, gain_stat.flaws);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn gain_code_transient_excludes_blocked_cookie() {
+    let code_cases = [
+      (200, false), (302, false), (401, false), (403, false), (404, false),
+      // 418是cookie被封禁，由调用方隔离cookie，不原样重试
+      (418, false),
+      (429, true), (500, true), (502, true), (503, true),
+    ];
+    for (reap_code, code_transient) in code_cases {
+      assert_eq!(gain_code_transient(reap_code), code_transient, "{}", reap_code);
+    }
+  }
+
+  #[test]
+  fn gain_backoff_cap_doubles_until_max() {
+    let backoff_cases = [
+      (1, 500, 60_000, 500),
+      (2, 500, 60_000, 1000),
+      (3, 500, 60_000, 2000),
+      (4, 500, 60_000, 4000),
+      (7, 500, 60_000, 32_000),
+      (8, 500, 60_000, 60_000),
+      (5, 500, 3000, 3000),
+      // 指数在2^20处封顶，乘法饱和而不溢出
+      (64, 500, 60_000, 60_000),
+      (64, u64::MAX, u64::MAX, u64::MAX),
+      // 尚未失败时按首次处理
+      (0, 500, 60_000, 500),
+    ];
+    for (attempt_cnt, backoff_ms, backoff_max_ms, backoff_cap_ms) in backoff_cases {
+      assert_eq!(gain_backoff_cap_ms(attempt_cnt, backoff_ms, backoff_max_ms), backoff_cap_ms,
+                 "attempt {} base {} max {}", attempt_cnt, backoff_ms, backoff_max_ms);
+    }
+  }
+
+  #[test]
+  fn gain_backoff_jitter_stays_in_upper_half() {
+    let weibo_prefs = prefs();
+    for attempt_cnt in 1..=10 {
+      let backoff_cap_ms = gain_backoff_cap_ms(attempt_cnt, weibo_prefs.gain_backoff_ms,
+                                               weibo_prefs.gain_backoff_max_secs * 1000);
+      for _ in 0..50 {
+        let backoff_ms = gain_backoff(attempt_cnt).as_millis() as u64;
+        assert!(backoff_ms >= backoff_cap_ms - backoff_cap_ms / 2 && backoff_ms <= backoff_cap_ms,
+                "attempt {} waited {}ms, cap {}ms", attempt_cnt, backoff_ms, backoff_cap_ms);
+      }
+    }
+  }
+
+  #[test]
+  fn anly_retry_after_val_secs_and_http_date() {
+    // 2024-01-01 00:00:00 UTC
+    let nub_secs = 1_704_067_200;
+    let retry_cases = [
+      ("0", Some(0)),
+      ("120", Some(120)),
+      (" 7 ", Some(7)),
+      ("Mon, 01 Jan 2024 00:00:30 GMT", Some(30)),
+      ("Mon, 01-Jan-2024 00:01:00 GMT", Some(60)),
+      // 已经过去的日期不等待
+      ("Sun, 31 Dec 2023 23:59:00 GMT", Some(0)),
+      ("-5", None),
+      ("1.5", None),
+      ("soon", None),
+      ("", None),
+      ("Mon, 01 Foo 2024 00:00:30 GMT", None),
+    ];
+    for (retry_after, retry_secs) in retry_cases {
+      assert_eq!(anly_retry_after_val(retry_after, nub_secs), retry_secs.map(Duration::from_secs),
+                 "{:?}", retry_after);
+    }
+  }
+}
@@ -1,62 +1,130 @@
 mod dbs;
+mod ents;
 mod exceptions;
+mod fold;
+mod gain;
+mod meta;
+mod migr;
+mod pics;
 mod prefs;
+mod sched;
+mod tls;
 mod utils;
 mod weibo;
 mod wm;
 mod views;
+mod visitor;
+mod watch;
 
+use std::time::Duration;
+use log::info;
+use log::warn;
 use nyquest::AsyncClient;
 use nyquest::ClientBuilder;
 use rbatis::RBatis;
 use rbdc_sqlite::SqliteDriver;
 use salvo::prelude::*;
 use salvo_mdw::LogLogger;
-use crate::prefs::WEIBO_DB_PTH;
 use crate::views::*;
 
 #[tokio::main]
 async fn main() {
+  // 运行配置，有误时打印全部问题后退出
+  let weibo_args: Vec<String> = std::env::args().skip(1).collect();
+  let weibo_prefs = match prefs::prefs_init(&weibo_args) {
+    Ok(weibo_prefs) => weibo_prefs,
+    Err(flaw) => {
+      eprintln!("{}", flaw);
+      std::process::exit(2);
+    }
+  };
+
   // log4rs日志初始化
-  log4rs::init_file("weibo-log4rs.yml", Default::default()).expect("log4rs: failed to init logger");
-
-  // nyquest爬虫客户端
-  nyquest_preset::register();
-  let weibo_clt: AsyncClient =
-    ClientBuilder::default().base_url("https://weibo.com/ajax/").
-      with_header("Referer", "https://weibo.com/newlogin").
-      user_agent("Mozilla/5.0 (X11; Linux x86_64) \
-                  AppleWebKit/537.36 (KHTML, like Gecko) \
-                  Chrome/130.0.0.0 Safari/537.36").
-      dangerously_ignore_certificate_errors().no_redirects().
-      build_async().await.
-      expect("nyquest: failed to build async-client");
+  log4rs::init_file(&weibo_prefs.log4rs_pth, Default::default()).
+    expect("log4rs: failed to init logger");
+
+  // nyquest爬虫客户端，默认校验服务器证书，tls_insecure仅用于调试
+  if let Err(flaw) = tls::tls_register(weibo_prefs) {
+    eprintln!("{}", flaw);
+    std::process::exit(2);
+  }
+  let mut weibo_clt_builder =
+    ClientBuilder::default().base_url(weibo_prefs.base_url.as_str()).
+      with_header("Referer", weibo_prefs.referer.as_str()).
+      user_agent(weibo_prefs.user_agent.as_str()).
+      request_timeout(Duration::from_secs(weibo_prefs.request_timeout_secs)).
+      no_redirects();
+  if weibo_prefs.tls_insecure {
+    let tls_warn = "TLS CERTIFICATE VERIFICATION IS DISABLED (tls_insecure = true): \
+                    connections to weibo can be intercepted, never use this in production";
+    eprintln!("WARNING: {}", tls_warn);
+    warn!("!!! {} !!!", tls_warn);
+    weibo_clt_builder = weibo_clt_builder.dangerously_ignore_certificate_errors();
+  }
+  let weibo_clt: AsyncClient = weibo_clt_builder.build_async().await.
+    expect("nyquest: failed to build async-client");
 
   // rbatis数据库连接
   let weibo_db_rb_conn: RBatis = RBatis::new();
-  weibo_db_rb_conn.link(SqliteDriver {}, WEIBO_DB_PTH).await.
+  weibo_db_rb_conn.link(SqliteDriver {}, &weibo_prefs.db_pth).await.
     expect("rbatis: failed to link sqlite");
 
-  let salvo_accept = TcpListener::new("0.0.0.0:5800").bind().await;
+  // 数据库迁移，--migrate-dry-run只打印待执行的迁移后退出
+  if weibo_args.iter().any(|arg| arg == "--migrate-dry-run") {
+    migr::migr_dry_run(&weibo_db_rb_conn).await.expect("rbatis: failed to check migrations");
+    return;
+  }
+  let migr_cnt = migr::migr_apply(&weibo_db_rb_conn).await.
+    expect("rbatis: failed to apply migrations");
+  info!("{} migrations applied", migr_cnt);
+
+  // 关键词监控匹配器
+  watch::WEIBO_WATCH_CACHE.watch_compile(&weibo_db_rb_conn).await.
+    expect("rbatis: failed to compile keyword watch");
+
+  // 定时爬取调度器
+  tokio::spawn(sched::sched_loop(weibo_clt.clone(), weibo_db_rb_conn.clone()));
+
+  let salvo_accept = TcpListener::new(weibo_prefs.bind_addr.as_str()).bind().await;
   let salvo_rt = Router::new().
     hoop(affix_state::insert("weibo_clt", weibo_clt).
       insert("weibo_db_rb_conn", weibo_db_rb_conn)).
     hoop(CatchPanic::new()).
     get(hello).
     push(Router::with_path("r").push(
-      Router::with_path("hot_search").post(hot_search_r)).push(
-      Router::with_path("hot_timeline").post(hot_timeline_r)).push(
-      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_r))
+      Router::with_path("hot_search").post(hot_search_r).push(
+        Router::with_path("history").post(hot_search_history_r))).push(
+      Router::with_path("hot_timeline").post(hot_timeline_r).push(
+        Router::with_path("history").post(hot_timeline_history_r))).push(
+      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_r)).push(
+      Router::with_path("hot_timeline_pic_meta").post(hot_timeline_pic_meta_r)).push(
+      Router::with_path("hot_timeline_pic_similar").post(hot_timeline_pic_similar_r)).push(
+      Router::with_path("topics").post(topics_r)).push(
+      Router::with_path("mentions").post(mentions_r)).push(
+      Router::with_path("keyword").post(keyword_r)).push(
+      Router::with_path("keyword_hits").post(keyword_hits_r)).push(
+      Router::with_path("sched").post(sched_r)).push(
+      Router::with_path("cok").post(cok_r))
     ).
     push(Router::with_path("u").push(
       Router::with_path("hot_search").post(hot_search_u)).push(
       Router::with_path("hot_timeline").post(hot_timeline_u)).push(
-      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_u))
+      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_u)).push(
+      Router::with_path("hot_timeline_pic").post(hot_timeline_pic_u)).push(
+      Router::with_path("sched").post(sched_u)).push(
+      Router::with_path("cok").post(cok_u)).push(
+      Router::with_path("keyword").post(keyword_u))
+    ).
+    push(Router::with_path("pics").push(
+      Router::with_path("hash/{pic_hash}").get(pic_hash_r)).push(
+      Router::with_path("{mid}/{pic_id}").get(pic_r))
     ).
     push(Router::with_path("d").push(
       Router::with_path("hot_search").post(hot_search_d)).push(
       Router::with_path("hot_timeline").post(hot_timeline_d)).push(
-      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_d))
+      Router::with_path("hot_timeline_comm").post(hot_timeline_comm_d)).push(
+      Router::with_path("cok").post(cok_d)).push(
+      Router::with_path("keyword").post(keyword_d))
     );
   let salvo_svc = Service::new(salvo_rt).hoop(LogLogger::new());
   Server::new(salvo_accept).serve(salvo_svc).await;
@@ -0,0 +1,515 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use crate::pics::WeiboPicFmt;
+
+/// 从图片中解析出的EXIF/XMP元数据
+#[derive(Clone, Debug, Default)]
+pub struct WeiboPicMeta {
+  // 相机厂商
+  pub cam_make: String,
+  // 相机型号
+  pub cam_model: String,
+  // 镜头型号
+  pub lens_model: String,
+  // 处理软件
+  pub software: String,
+  // 拍摄时间，保留图片中的原始格式
+  pub shot_era: String,
+  // 修改时间，保留图片中的原始格式
+  pub modify_era: String,
+  // 是否带有位置信息，只要存在GPS字段即视为带有
+  pub gps_flag: bool,
+  // 纬度，南纬为负
+  pub gps_lat: Option<f64>,
+  // 经度，西经为负
+  pub gps_lon: Option<f64>,
+  // 全部EXIF字段，键为`IFD/字段名`
+  pub exif_tags: BTreeMap<String, String>,
+  // XMP数据包原文
+  pub xmp: String,
+}
+
+impl WeiboPicMeta {
+  /// 是否带有任何EXIF或XMP元数据
+  pub fn meta_flag(&self) -> bool {
+    !self.exif_tags.is_empty() || !self.xmp.is_empty()
+  }
+}
+
+/// 解析图片中的EXIF与XMP元数据，EXIF中缺少的字段以XMP补充。无法解析的部分忽略。
+///
+/// ## 参数
+/// - `pic_fmt`：图片格式
+/// - `pic_byt`：图片内容
+pub fn anly_pic_meta(pic_fmt: WeiboPicFmt, pic_byt: &[u8]) -> WeiboPicMeta {
+  let mut pic_meta = WeiboPicMeta::default();
+
+  // GIF不支持EXIF
+  if pic_fmt != WeiboPicFmt::Gif &&
+    let Ok(pic_exif) = exif::Reader::new().read_from_container(&mut Cursor::new(pic_byt)) {
+    anly_pic_exif(&pic_exif, &mut pic_meta);
+  }
+
+  if let Some(pic_xmp) = anly_pic_xmp(pic_byt) {
+    let meta_vals = [
+      (&mut pic_meta.cam_make, "tiff:Make"),
+      (&mut pic_meta.cam_model, "tiff:Model"),
+      (&mut pic_meta.lens_model, "exifEX:LensModel"),
+      (&mut pic_meta.software, "xmp:CreatorTool"),
+      (&mut pic_meta.shot_era, "exif:DateTimeOriginal"),
+      (&mut pic_meta.modify_era, "xmp:ModifyDate"),
+    ];
+    for (meta_val, xmp_prop) in meta_vals {
+      if meta_val.is_empty() {
+        *meta_val = anly_xmp_prop(&pic_xmp, xmp_prop).unwrap_or_default();
+      }
+    }
+    let xmp_lat = anly_xmp_prop(&pic_xmp, "exif:GPSLatitude");
+    let xmp_lon = anly_xmp_prop(&pic_xmp, "exif:GPSLongitude");
+    if xmp_lat.is_some() || xmp_lon.is_some() {
+      pic_meta.gps_flag = true;
+    }
+    if pic_meta.gps_lat.is_none() {
+      pic_meta.gps_lat = xmp_lat.as_deref().and_then(anly_xmp_gps);
+    }
+    if pic_meta.gps_lon.is_none() {
+      pic_meta.gps_lon = xmp_lon.as_deref().and_then(anly_xmp_gps);
+    }
+    pic_meta.xmp = pic_xmp;
+  }
+  pic_meta
+}
+
+/// 从EXIF中取出常用字段与全部字段
+///
+/// ## 参数
+/// - `pic_exif`：解析后的EXIF
+/// - `pic_meta`：写入的元数据
+fn anly_pic_exif(pic_exif: &exif::Exif, pic_meta: &mut WeiboPicMeta) {
+  let exif_ascii = |exif_tag: exif::Tag| -> String {
+    match pic_exif.get_field(exif_tag, exif::In::PRIMARY).map(|exif_field| &exif_field.value) {
+      Some(exif::Value::Ascii(exif_vals)) => exif_vals.first().
+        map(|exif_val| String::from_utf8_lossy(exif_val).trim_end_matches('\0').trim().to_string()).
+        unwrap_or_default(),
+      _ => String::new(),
+    }
+  };
+  pic_meta.cam_make = exif_ascii(exif::Tag::Make);
+  pic_meta.cam_model = exif_ascii(exif::Tag::Model);
+  pic_meta.lens_model = exif_ascii(exif::Tag::LensModel);
+  pic_meta.software = exif_ascii(exif::Tag::Software);
+  pic_meta.shot_era = exif_ascii(exif::Tag::DateTimeOriginal);
+  pic_meta.modify_era = exif_ascii(exif::Tag::DateTime);
+
+  // 度分秒转换为小数，参考方向为S或W时取负
+  let exif_gps = |exif_tag: exif::Tag, exif_ref_tag: exif::Tag, exif_neg: &str| -> Option<f64> {
+    let exif::Value::Rational(exif_dms) =
+      &pic_exif.get_field(exif_tag, exif::In::PRIMARY)?.value else {
+      return None;
+    };
+    let gps_deg = exif_dms.iter().zip([1.0, 60.0, 3600.0]).
+      map(|(exif_dms_arri, gps_div)| exif_dms_arri.to_f64() / gps_div).
+      sum::<f64>();
+    if !gps_deg.is_finite() {
+      return None;
+    }
+    Some(if exif_ascii(exif_ref_tag).eq_ignore_ascii_case(exif_neg) { -gps_deg } else { gps_deg })
+  };
+  pic_meta.gps_lat = exif_gps(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S");
+  pic_meta.gps_lon = exif_gps(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W");
+
+  for exif_field in pic_exif.fields() {
+    if exif_field.tag.context() == exif::Context::Gps {
+      pic_meta.gps_flag = true;
+    }
+    pic_meta.exif_tags.insert(
+      format!("{}/{}", exif_field.ifd_num, exif_field.tag),
+      exif_field.display_value().with_unit(pic_exif).to_string());
+  }
+}
+
+/// 取出图片中的XMP数据包，各格式中的XMP都以未压缩的文本存储，直接查找数据包的起止
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+fn anly_pic_xmp(pic_byt: &[u8]) -> Option<String> {
+  let xmp_head = b"<x:xmpmeta";
+  let xmp_tail = b"</x:xmpmeta>";
+  let xmp_pos = pic_byt.windows(xmp_head.len()).position(|pic_win| pic_win == xmp_head)?;
+  let xmp_len = pic_byt[xmp_pos..].windows(xmp_tail.len()).
+    position(|pic_win| pic_win == xmp_tail)? + xmp_tail.len();
+  Some(String::from_utf8_lossy(&pic_byt[xmp_pos..xmp_pos + xmp_len]).into_owned())
+}
+
+/// 取出XMP中的简单属性，支持`prop="…"`与`<prop>…</prop>`两种写法
+///
+/// ## 参数
+/// - `pic_xmp`：XMP数据包
+/// - `xmp_prop`：带命名空间前缀的属性名
+fn anly_xmp_prop(pic_xmp: &str, xmp_prop: &str) -> Option<String> {
+  let xmp_attr = format!("{}=\"", xmp_prop);
+  let xmp_attr_pos = pic_xmp.match_indices(&xmp_attr).map(|(xmp_pos, _)| xmp_pos).
+    find(|xmp_pos| pic_xmp[..*xmp_pos].ends_with(char::is_whitespace));
+  let xmp_val = if let Some(xmp_pos) = xmp_attr_pos {
+    let xmp_val = &pic_xmp[xmp_pos + xmp_attr.len()..];
+    &xmp_val[..xmp_val.find('"')?]
+  } else {
+    let xmp_head = format!("<{}>", xmp_prop);
+    let xmp_val = &pic_xmp[pic_xmp.find(&xmp_head)? + xmp_head.len()..];
+    &xmp_val[..xmp_val.find(&format!("</{}>", xmp_prop))?]
+  };
+  let xmp_val = xmp_val.trim();
+  (!xmp_val.is_empty() && !xmp_val.starts_with('<')).then(|| xmp_val.to_string())
+}
+
+/// 解析XMP中的坐标，格式为`度,分[,秒]方向`，如`39,54.123N`
+///
+/// ## 参数
+/// - `xmp_gps`：XMP中的坐标
+fn anly_xmp_gps(xmp_gps: &str) -> Option<f64> {
+  let gps_dir = xmp_gps.chars().last()?;
+  let gps_deg = xmp_gps[..xmp_gps.len() - gps_dir.len_utf8()].split(',').
+    zip([1.0, 60.0, 3600.0]).
+    map(|(gps_part, gps_div)| gps_part.trim().parse::<f64>().map(|gps_val| gps_val / gps_div)).
+    sum::<Result<f64, _>>().ok()?;
+  match gps_dir {
+    'N' | 'E' => Some(gps_deg),
+    'S' | 'W' => Some(-gps_deg),
+    _ => None,
+  }
+}
+
+/// 去除图片中的元数据，得到可以对外提供的副本。只删除元数据所在的段或块，不重新编码图像数据。
+///
+/// - JPEG：删除APP1（EXIF、XMP）、APP13（IPTC）、COM及其他应用段，保留JFIF、ICC与Adobe段
+/// - PNG：删除eXIf、tEXt、zTXt、iTXt与tIME块
+/// - WebP：删除EXIF与XMP块，并清除VP8X中的对应标志
+/// - GIF：删除注释扩展，以及除循环播放外的应用扩展
+///
+/// ## 参数
+/// - `pic_fmt`：图片格式
+/// - `pic_byt`：图片内容
+///
+/// ## 返回
+/// 图片结构无法解析时返回None
+pub fn pics_meta_strip(pic_fmt: WeiboPicFmt, pic_byt: &[u8]) -> Option<Vec<u8>> {
+  match pic_fmt {
+    WeiboPicFmt::Jpeg => strip_jpeg(pic_byt),
+    WeiboPicFmt::Png => strip_png(pic_byt),
+    WeiboPicFmt::Webp => strip_webp(pic_byt),
+    WeiboPicFmt::Gif => strip_gif(pic_byt),
+  }
+}
+
+fn strip_jpeg(pic_byt: &[u8]) -> Option<Vec<u8>> {
+  let mut strip_byt = pic_byt.get(0..2)?.to_vec();
+  let mut pic_pos = 2;
+  loop {
+    if *pic_byt.get(pic_pos)? != 0xff {
+      return None;
+    }
+    let pic_marker = *pic_byt.get(pic_pos + 1)?;
+    // 填充字节
+    if pic_marker == 0xff {
+      pic_pos += 1;
+      continue;
+    }
+    // 无长度的标记
+    if matches!(pic_marker, 0x01 | 0xd0..=0xd8) {
+      strip_byt.extend_from_slice(&pic_byt[pic_pos..pic_pos + 2]);
+      pic_pos += 2;
+      continue;
+    }
+    // 扫描开始后是压缩数据，原样保留
+    if matches!(pic_marker, 0xda | 0xd9) {
+      strip_byt.extend_from_slice(&pic_byt[pic_pos..]);
+      return Some(strip_byt);
+    }
+    let seg_len = u16::from_be_bytes(pic_byt.get(pic_pos + 2..pic_pos + 4)?.try_into().ok()?);
+    let seg_end = pic_pos + 2 + seg_len as usize;
+    let seg_byt = pic_byt.get(pic_pos..seg_end)?;
+    if !(matches!(pic_marker, 0xe1 | 0xe3..=0xed | 0xef | 0xfe)) {
+      strip_byt.extend_from_slice(seg_byt);
+    }
+    pic_pos = seg_end;
+  }
+}
+
+fn strip_png(pic_byt: &[u8]) -> Option<Vec<u8>> {
+  let mut strip_byt = pic_byt.get(0..8)?.to_vec();
+  let mut pic_pos = 8;
+  while pic_pos < pic_byt.len() {
+    let chunk_len = u32::from_be_bytes(pic_byt.get(pic_pos..pic_pos + 4)?.try_into().ok()?);
+    let chunk_ilk = pic_byt.get(pic_pos + 4..pic_pos + 8)?;
+    let chunk_end = pic_pos.checked_add(12 + chunk_len as usize)?;
+    let chunk_byt = pic_byt.get(pic_pos..chunk_end)?;
+    if !matches!(chunk_ilk, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+      strip_byt.extend_from_slice(chunk_byt);
+    }
+    if chunk_ilk == b"IEND" {
+      break;
+    }
+    pic_pos = chunk_end;
+  }
+  Some(strip_byt)
+}
+
+fn strip_webp(pic_byt: &[u8]) -> Option<Vec<u8>> {
+  let mut strip_byt = pic_byt.get(0..12)?.to_vec();
+  let riff_end = (u32::from_le_bytes(pic_byt.get(4..8)?.try_into().ok()?) as usize + 8).
+    min(pic_byt.len());
+  let mut pic_pos = 12;
+  while pic_pos + 8 <= riff_end {
+    let chunk_ilk = &pic_byt[pic_pos..pic_pos + 4];
+    let chunk_len = u32::from_le_bytes(pic_byt[pic_pos + 4..pic_pos + 8].try_into().ok()?);
+    // 块的长度为奇数时有一个填充字节
+    let chunk_end = pic_pos.checked_add(8 + chunk_len as usize + (chunk_len as usize & 1))?.
+      min(riff_end);
+    let chunk_byt = pic_byt.get(pic_pos..chunk_end)?;
+    match chunk_ilk {
+      b"EXIF" | b"XMP " => {}
+      b"VP8X" => {
+        let strip_pos = strip_byt.len();
+        strip_byt.extend_from_slice(chunk_byt);
+        if let Some(vp8x_flags) = strip_byt.get_mut(strip_pos + 8) {
+          *vp8x_flags &= !(0x08 | 0x04);
+        }
+      }
+      _ => strip_byt.extend_from_slice(chunk_byt),
+    }
+    pic_pos = chunk_end;
+  }
+  let riff_len = (strip_byt.len() - 8) as u32;
+  strip_byt[4..8].copy_from_slice(&riff_len.to_le_bytes());
+  Some(strip_byt)
+}
+
+fn strip_gif(pic_byt: &[u8]) -> Option<Vec<u8>> {
+  // 跳过数据子块，返回子块序列结束后的位置
+  let gif_sub_end = |mut pic_pos: usize| -> Option<usize> {
+    loop {
+      let sub_len = *pic_byt.get(pic_pos)? as usize;
+      pic_pos += 1 + sub_len;
+      if sub_len == 0 {
+        return Some(pic_pos);
+      }
+    }
+  };
+  // 全局颜色表
+  let lsd_flags = *pic_byt.get(10)?;
+  let mut pic_pos = 13;
+  if lsd_flags & 0x80 != 0 {
+    pic_pos += 3 << ((lsd_flags & 0x07) + 1);
+  }
+  let mut strip_byt = pic_byt.get(0..pic_pos)?.to_vec();
+  loop {
+    match *pic_byt.get(pic_pos)? {
+      0x21 => {
+        let ext_label = *pic_byt.get(pic_pos + 1)?;
+        let ext_end = gif_sub_end(pic_pos + 2)?;
+        let ext_byt = pic_byt.get(pic_pos..ext_end)?;
+        let ext_keep = match ext_label {
+          0xfe => false,
+          0xff => matches!(pic_byt.get(pic_pos + 3..pic_pos + 14),
+                           Some(b"NETSCAPE2.0") | Some(b"ANIMEXTS1.0")),
+          _ => true,
+        };
+        if ext_keep {
+          strip_byt.extend_from_slice(ext_byt);
+        }
+        pic_pos = ext_end;
+      }
+      0x2c => {
+        let img_flags = *pic_byt.get(pic_pos + 9)?;
+        let mut img_pos = pic_pos + 10;
+        if img_flags & 0x80 != 0 {
+          img_pos += 3 << ((img_flags & 0x07) + 1);
+        }
+        // LZW最小码长之后是图像数据子块
+        let img_end = gif_sub_end(img_pos + 1)?;
+        strip_byt.extend_from_slice(pic_byt.get(pic_pos..img_end)?);
+        pic_pos = img_end;
+      }
+      0x3b => {
+        strip_byt.push(0x3b);
+        return Some(strip_byt);
+      }
+      _ => return None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use image::ImageFormat;
+  use super::*;
+
+  /// 带有经纬度的XMP数据包，北纬39.5度，东经116.25度
+  const META_XMP: &[u8] = b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF><rdf:Description \
+    exif:GPSLatitude=\"39,30N\" exif:GPSLongitude=\"116,15E\"/></rdf:RDF></x:xmpmeta>";
+
+  /// 只带GPS字段的小端TIFF，北纬39.5度，东经116.25度
+  fn meta_tiff() -> Vec<u8> {
+    let tiff_ent = |tiff_byt: &mut Vec<u8>, tiff_tag: u16, tiff_ilk: u16, tiff_cnt: u32,
+                    tiff_val: [u8; 4]| {
+      tiff_byt.extend_from_slice(&tiff_tag.to_le_bytes());
+      tiff_byt.extend_from_slice(&tiff_ilk.to_le_bytes());
+      tiff_byt.extend_from_slice(&tiff_cnt.to_le_bytes());
+      tiff_byt.extend_from_slice(&tiff_val);
+    };
+    let mut tiff_byt = b"II*\0\x08\0\0\0".to_vec();
+    // IFD0只有指向GPS IFD的字段，GPS IFD从26开始，其后的有理数从80开始
+    tiff_byt.extend_from_slice(&1u16.to_le_bytes());
+    tiff_ent(&mut tiff_byt, 0x8825, 4, 1, 26u32.to_le_bytes());
+    tiff_byt.extend_from_slice(&0u32.to_le_bytes());
+    tiff_byt.extend_from_slice(&4u16.to_le_bytes());
+    tiff_ent(&mut tiff_byt, 1, 2, 2, *b"N\0\0\0");
+    tiff_ent(&mut tiff_byt, 2, 5, 3, 80u32.to_le_bytes());
+    tiff_ent(&mut tiff_byt, 3, 2, 2, *b"E\0\0\0");
+    tiff_ent(&mut tiff_byt, 4, 5, 3, 104u32.to_le_bytes());
+    tiff_byt.extend_from_slice(&0u32.to_le_bytes());
+    for gps_num in [39u32, 30, 0, 116, 15, 0] {
+      tiff_byt.extend_from_slice(&gps_num.to_le_bytes());
+      tiff_byt.extend_from_slice(&1u32.to_le_bytes());
+    }
+    tiff_byt
+  }
+
+  /// 用image编码一张4x4的图片
+  fn meta_plain(pic_ilk: ImageFormat) -> Vec<u8> {
+    let pic_img = image::RgbImage::from_fn(
+      4, 4, |x, y| image::Rgb([x as u8 * 60, y as u8 * 60, 0]));
+    let mut pic_byt = Cursor::new(vec![]);
+    pic_img.write_to(&mut pic_byt, pic_ilk).unwrap();
+    pic_byt.into_inner()
+  }
+
+  fn png_crc(chunk_byt: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &chunk_val in chunk_byt {
+      crc ^= chunk_val as u32;
+      for _ in 0..8 {
+        crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+      }
+    }
+    !crc
+  }
+
+  fn png_chunk(chunk_ilk: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let mut chunk_byt = (chunk_data.len() as u32).to_be_bytes().to_vec();
+    chunk_byt.extend_from_slice(chunk_ilk);
+    chunk_byt.extend_from_slice(chunk_data);
+    chunk_byt.extend_from_slice(&png_crc(&chunk_byt[4..]).to_be_bytes());
+    chunk_byt
+  }
+
+  fn webp_chunk(chunk_ilk: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let mut chunk_byt = chunk_ilk.to_vec();
+    chunk_byt.extend_from_slice(&(chunk_data.len() as u32).to_le_bytes());
+    chunk_byt.extend_from_slice(chunk_data);
+    if chunk_data.len() % 2 == 1 {
+      chunk_byt.push(0);
+    }
+    chunk_byt
+  }
+
+  /// 在SOI之后插入EXIF与XMP的APP1段
+  fn meta_jpeg() -> Vec<u8> {
+    let pic_byt = meta_plain(ImageFormat::Jpeg);
+    let mut meta_byt = pic_byt[..2].to_vec();
+    for app1_data in [[b"Exif\0\0".as_slice(), &meta_tiff()].concat(),
+                      [b"http://ns.adobe.com/xap/1.0/\0".as_slice(), META_XMP].concat()] {
+      meta_byt.extend_from_slice(&[0xff, 0xe1]);
+      meta_byt.extend_from_slice(&(app1_data.len() as u16 + 2).to_be_bytes());
+      meta_byt.extend_from_slice(&app1_data);
+    }
+    meta_byt.extend_from_slice(&pic_byt[2..]);
+    meta_byt
+  }
+
+  /// 在IHDR之后插入eXIf与iTXt块
+  fn meta_png() -> Vec<u8> {
+    let pic_byt = meta_plain(ImageFormat::Png);
+    // 签名8字节，IHDR块25字节
+    let mut meta_byt = pic_byt[..33].to_vec();
+    meta_byt.extend(png_chunk(b"eXIf", &meta_tiff()));
+    meta_byt.extend(png_chunk(b"iTXt", &[b"XML:com.adobe.xmp\0\0\0\0\0".as_slice(), META_XMP].
+      concat()));
+    meta_byt.extend_from_slice(&pic_byt[33..]);
+    meta_byt
+  }
+
+  /// 将无损WebP改写为带VP8X的扩展格式，并附加EXIF与XMP块
+  fn meta_webp() -> Vec<u8> {
+    let pic_byt = meta_plain(ImageFormat::WebP);
+    let mut vp8x_data = vec![0x08 | 0x04, 0, 0, 0];
+    vp8x_data.extend_from_slice(&3u32.to_le_bytes()[..3]);
+    vp8x_data.extend_from_slice(&3u32.to_le_bytes()[..3]);
+    let mut riff_data = b"WEBP".to_vec();
+    riff_data.extend(webp_chunk(b"VP8X", &vp8x_data));
+    riff_data.extend_from_slice(&pic_byt[12..]);
+    riff_data.extend(webp_chunk(b"EXIF", &meta_tiff()));
+    riff_data.extend(webp_chunk(b"XMP ", META_XMP));
+    let mut meta_byt = b"RIFF".to_vec();
+    meta_byt.extend_from_slice(&(riff_data.len() as u32).to_le_bytes());
+    meta_byt.extend(riff_data);
+    meta_byt
+  }
+
+  /// 在第一个图像之前插入注释扩展与XMP应用扩展
+  fn meta_gif() -> Vec<u8> {
+    let pic_byt = meta_plain(ImageFormat::Gif);
+    let lsd_flags = pic_byt[10];
+    let mut pic_pos = 13;
+    if lsd_flags & 0x80 != 0 {
+      pic_pos += 3 << ((lsd_flags & 0x07) + 1);
+    }
+    let mut meta_byt = pic_byt[..pic_pos].to_vec();
+    meta_byt.extend_from_slice(b"\x21\xfe\x07comment\0");
+    meta_byt.extend_from_slice(b"\x21\xff\x0bXMP DataXMP");
+    meta_byt.push(META_XMP.len() as u8);
+    meta_byt.extend_from_slice(META_XMP);
+    meta_byt.push(0);
+    meta_byt.extend_from_slice(&pic_byt[pic_pos..]);
+    meta_byt
+  }
+
+  fn pic_contains(pic_byt: &[u8], pic_part: &[u8]) -> bool {
+    pic_byt.windows(pic_part.len()).any(|pic_win| pic_win == pic_part)
+  }
+
+  #[test]
+  fn pics_meta_strip_removes_meta_and_keeps_image() {
+    let meta_cases = [
+      (WeiboPicFmt::Jpeg, meta_jpeg(), ["Exif\0\0", "http://ns.adobe.com/xap/1.0/"]),
+      (WeiboPicFmt::Png, meta_png(), ["eXIf", "iTXt"]),
+      (WeiboPicFmt::Webp, meta_webp(), ["EXIF", "XMP "]),
+      (WeiboPicFmt::Gif, meta_gif(), ["XMP DataXMP", "comment"]),
+    ];
+    for (pic_fmt, meta_byt, meta_parts) in meta_cases {
+      // 原图带有元数据，并且可以读出位置
+      image::load_from_memory(&meta_byt).unwrap();
+      let pic_meta = anly_pic_meta(pic_fmt, &meta_byt);
+      assert!(pic_meta.meta_flag() && pic_meta.gps_flag, "{pic_fmt:?}");
+      assert_eq!(pic_meta.gps_lat, Some(39.5), "{pic_fmt:?}");
+      assert_eq!(pic_meta.gps_lon, Some(116.25), "{pic_fmt:?}");
+
+      let strip_byt = pics_meta_strip(pic_fmt, &meta_byt).unwrap();
+      for meta_part in meta_parts.into_iter().chain(["<x:xmpmeta"]) {
+        assert!(!pic_contains(&strip_byt, meta_part.as_bytes()), "{pic_fmt:?} {meta_part:?}");
+      }
+      let strip_meta = anly_pic_meta(pic_fmt, &strip_byt);
+      assert!(!strip_meta.meta_flag() && !strip_meta.gps_flag, "{pic_fmt:?}");
+      let strip_img = image::load_from_memory(&strip_byt).unwrap();
+      assert_eq!((strip_img.width(), strip_img.height()), (4, 4), "{pic_fmt:?}");
+    }
+  }
+
+  #[test]
+  fn pics_meta_strip_rejects_truncated() {
+    for (pic_fmt, meta_byt) in [(WeiboPicFmt::Jpeg, meta_jpeg()), (WeiboPicFmt::Png, meta_png()),
+                                (WeiboPicFmt::Gif, meta_gif())] {
+      assert_eq!(pics_meta_strip(pic_fmt, &meta_byt[..meta_byt.len() / 3]), None, "{pic_fmt:?}");
+    }
+  }
+}
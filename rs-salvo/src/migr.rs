@@ -0,0 +1,460 @@
+use hifitime::prelude::Epoch;
+use log::info;
+use rbatis::RBatis;
+use rbatis::executor::Executor;
+use crate::exceptions::WeiboError;
+use crate::utils::secs_era;
+use crate::weibo_migr_err;
+
+/// 编译进二进制文件的数据库迁移
+pub struct WeiboMigr {
+  /// 迁移版本号，从1开始连续递增
+  pub version: i64,
+  /// 迁移名称
+  pub name: &'static str,
+  /// 迁移的SQL语句，多条语句以`;`分隔
+  pub sent: &'static str,
+}
+
+/// 全部数据库迁移，按版本号递增排列。新增迁移时在migrations目录下添加SQL文件并追加到末尾，
+/// 已发布的迁移不可修改。
+pub const WEIBO_MIGRS: &[WeiboMigr] = &[
+  WeiboMigr {
+    version: 1,
+    name: "init",
+    sent: include_str!("../migrations/0001_init.sql"),
+  },
+  WeiboMigr {
+    version: 2,
+    name: "hot_search_snap",
+    sent: include_str!("../migrations/0002_hot_search_snap.sql"),
+  },
+  WeiboMigr {
+    version: 3,
+    name: "sched",
+    sent: include_str!("../migrations/0003_sched.sql"),
+  },
+  WeiboMigr {
+    version: 4,
+    name: "comm_pic_unique",
+    sent: include_str!("../migrations/0004_comm_pic_unique.sql"),
+  },
+  WeiboMigr {
+    version: 5,
+    name: "cok",
+    sent: include_str!("../migrations/0005_cok.sql"),
+  },
+  WeiboMigr {
+    version: 6,
+    name: "pic_dl",
+    sent: include_str!("../migrations/0006_pic_dl.sql"),
+  },
+  WeiboMigr {
+    version: 7,
+    name: "pic_stor",
+    sent: include_str!("../migrations/0007_pic_stor.sql"),
+  },
+  WeiboMigr {
+    version: 8,
+    name: "pic_phash",
+    sent: include_str!("../migrations/0008_pic_phash.sql"),
+  },
+  WeiboMigr {
+    version: 9,
+    name: "pic_meta",
+    sent: include_str!("../migrations/0009_pic_meta.sql"),
+  },
+  WeiboMigr {
+    version: 10,
+    name: "timeline_metr",
+    sent: include_str!("../migrations/0010_timeline_metr.sql"),
+  },
+  WeiboMigr {
+    version: 11,
+    name: "timeline_longtext",
+    sent: include_str!("../migrations/0011_timeline_longtext.sql"),
+  },
+  WeiboMigr {
+    version: 12,
+    name: "occur_utc",
+    sent: include_str!("../migrations/0012_occur_utc.sql"),
+  },
+  WeiboMigr {
+    version: 13,
+    name: "text_ent",
+    sent: include_str!("../migrations/0013_text_ent.sql"),
+  },
+  WeiboMigr {
+    version: 14,
+    name: "keyword",
+    sent: include_str!("../migrations/0014_keyword.sql"),
+  },
+  WeiboMigr {
+    version: 15,
+    name: "keyword_hit_char",
+    sent: include_str!("../migrations/0015_keyword_hit_char.sql"),
+  },
+];
+
+/// 读取数据库当前的迁移版本号，没有schema_version表时视为0
+///
+/// ## 参数
+/// - `migr_exec`：rbatis执行器，可以是数据库连接或事务
+async fn migr_version_r(migr_exec: &dyn Executor) -> Result<i64, WeiboError> {
+  let schema_version_val = migr_exec.query(
+    "select count(1) from sqlite_master where type = 'table' and name = 'schema_version'",
+    vec![]).await.
+    map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+  if rbatis::decode::<i64>(schema_version_val)? == 0 {
+    return Ok(0);
+  }
+
+  let migr_version_val = migr_exec.query(
+    "select coalesce(max(version), 0) from schema_version", vec![]).await.
+    map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+  Ok(rbatis::decode::<i64>(migr_version_val)?)
+}
+
+/// 根据数据库当前的迁移版本号筛选待执行的迁移
+///
+/// ## 参数
+/// - `weibo_migrs`: 全部迁移，按版本号递增排列
+/// - `migr_version`: 数据库当前的迁移版本号
+///
+/// ## 返回
+/// 数据库版本号高于二进制文件中最新的迁移时返回错误，拒绝继续运行；否则返回待执行的迁移
+fn migr_pending(weibo_migrs: &[WeiboMigr],
+                migr_version: i64) -> Result<Vec<&WeiboMigr>, WeiboError> {
+  let migr_latest = weibo_migrs.last().map(|weibo_migr| weibo_migr.version).unwrap_or(0);
+  if migr_version > migr_latest {
+    return Err(weibo_migr_err!(format!(
+      "database schema version {} is newer than the latest migration {} of this binary",
+      migr_version, migr_latest)));
+  }
+  Ok(weibo_migrs.iter().filter(|weibo_migr| weibo_migr.version > migr_version).collect())
+}
+
+/// 将迁移的SQL拆分为单条语句，跳过只有注释的片段。字符串、带引号的名称与注释中的分号不拆分，
+/// 触发器只在`END;`处结束
+///
+/// ## 参数
+/// - `migr_sent`: 迁移的SQL语句
+fn migr_sent_split(migr_sent: &str) -> Vec<&str> {
+  let migr_bytes = migr_sent.as_bytes();
+  let mut migr_sent_arrs = vec![];
+  // 当前语句的起点，是否有注释以外的内容，与已读到的单词
+  let mut sent_start = 0;
+  let mut sent_solid = false;
+  let mut sent_words: Vec<String> = vec![];
+  let mut word_start: Option<usize> = None;
+  let mut site = 0;
+  // 从`from`起查找`needle`，返回其后的位置，找不到时到结尾
+  let migr_skip = |from: usize, needle: &str| {
+    migr_sent[from..].find(needle).map_or(migr_bytes.len(), |skip| from + skip + needle.len())
+  };
+
+  while site < migr_bytes.len() {
+    let migr_byte = migr_bytes[site];
+    if migr_byte.is_ascii_alphanumeric() || migr_byte == b'_' {
+      word_start.get_or_insert(site);
+      sent_solid = true;
+      site += 1;
+      continue;
+    }
+    if let Some(word_start) = word_start.take() {
+      sent_words.push(migr_sent[word_start..site].to_ascii_lowercase());
+    }
+    site = match migr_byte {
+      b'-' if migr_bytes.get(site + 1) == Some(&b'-') => migr_skip(site, "\n"),
+      b'/' if migr_bytes.get(site + 1) == Some(&b'*') => migr_skip(site + 2, "*/"),
+      // 字符串中转义的''相当于连续的两个字符串
+      b'\'' | b'"' | b'`' | b'[' => {
+        sent_solid = true;
+        migr_skip(site + 1, if migr_byte == b'[' { "]" } else { &migr_sent[site..=site] })
+      }
+      b';' => {
+        let sent_trigger = match sent_words.as_slice() {
+          [create, trigger, ..] if create == "create" && trigger == "trigger" => true,
+          [create, temp, trigger, ..] => create == "create" &&
+            (temp == "temp" || temp == "temporary") && trigger == "trigger",
+          _ => false,
+        };
+        if !sent_trigger || sent_words.last().is_some_and(|word| word == "end") {
+          if sent_solid {
+            migr_sent_arrs.push(migr_sent[sent_start..site].trim());
+          }
+          sent_start = site + 1;
+          sent_solid = false;
+          sent_words.clear();
+        }
+        site + 1
+      }
+      _ => {
+        sent_solid |= !migr_byte.is_ascii_whitespace();
+        site + 1
+      }
+    };
+  }
+  if sent_solid {
+    migr_sent_arrs.push(migr_sent[sent_start..].trim());
+  }
+  migr_sent_arrs
+}
+
+/// 从`ALTER TABLE t ADD [COLUMN] c ...`语句中取出表名与列名，其他语句返回None
+///
+/// ## 参数
+/// - `migr_sent`: 单条迁移语句
+fn anly_add_column(migr_sent: &str) -> Option<(String, String)> {
+  let migr_words: Vec<&str> = migr_sent.lines().
+    filter(|migr_line| !migr_line.trim().starts_with("--")).
+    flat_map(|migr_line| migr_line.split_whitespace()).
+    collect();
+  let migr_unquote = |migr_word: &str| migr_word.trim_matches(['"', '`', '[', ']']).to_string();
+  match migr_words.as_slice() {
+    [alter, table, migr_table, add, column, migr_col, ..]
+    if alter.eq_ignore_ascii_case("alter") && table.eq_ignore_ascii_case("table") &&
+      add.eq_ignore_ascii_case("add") && column.eq_ignore_ascii_case("column") => {
+      Some((migr_unquote(migr_table), migr_unquote(migr_col)))
+    }
+    [alter, table, migr_table, add, migr_col, ..]
+    if alter.eq_ignore_ascii_case("alter") && table.eq_ignore_ascii_case("table") &&
+      add.eq_ignore_ascii_case("add") => {
+      Some((migr_unquote(migr_table), migr_unquote(migr_col)))
+    }
+    _ => None,
+  }
+}
+
+/// 查询表中是否已有该列
+///
+/// ## 参数
+/// - `migr_exec`：rbatis执行器，可以是数据库连接或事务
+/// - `migr_table`: 表名
+/// - `migr_col`: 列名
+async fn migr_column_exists(migr_exec: &dyn Executor, migr_table: &str,
+                            migr_col: &str) -> Result<bool, WeiboError> {
+  let migr_col_val = migr_exec.query(
+    "select count(1) from pragma_table_info(?) where name = ? collate nocase",
+    vec![rbs::value!(migr_table), rbs::value!(migr_col)]).await.
+    map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+  Ok(rbatis::decode::<i64>(migr_col_val)? > 0)
+}
+
+/// 打印待执行的迁移而不修改数据库
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+///
+/// ## 返回
+/// 成功则返回待执行的迁移数量
+pub async fn migr_dry_run(weibo_db_rb_conn: &RBatis) -> Result<usize, WeiboError> {
+  let migr_version = migr_version_r(weibo_db_rb_conn).await?;
+  let migr_pendings = migr_pending(WEIBO_MIGRS, migr_version)?;
+
+  println!("schema version: {}", migr_version);
+  for weibo_migr in migr_pendings.iter() {
+    println!("-- pending migration {:04}_{}", weibo_migr.version, weibo_migr.name);
+    for migr_sent_arri in migr_sent_split(weibo_migr.sent) {
+      println!("{};", migr_sent_arri);
+    }
+  }
+  if migr_pendings.is_empty() {
+    println!("no pending migrations");
+  }
+  Ok(migr_pendings.len())
+}
+
+/// 在同一个事务中执行全部待执行的迁移并记录到schema_version表，任一语句失败则整体回滚。
+/// 数据库版本号高于二进制文件中最新的迁移时返回错误，不做任何修改。
+///
+/// 为兼容迁移引入之前手动建立的数据库，`ADD COLUMN`的列已存在时跳过该语句。
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+///
+/// ## 返回
+/// 成功则返回执行的迁移数量
+pub async fn migr_apply(weibo_db_rb_conn: &RBatis) -> Result<usize, WeiboError> {
+  let migr_tx = weibo_db_rb_conn.acquire_begin().await.map_err(|flaw| {
+    WeiboError::RbatisError(flaw.to_string())
+  })?;
+
+  let migr_cnt = match migr_apply_tx(&migr_tx, WEIBO_MIGRS).await {
+    Ok(migr_cnt) => migr_cnt,
+    Err(flaw) => {
+      let _ = migr_tx.rollback().await;
+      return Err(flaw);
+    }
+  };
+  migr_tx.commit().await.map_err(|flaw| {
+    WeiboError::RbatisError(flaw.to_string())
+  })?;
+  Ok(migr_cnt)
+}
+
+/// 在事务中执行待执行的迁移
+///
+/// ## 参数
+/// - `migr_exec`：rbatis事务执行器
+/// - `weibo_migrs`: 全部迁移，按版本号递增排列
+async fn migr_apply_tx(migr_exec: &dyn Executor,
+                       weibo_migrs: &[WeiboMigr]) -> Result<usize, WeiboError> {
+  migr_exec.exec(
+    "create table if not exists schema_version \
+       (version INTEGER PRIMARY KEY, \
+        name TEXT NOT NULL, \
+        applied_era TEXT NOT NULL)", vec![]).await.
+    map_err(|flaw| {
+      WeiboError::RbatisError(flaw.to_string())
+    })?;
+
+  let migr_version = migr_version_r(migr_exec).await?;
+  let migr_pendings = migr_pending(weibo_migrs, migr_version)?;
+  for weibo_migr in migr_pendings.iter() {
+    for migr_sent_arri in migr_sent_split(weibo_migr.sent) {
+      // 只有ADD COLUMN且该列已存在时跳过，其余错误一律中止迁移
+      if let Some((migr_table, migr_col)) = anly_add_column(migr_sent_arri) &&
+        migr_column_exists(migr_exec, &migr_table, &migr_col).await? {
+        info!("migration {:04}_{}: column {}.{} already exists, skipped",
+              weibo_migr.version, weibo_migr.name, migr_table, migr_col);
+        continue;
+      }
+      migr_exec.exec(migr_sent_arri, vec![]).await.map_err(|flaw| {
+        weibo_migr_err!(format!(
+          "migration {:04}_{} failed: {}", weibo_migr.version, weibo_migr.name, flaw))
+      })?;
+    }
+
+    let applied_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+    migr_exec.exec(
+      "insert into schema_version (version, name, applied_era) values (?, ?, ?)",
+      vec![rbs::value!(weibo_migr.version), rbs::value!(weibo_migr.name),
+           rbs::value!(applied_era)]).await.
+      map_err(|flaw| {
+        WeiboError::RbatisError(flaw.to_string())
+      })?;
+    info!("migration {:04}_{} applied", weibo_migr.version, weibo_migr.name);
+  }
+  Ok(migr_pendings.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+  use crate::dbs::tests::dbs_test_raw_conn;
+  use super::*;
+
+  #[test]
+  fn anly_add_column_only_matches_add_column() {
+    let col_cases = [
+      ("ALTER TABLE weibo_hot_timeline_comm ADD COLUMN like_cnt INTEGER NOT NULL DEFAULT 0",
+       Some(("weibo_hot_timeline_comm", "like_cnt"))),
+      ("-- 注释\nalter table `weibo_hot_timeline`\n  add \"is_long\" INTEGER",
+       Some(("weibo_hot_timeline", "is_long"))),
+      ("ALTER TABLE weibo_hot_timeline RENAME COLUMN text TO talk", None),
+      ("ALTER TABLE weibo_hot_timeline DROP COLUMN text", None),
+      ("CREATE TABLE t (a INTEGER, a INTEGER)", None),
+      ("ALTER TABLE t ADD", None),
+    ];
+    for (migr_sent, migr_col) in col_cases {
+      assert_eq!(anly_add_column(migr_sent), migr_col.map(|(migr_table, migr_col)| {
+        (migr_table.to_string(), migr_col.to_string())
+      }), "{}", migr_sent);
+    }
+  }
+
+  #[test]
+  fn migr_sent_split_keeps_quoted_semicolons() {
+    let split_cases: [(&str, &[&str]); 8] = [
+      ("CREATE TABLE a (x);\nCREATE TABLE b (y);\n", &["CREATE TABLE a (x)", "CREATE TABLE b (y)"]),
+      ("INSERT INTO a VALUES (';');INSERT INTO a VALUES ('it''s;')",
+       &["INSERT INTO a VALUES (';')", "INSERT INTO a VALUES ('it''s;')"]),
+      ("CREATE TABLE \"a;b\" (`c;d` INTEGER, [e;f] TEXT)",
+       &["CREATE TABLE \"a;b\" (`c;d` INTEGER, [e;f] TEXT)"]),
+      ("-- 注释; 不是语句\nCREATE TABLE a (x); -- 行尾注释;\n",
+       &["-- 注释; 不是语句\nCREATE TABLE a (x)"]),
+      ("/* 块注释; */ CREATE TABLE a (x);\n/* 只有注释 */;", &["/* 块注释; */ CREATE TABLE a (x)"]),
+      ("CREATE TRIGGER t AFTER INSERT ON a BEGIN\n  UPDATE b SET y = 1;\n  DELETE FROM c;\nEND;\n\
+        CREATE TABLE d (z)",
+       &["CREATE TRIGGER t AFTER INSERT ON a BEGIN\n  UPDATE b SET y = 1;\n  DELETE FROM c;\nEND",
+         "CREATE TABLE d (z)"]),
+      ("create temp trigger t after delete on a begin select ';'; end; select 1",
+       &["create temp trigger t after delete on a begin select ';'; end", "select 1"]),
+      ("  \n-- 空迁移\n;;", &[]),
+    ];
+    for (migr_sent, migr_sent_arrs) in split_cases {
+      assert_eq!(migr_sent_split(migr_sent), migr_sent_arrs, "{}", migr_sent);
+    }
+    for weibo_migr in WEIBO_MIGRS {
+      assert!(migr_sent_split(weibo_migr.sent).iter().all(|migr_sent_arri| {
+        !migr_sent_arri.is_empty() && !migr_sent_arri.ends_with(';')
+      }), "{}", weibo_migr.name);
+    }
+  }
+
+  #[tokio::test]
+  async fn migr_apply_keeps_semicolon_literal() {
+    let weibo_migrs = [
+      WeiboMigr { version: 1, name: "semi", sent: "CREATE TABLE migr_semi (a TEXT);\n\
+        INSERT INTO migr_semi (a) VALUES ('x;y'), (';');\n" },
+    ];
+    let weibo_db_rb_conn = dbs_test_raw_conn("migr-semi");
+    let migr_tx = weibo_db_rb_conn.acquire_begin().await.unwrap();
+    migr_apply_tx(&migr_tx, &weibo_migrs).await.unwrap();
+    migr_tx.commit().await.unwrap();
+    let migr_semis: Vec<HashMap<String, String>> = weibo_db_rb_conn.
+      query_decode("select a from migr_semi order by rowid", vec![]).await.unwrap();
+    let migr_semis: Vec<&str> = migr_semis.iter().map(|migr_semi| migr_semi["a"].as_str()).
+      collect();
+    assert_eq!(migr_semis, ["x;y", ";"]);
+  }
+
+  #[test]
+  fn migr_pending_rejects_newer_db() {
+    let migr_latest = WEIBO_MIGRS.last().unwrap().version;
+    assert_eq!(migr_pending(WEIBO_MIGRS, 0).unwrap().len(), WEIBO_MIGRS.len());
+    assert!(migr_pending(WEIBO_MIGRS, migr_latest).unwrap().is_empty());
+    assert!(migr_pending(WEIBO_MIGRS, migr_latest + 1).is_err());
+  }
+
+  #[tokio::test]
+  async fn migr_apply_skips_existing_added_column() {
+    // 迁移引入之前手动建立的数据库：已有初始表结构，并且已手动加过like_cnt列
+    let weibo_db_rb_conn = dbs_test_raw_conn("migr-legacy");
+    for migr_sent_arri in migr_sent_split(WEIBO_MIGRS[0].sent) {
+      weibo_db_rb_conn.exec(migr_sent_arri, vec![]).await.unwrap();
+    }
+    weibo_db_rb_conn.exec(
+      "alter table weibo_hot_timeline_comm add column like_cnt INTEGER NOT NULL DEFAULT 0",
+      vec![]).await.unwrap();
+
+    assert_eq!(migr_apply(&weibo_db_rb_conn).await.unwrap(), WEIBO_MIGRS.len());
+    assert!(migr_column_exists(&weibo_db_rb_conn, "weibo_hot_timeline_comm", "reply_cnt").
+      await.unwrap());
+    assert_eq!(migr_version_r(&weibo_db_rb_conn).await.unwrap(),
+               WEIBO_MIGRS.last().unwrap().version);
+    assert_eq!(migr_apply(&weibo_db_rb_conn).await.unwrap(), 0);
+  }
+
+  #[tokio::test]
+  async fn migr_apply_fails_on_other_duplicate_column() {
+    // 建表语句中的重复列不是ADD COLUMN，不能被当作已迁移而跳过
+    let weibo_migrs = [
+      WeiboMigr { version: 1, name: "ok", sent: "CREATE TABLE migr_ok (a INTEGER)" },
+      WeiboMigr { version: 2, name: "dup", sent: "CREATE TABLE migr_dup (a INTEGER, a INTEGER)" },
+    ];
+    let weibo_db_rb_conn = dbs_test_raw_conn("migr-dup");
+    let migr_tx = weibo_db_rb_conn.acquire_begin().await.unwrap();
+    let migr_flaw = migr_apply_tx(&migr_tx, &weibo_migrs).await.unwrap_err();
+    assert!(migr_flaw.to_string().contains("0002_dup"), "{}", migr_flaw);
+    migr_tx.rollback().await.unwrap();
+    assert_eq!(migr_version_r(&weibo_db_rb_conn).await.unwrap(), 0);
+  }
+}
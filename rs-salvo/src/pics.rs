@@ -0,0 +1,546 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::path::PathBuf;
+use image::ImageReader;
+use image::Limits;
+use log::warn;
+use crate::exceptions::WeiboError;
+use crate::meta::WeiboPicMeta;
+use crate::meta::anly_pic_meta;
+use crate::meta::pics_meta_strip;
+use crate::prefs::prefs;
+use crate::weibo_io_err;
+
+/// 计算感知哈希时允许解码的最大宽度与高度
+const PHASH_DIM_MAX: u32 = 16384;
+/// 计算感知哈希时解码允许分配的最大字节数
+const PHASH_ALLOC_MAX: u64 = 256 * 1024 * 1024;
+
+/// 按魔数识别的图片格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeiboPicFmt {
+  Jpeg,
+  Png,
+  Gif,
+  Webp,
+}
+
+impl WeiboPicFmt {
+  /// 数据库中存储的格式名称，同时用作文件扩展名
+  pub fn pic_ext(&self) -> &'static str {
+    match self {
+      WeiboPicFmt::Jpeg => "jpg",
+      WeiboPicFmt::Png => "png",
+      WeiboPicFmt::Gif => "gif",
+      WeiboPicFmt::Webp => "webp",
+    }
+  }
+
+  /// MIME类型
+  pub fn pic_mime(&self) -> &'static str {
+    match self {
+      WeiboPicFmt::Jpeg => "image/jpeg",
+      WeiboPicFmt::Png => "image/png",
+      WeiboPicFmt::Gif => "image/gif",
+      WeiboPicFmt::Webp => "image/webp",
+    }
+  }
+
+  /// 由数据库中存储的格式名称还原
+  ///
+  /// ## 参数
+  /// - `pic_ext`：格式名称
+  pub fn from_ext(pic_ext: &str) -> Option<Self> {
+    match pic_ext {
+      "jpg" => Some(WeiboPicFmt::Jpeg),
+      "png" => Some(WeiboPicFmt::Png),
+      "gif" => Some(WeiboPicFmt::Gif),
+      "webp" => Some(WeiboPicFmt::Webp),
+      _ => None,
+    }
+  }
+}
+
+/// 存入图片库的图片
+#[derive(Clone, Debug)]
+pub struct WeiboPicStor {
+  // 图片文件路径
+  pub pth: String,
+  // 字节数
+  pub size: u64,
+  // 内容的SHA-256，十六进制小写
+  pub hash: String,
+  // 图片格式
+  pub fmt: WeiboPicFmt,
+  // 宽度，无法解析时为0
+  pub width: u32,
+  // 高度，无法解析时为0
+  pub height: u32,
+  // 感知哈希，无法解码时为空
+  pub phash: String,
+  // EXIF/XMP元数据
+  pub meta: WeiboPicMeta,
+}
+
+/// 按内容存储图片：文件名为内容的SHA-256，按哈希的前两级各两个字符分目录，
+/// 扩展名取自魔数识别的真实格式。相同内容的图片只存储一份，目录按需创建。
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+///
+/// ## 返回
+/// 无法识别为JPEG、PNG、GIF或WebP时返回错误
+pub async fn pics_stor(pic_byt: &[u8]) -> Result<WeiboPicStor, WeiboError> {
+  let pic_fmt = anly_pic_fmt(pic_byt).ok_or_else(|| {
+    WeiboError::SalvoError(format!("unrecognized picture format, {} bytes", pic_byt.len()))
+  })?;
+  let pic_hash = pics_hash(pic_byt);
+  let (pic_width, pic_height) = anly_pic_dims(pic_fmt, pic_byt).unwrap_or((0, 0));
+  let pic_pth = pics_pth(&pic_hash, pic_fmt);
+  let pic_phash = pics_phash_blocking(pic_byt.to_vec()).await.unwrap_or_default();
+
+  let pic_meta = anly_pic_meta(pic_fmt, pic_byt);
+
+  // 已存储过相同内容的图片时直接复用
+  if !tokio::fs::try_exists(&pic_pth).await.unwrap_or(false) {
+    pics_write(&pic_pth, pic_byt).await?;
+  }
+  // 需要对外提供去除元数据的副本时一并写入，失败时不影响下载结果，提供图片时会再次尝试
+  if prefs().hot_timeline_pic_strip && pic_meta.meta_flag() &&
+    let Err(flaw) = pics_strip_stor(&pic_hash, pic_fmt, pic_byt).await {
+    warn!("{}", flaw);
+  }
+
+  Ok(WeiboPicStor {
+    pth: pic_pth.to_string_lossy().into_owned(),
+    size: pic_byt.len() as u64,
+    hash: pic_hash,
+    fmt: pic_fmt,
+    width: pic_width,
+    height: pic_height,
+    phash: pic_phash,
+    meta: pic_meta,
+  })
+}
+
+/// 写入图片库中的文件，目录按需创建。先写入临时文件再重命名，避免并发写入相同图片时读到写了一半的文件
+///
+/// ## 参数
+/// - `pic_pth`：文件路径
+/// - `pic_byt`：文件内容
+async fn pics_write(pic_pth: &Path, pic_byt: &[u8]) -> Result<(), WeiboError> {
+  let pic_dir = pic_pth.parent().unwrap_or(Path::new("."));
+  tokio::fs::create_dir_all(pic_dir).await.map_err(|flaw| {
+    weibo_io_err!(format!("{} cannot be created, {}", pic_dir.display(), flaw))
+  })?;
+  let pic_tmp_pth = pic_pth.with_extension(format!("{}.tmp", rand::random::<u32>()));
+  tokio::fs::write(&pic_tmp_pth, pic_byt).await.map_err(|flaw| {
+    weibo_io_err!(format!("{} cannot be written, {}", pic_tmp_pth.display(), flaw))
+  })?;
+  tokio::fs::rename(&pic_tmp_pth, pic_pth).await.map_err(|flaw| {
+    weibo_io_err!(format!("{} cannot be written, {}", pic_pth.display(), flaw))
+  })
+}
+
+/// 写入去除元数据的副本
+///
+/// ## 参数
+/// - `pic_hash`：原图内容的SHA-256
+/// - `pic_fmt`：图片格式
+/// - `pic_byt`：原图内容
+///
+/// ## 返回
+/// 副本的路径
+async fn pics_strip_stor(pic_hash: &str, pic_fmt: WeiboPicFmt,
+                         pic_byt: &[u8]) -> Result<PathBuf, WeiboError> {
+  let pic_strip_byt = pics_meta_strip(pic_fmt, pic_byt).ok_or_else(|| {
+    WeiboError::SalvoError(format!("metadata of picture {} cannot be stripped", pic_hash))
+  })?;
+  let pic_strip_pth = pics_strip_pth(pic_hash, pic_fmt);
+  pics_write(&pic_strip_pth, &pic_strip_byt).await?;
+  Ok(pic_strip_pth)
+}
+
+/// 图片在图片库中的路径：`{hot_timeline_pics_pth}/ab/cd/abcd….jpg`
+///
+/// ## 参数
+/// - `pic_hash`：内容的SHA-256，十六进制小写
+/// - `pic_fmt`：图片格式
+pub fn pics_pth(pic_hash: &str, pic_fmt: WeiboPicFmt) -> PathBuf {
+  Path::new(&prefs().hot_timeline_pics_pth).
+    join(&pic_hash[0..2]).
+    join(&pic_hash[2..4]).
+    join(format!("{}.{}", pic_hash, pic_fmt.pic_ext()))
+}
+
+/// 去除元数据的副本的路径：`{hot_timeline_pics_pth}/strip/ab/cd/abcd….jpg`，文件名仍为原图内容的哈希
+///
+/// ## 参数
+/// - `pic_hash`：原图内容的SHA-256，十六进制小写
+/// - `pic_fmt`：图片格式
+pub fn pics_strip_pth(pic_hash: &str, pic_fmt: WeiboPicFmt) -> PathBuf {
+  Path::new(&prefs().hot_timeline_pics_pth).
+    join("strip").
+    join(&pic_hash[0..2]).
+    join(&pic_hash[2..4]).
+    join(format!("{}.{}", pic_hash, pic_fmt.pic_ext()))
+}
+
+/// 校验从请求中取得的图片标识，只允许字母、数字、`_`与`-`，防止拼接路径时越出图片库
+///
+/// ## 参数
+/// - `pic_key`：mid、图片id等标识
+pub fn pics_key_verify(pic_key: &str) -> bool {
+  !pic_key.is_empty() && pic_key.len() <= 64 &&
+    pic_key.bytes().all(|pic_key_byt| pic_key_byt.is_ascii_alphanumeric() ||
+      pic_key_byt == b'_' || pic_key_byt == b'-')
+}
+
+/// 校验图片内容的哈希，必须为64位十六进制小写
+///
+/// ## 参数
+/// - `pic_hash`：图片内容的SHA-256
+pub fn pics_hash_verify(pic_hash: &str) -> bool {
+  pic_hash.len() == 64 &&
+    pic_hash.bytes().all(|pic_hash_byt| matches!(pic_hash_byt, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// 在图片库中定位图片文件，解析符号链接后仍须位于图片库的根目录之下
+///
+/// ## 参数
+/// - `pic_hash`：内容的SHA-256，十六进制小写
+/// - `pic_fmt`：图片格式
+///
+/// ## 返回
+/// 文件不存在或位于图片库之外时返回None
+pub async fn pics_locate(pic_hash: &str, pic_fmt: WeiboPicFmt) -> Option<PathBuf> {
+  if !pics_hash_verify(pic_hash) {
+    return None;
+  }
+  let pics_root = tokio::fs::canonicalize(&prefs().hot_timeline_pics_pth).await.ok()?;
+  let pic_pth = tokio::fs::canonicalize(pics_pth(pic_hash, pic_fmt)).await.ok()?;
+  if !pic_pth.starts_with(&pics_root) || !tokio::fs::metadata(&pic_pth).await.ok()?.is_file() {
+    return None;
+  }
+  Some(pic_pth)
+}
+
+/// 在图片库中定位去除元数据的副本，副本不存在时由原图生成
+///
+/// ## 参数
+/// - `pic_hash`：原图内容的SHA-256，十六进制小写
+/// - `pic_fmt`：图片格式
+///
+/// ## 返回
+/// 原图不存在或无法去除元数据时返回None
+pub async fn pics_strip_locate(pic_hash: &str, pic_fmt: WeiboPicFmt) -> Option<PathBuf> {
+  let pic_pth = pics_locate(pic_hash, pic_fmt).await?;
+  let pic_strip_pth = pics_strip_pth(pic_hash, pic_fmt);
+  if !tokio::fs::try_exists(&pic_strip_pth).await.unwrap_or(false) {
+    let pic_byt = tokio::fs::read(&pic_pth).await.ok()?;
+    pics_strip_stor(pic_hash, pic_fmt, &pic_byt).await.ok()?;
+  }
+  let pics_root = tokio::fs::canonicalize(&prefs().hot_timeline_pics_pth).await.ok()?;
+  let pic_strip_pth = tokio::fs::canonicalize(pic_strip_pth).await.ok()?;
+  pic_strip_pth.starts_with(&pics_root).then_some(pic_strip_pth)
+}
+
+/// 计算内容的SHA-256，十六进制小写
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+pub fn pics_hash(pic_byt: &[u8]) -> String {
+  ring::digest::digest(&ring::digest::SHA256, pic_byt).as_ref().iter().
+    map(|pic_hash_byt| format!("{:02x}", pic_hash_byt)).
+    collect()
+}
+
+/// 计算图片的感知哈希（dHash）：缩放为9x8的灰度图，逐行比较相邻像素的亮度，
+/// 得到64位的指纹，十六进制小写。重新压缩、缩放与轻微调色后的图片指纹相近。
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+///
+/// ## 返回
+/// 无法解码，或宽高、内存占用超出限制时返回None
+pub fn pics_phash(pic_byt: &[u8]) -> Option<String> {
+  // 图片可能来自上传，先按头部限制尺寸与内存，防止解码炸弹
+  let mut pic_limits = Limits::default();
+  pic_limits.max_image_width = Some(PHASH_DIM_MAX);
+  pic_limits.max_image_height = Some(PHASH_DIM_MAX);
+  pic_limits.max_alloc = Some(PHASH_ALLOC_MAX);
+  let mut pic_reader = ImageReader::new(Cursor::new(pic_byt)).with_guessed_format().ok()?;
+  pic_reader.limits(pic_limits);
+  let pic_luma = pic_reader.decode().ok()?.
+    resize_exact(9, 8, image::imageops::FilterType::Triangle).
+    to_luma8();
+  let mut pic_phash: u64 = 0;
+  for pic_y in 0..8 {
+    for pic_x in 0..8 {
+      pic_phash <<= 1;
+      if pic_luma.get_pixel(pic_x, pic_y)[0] < pic_luma.get_pixel(pic_x + 1, pic_y)[0] {
+        pic_phash |= 1;
+      }
+    }
+  }
+  Some(format!("{:016x}", pic_phash))
+}
+
+/// 在阻塞线程池中计算感知哈希，解码大图时不占用异步运行时
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+pub async fn pics_phash_blocking(pic_byt: Vec<u8>) -> Option<String> {
+  tokio::task::spawn_blocking(move || pics_phash(&pic_byt)).await.ok()?
+}
+
+/// 两个感知哈希之间的汉明距离
+///
+/// ## 参数
+/// - `pic_phash_l`：感知哈希
+/// - `pic_phash_r`：感知哈希
+///
+/// ## 返回
+/// 任一哈希格式不正确时返回None
+pub fn pics_phash_dist(pic_phash_l: &str, pic_phash_r: &str) -> Option<u32> {
+  let pic_phash_l = u64::from_str_radix(pic_phash_l, 16).ok()?;
+  let pic_phash_r = u64::from_str_radix(pic_phash_r, 16).ok()?;
+  Some((pic_phash_l ^ pic_phash_r).count_ones())
+}
+
+/// 按魔数识别图片格式
+///
+/// ## 参数
+/// - `pic_byt`：图片内容
+pub fn anly_pic_fmt(pic_byt: &[u8]) -> Option<WeiboPicFmt> {
+  if pic_byt.starts_with(&[0xff, 0xd8, 0xff]) {
+    Some(WeiboPicFmt::Jpeg)
+  } else if pic_byt.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some(WeiboPicFmt::Png)
+  } else if pic_byt.starts_with(b"GIF87a") || pic_byt.starts_with(b"GIF89a") {
+    Some(WeiboPicFmt::Gif)
+  } else if pic_byt.len() >= 12 && &pic_byt[0..4] == b"RIFF" && &pic_byt[8..12] == b"WEBP" {
+    Some(WeiboPicFmt::Webp)
+  } else {
+    None
+  }
+}
+
+/// 从图片头部解析宽度与高度
+///
+/// ## 参数
+/// - `pic_fmt`：图片格式
+/// - `pic_byt`：图片内容
+pub fn anly_pic_dims(pic_fmt: WeiboPicFmt, pic_byt: &[u8]) -> Option<(u32, u32)> {
+  let be_u16 = |pos: usize| -> Option<u32> {
+    Some(u16::from_be_bytes(pic_byt.get(pos..pos + 2)?.try_into().ok()?) as u32)
+  };
+  let le_u16 = |pos: usize| -> Option<u32> {
+    Some(u16::from_le_bytes(pic_byt.get(pos..pos + 2)?.try_into().ok()?) as u32)
+  };
+  let le_u24 = |pos: usize| -> Option<u32> {
+    let pic_dim_byt = pic_byt.get(pos..pos + 3)?;
+    Some(pic_dim_byt[0] as u32 | (pic_dim_byt[1] as u32) << 8 | (pic_dim_byt[2] as u32) << 16)
+  };
+
+  match pic_fmt {
+    WeiboPicFmt::Png => {
+      // 8字节签名之后的第一个块是IHDR
+      if pic_byt.get(12..16)? != b"IHDR" {
+        return None;
+      }
+      let pic_width = u32::from_be_bytes(pic_byt.get(16..20)?.try_into().ok()?);
+      let pic_height = u32::from_be_bytes(pic_byt.get(20..24)?.try_into().ok()?);
+      Some((pic_width, pic_height))
+    }
+    WeiboPicFmt::Gif => Some((le_u16(6)?, le_u16(8)?)),
+    WeiboPicFmt::Webp => match pic_byt.get(12..16)? {
+      b"VP8 " => Some((le_u16(26)? & 0x3fff, le_u16(28)? & 0x3fff)),
+      b"VP8L" => {
+        let pic_dim_bits = u32::from_le_bytes(pic_byt.get(21..25)?.try_into().ok()?);
+        Some(((pic_dim_bits & 0x3fff) + 1, ((pic_dim_bits >> 14) & 0x3fff) + 1))
+      }
+      b"VP8X" => Some((le_u24(24)? + 1, le_u24(27)? + 1)),
+      _ => None,
+    },
+    WeiboPicFmt::Jpeg => {
+      // 逐个跳过段，直到帧开始段SOFn（不包括DHT、JPG、DAC）
+      let mut pic_pos = 2;
+      loop {
+        while *pic_byt.get(pic_pos)? != 0xff {
+          pic_pos += 1;
+        }
+        while *pic_byt.get(pic_pos)? == 0xff {
+          pic_pos += 1;
+        }
+        let pic_marker = *pic_byt.get(pic_pos)?;
+        pic_pos += 1;
+        if matches!(pic_marker, 0xd8 | 0x01 | 0xd0..=0xd7) {
+          continue;
+        }
+        if matches!(pic_marker, 0xc0..=0xcf) && !matches!(pic_marker, 0xc4 | 0xc8 | 0xcc) {
+          return Some((be_u16(pic_pos + 5)?, be_u16(pic_pos + 3)?));
+        }
+        pic_pos += be_u16(pic_pos)? as usize;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use image::ImageFormat;
+  use super::*;
+
+  /// 用image编码一张灰度图片，`pic_luma`由坐标给出亮度
+  fn pics_plain(pic_ilk: ImageFormat, pic_width: u32, pic_height: u32,
+                pic_luma: impl Fn(u32, u32) -> u8) -> Vec<u8> {
+    let pic_img = image::GrayImage::from_fn(
+      pic_width, pic_height, |x, y| image::Luma([pic_luma(x, y)]));
+    let mut pic_byt = Cursor::new(vec![]);
+    pic_img.write_to(&mut pic_byt, pic_ilk).unwrap();
+    pic_byt.into_inner()
+  }
+
+  #[test]
+  fn pics_phash_dist_counts_bits() {
+    assert_eq!(pics_phash_dist("00ff00ff00ff00ff", "00ff00ff00ff00ff"), Some(0));
+    assert_eq!(pics_phash_dist("0000000000000000", "ffffffffffffffff"), Some(64));
+    assert_eq!(pics_phash_dist("0000000000000001", "8000000000000000"), Some(2));
+    assert_eq!(pics_phash_dist("00000000000000f0", "0"), Some(4));
+    for pic_phash in ["", "xyz", "1ffffffffffffffff", "-1"] {
+      assert_eq!(pics_phash_dist(pic_phash, "0000000000000000"), None, "{:?}", pic_phash);
+    }
+  }
+
+  #[test]
+  fn pics_phash_follows_gradient() {
+    // 从左到右变亮时每一位都是1，变暗时都是0
+    let pic_brighter = pics_plain(ImageFormat::Png, 90, 80, |x, _| (x * 2) as u8);
+    assert_eq!(pics_phash(&pic_brighter).unwrap(), "ffffffffffffffff");
+    let pic_darker = pics_plain(ImageFormat::Png, 90, 80, |x, _| 255 - (x * 2) as u8);
+    assert_eq!(pics_phash(&pic_darker).unwrap(), "0000000000000000");
+
+    // 重新压缩与缩放后的指纹相近
+    let pic_wave = |x: u32, y: u32| ((x * 7 + y * 3) % 97 * 2 + (x / 20) * 30) as u8;
+    let pic_phash = pics_phash(&pics_plain(ImageFormat::Png, 180, 160, pic_wave)).unwrap();
+    let pic_jpeg_phash = pics_phash(&pics_plain(ImageFormat::Jpeg, 180, 160, pic_wave)).unwrap();
+    let pic_small_phash = pics_phash(&pics_plain(ImageFormat::Png, 90, 80, |x, y| {
+      pic_wave(x * 2, y * 2)
+    })).unwrap();
+    assert!(pics_phash_dist(&pic_phash, &pic_jpeg_phash).unwrap() <= 6);
+    assert!(pics_phash_dist(&pic_phash, &pic_small_phash).unwrap() <= 10);
+  }
+
+  #[test]
+  fn pics_phash_rejects_undecodable_and_oversized() {
+    assert_eq!(pics_phash(b""), None);
+    assert_eq!(pics_phash(b"not a picture"), None);
+    let pic_png = pics_plain(ImageFormat::Png, 16, 16, |x, y| (x * y) as u8);
+    assert_eq!(pics_phash(&pic_png[..pic_png.len() / 2]), None);
+    // 宽度超出限制的图片不解码
+    let pic_wide = pics_plain(ImageFormat::Png, PHASH_DIM_MAX + 1, 1, |x, _| x as u8);
+    assert_eq!(pics_phash(&pic_wide), None);
+    assert!(pics_phash(&pics_plain(ImageFormat::Png, PHASH_DIM_MAX, 1, |x, _| x as u8)).is_some());
+  }
+
+  /// 用给定的块拼出WebP文件
+  fn pics_webp(webp_chunk: &[u8; 4], webp_pay: &[u8]) -> Vec<u8> {
+    let mut webp_byt = b"RIFF".to_vec();
+    webp_byt.extend_from_slice(&(4 + 8 + webp_pay.len() as u32).to_le_bytes());
+    webp_byt.extend_from_slice(b"WEBP");
+    webp_byt.extend_from_slice(webp_chunk);
+    webp_byt.extend_from_slice(&(webp_pay.len() as u32).to_le_bytes());
+    webp_byt.extend_from_slice(webp_pay);
+    webp_byt
+  }
+
+  #[test]
+  fn anly_pic_fmt_dims_from_encoders() {
+    let pic_luma = |x: u32, y: u32| (x + y) as u8;
+    for (pic_ilk, pic_fmt) in [(ImageFormat::Png, WeiboPicFmt::Png),
+                               (ImageFormat::Jpeg, WeiboPicFmt::Jpeg),
+                               (ImageFormat::WebP, WeiboPicFmt::Webp)] {
+      let pic_byt = pics_plain(pic_ilk, 90, 37, pic_luma);
+      assert_eq!(anly_pic_fmt(&pic_byt), Some(pic_fmt), "{:?}", pic_ilk);
+      assert_eq!(anly_pic_dims(pic_fmt, &pic_byt), Some((90, 37)), "{:?}", pic_ilk);
+    }
+    // image的GIF编码器只接受RGBA
+    let mut pic_gif = Cursor::new(vec![]);
+    image::RgbaImage::from_pixel(90, 37, image::Rgba([1, 2, 3, 255])).
+      write_to(&mut pic_gif, ImageFormat::Gif).unwrap();
+    let pic_gif = pic_gif.into_inner();
+    assert_eq!(anly_pic_fmt(&pic_gif), Some(WeiboPicFmt::Gif));
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Gif, &pic_gif), Some((90, 37)));
+  }
+
+  #[test]
+  fn anly_pic_dims_from_headers() {
+    // GIF87a同样识别
+    let pic_gif = b"GIF87a\x2c\x01\xc8\x00\x00\x00\x00";
+    assert_eq!(anly_pic_fmt(pic_gif), Some(WeiboPicFmt::Gif));
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Gif, pic_gif), Some((300, 200)));
+
+    // 有损VP8：起始码之后是14位宽高，高2位为缩放比例
+    let mut vp8_pay = vec![0x10, 0x02, 0x00, 0x9d, 0x01, 0x2a];
+    vp8_pay.extend_from_slice(&(0x4000u16 | 300).to_le_bytes());
+    vp8_pay.extend_from_slice(&(0xc000u16 | 200).to_le_bytes());
+    let pic_vp8 = pics_webp(b"VP8 ", &vp8_pay);
+    assert_eq!(anly_pic_fmt(&pic_vp8), Some(WeiboPicFmt::Webp));
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Webp, &pic_vp8), Some((300, 200)));
+
+    // 无损VP8L：签名0x2f之后是宽减一与高减一，各14位
+    let mut vp8l_pay = vec![0x2f];
+    vp8l_pay.extend_from_slice(&(299u32 | 199 << 14).to_le_bytes());
+    let pic_vp8l = pics_webp(b"VP8L", &vp8l_pay);
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Webp, &pic_vp8l), Some((300, 200)));
+
+    // 扩展格式VP8X：4字节标志之后是画布宽减一与高减一，各24位
+    let mut vp8x_pay = vec![0x10, 0, 0, 0];
+    vp8x_pay.extend_from_slice(&69999u32.to_le_bytes()[..3]);
+    vp8x_pay.extend_from_slice(&199u32.to_le_bytes()[..3]);
+    let pic_vp8x = pics_webp(b"VP8X", &vp8x_pay);
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Webp, &pic_vp8x), Some((70000, 200)));
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Webp, &pics_webp(b"ALPH", &[0; 10])), None);
+
+    // 渐进式JPEG，SOF2之前有DHT（0xc4），不能当作帧开始段
+    let mut pic_jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00];
+    pic_jpeg.extend_from_slice(&[0xff, 0xc4, 0x00, 0x07, 0x00, 0x10, 0x00, 0x20, 0x00]);
+    pic_jpeg.extend_from_slice(&[0xff, 0xff, 0xc2, 0x00, 0x11, 0x08, 0x00, 0xc8, 0x01, 0x2c]);
+    assert_eq!(anly_pic_fmt(&pic_jpeg), Some(WeiboPicFmt::Jpeg));
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Jpeg, &pic_jpeg), Some((300, 200)));
+  }
+
+  #[test]
+  fn anly_pic_fmt_dims_reject_truncated() {
+    for pic_byt in [&b""[..], b"\xff\xd8", b"\x89PNG\r\n", b"GIF89", b"RIFF\0\0\0\0WEB",
+                    b"RIFF\0\0\0\0AVI ", b"BM\0\0"] {
+      assert_eq!(anly_pic_fmt(pic_byt), None, "{:?}", pic_byt);
+    }
+
+    let pic_png = pics_plain(ImageFormat::Png, 90, 37, |x, _| x as u8);
+    let pic_jpeg = pics_plain(ImageFormat::Jpeg, 90, 37, |x, _| x as u8);
+    let pic_webp = pics_webp(b"VP8X", &[0; 10]);
+    let pic_gif = b"GIF89a\x2c\x01\xc8\x00".as_slice();
+    for (pic_fmt, pic_byt, pic_len) in [(WeiboPicFmt::Png, pic_png.as_slice(), 24),
+                                        (WeiboPicFmt::Jpeg, pic_jpeg.as_slice(), 0),
+                                        (WeiboPicFmt::Webp, pic_webp.as_slice(), 30),
+                                        (WeiboPicFmt::Gif, pic_gif, 10)] {
+      // 截断到宽高之前的任意长度都返回None，不能越界
+      let pic_len = if pic_len == 0 {
+        // JPEG的宽高在SOF段中，截断到SOF段结束之前
+        let pic_sof = pic_byt.windows(2).position(|pic_marker| pic_marker == [0xff, 0xc0]).
+          unwrap();
+        pic_sof + 9
+      } else {
+        pic_len
+      };
+      for pic_cut in 0..pic_len {
+        assert_eq!(anly_pic_dims(pic_fmt, &pic_byt[..pic_cut]), None, "{:?} {}", pic_fmt, pic_cut);
+      }
+      assert!(anly_pic_dims(pic_fmt, &pic_byt[..pic_len]).is_some(), "{:?}", pic_fmt);
+    }
+    // 段长度越过文件末尾
+    assert_eq!(anly_pic_dims(WeiboPicFmt::Jpeg, b"\xff\xd8\xff\xe0\xff\xff\x00"), None);
+  }
+}
@@ -1,4 +1,512 @@
-pub const WEIBO_DB_PTH: &str = "./weibo.db";
-pub const WEIBO_HOT_TIMELINE_PICS_PTH: &str = "./weibo_hot_timeline_pics";
-pub const WEIBO_COK: &str = "SUB=_2AkMfmjsOf8NxqwFRmvsXyG_mZIt_yQzEieKpxsrVJRMxH\
-                                Rl-yT9kqlA7tRB6NBoV4ZGJe5Iw-S2YDB_0-D8LEMJWYViw";
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use serde::Deserialize;
+use serde::Serialize;
+use crate::exceptions::WeiboError;
+use crate::tls::anly_spki_pins;
+use crate::wm::WmOverlap;
+use crate::weibo_prefs_err;
+
+/// 未通过`--prefs`或`WEIBO_PREFS`指定时读取的配置文件，不存在时使用默认配置
+pub const WEIBO_PREFS_PTH: &str = "./weibo.toml";
+/// 不带参数值的命令行开关，解析配置时跳过
+pub const WEIBO_PREFS_FLAGS: &[&str] = &["--migrate-dry-run"];
+
+static WEIBO_PREFS: OnceLock<WeiboPrefs> = OnceLock::new();
+
+/// 运行配置，优先级从低到高依次为：默认值、TOML配置文件、`WEIBO_*`环境变量、命令行参数。
+///
+/// 配置项`hot_timeline_page_size`在配置文件中写作`hot_timeline_page_size = 10`，
+/// 对应环境变量`WEIBO_HOT_TIMELINE_PAGE_SIZE=10`与命令行参数`--hot-timeline-page-size 10`。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeiboPrefs {
+  /// sqlite数据库文件路径
+  pub db_pth: String,
+  /// 批量upsert时每条语句的最大行数
+  pub db_upsert_chk: usize,
+  /// HTTP服务监听地址
+  pub bind_addr: String,
+  /// log4rs配置文件路径
+  pub log4rs_pth: String,
+  /// 微博ajax接口的基础URL，以`/`结尾
+  pub base_url: String,
+  /// 请求微博时携带的Referer
+  pub referer: String,
+  /// 请求微博时携带的User-Agent
+  pub user_agent: String,
+  /// cookie池为空时请求微博携带的Cookie，为空时使用访客cookie
+  pub cok: String,
+  /// cookie池与cok都为空时，是否通过访客系统自动获取访客cookie
+  pub visitor: bool,
+  /// 微博访客系统的基础URL，以`/`结尾
+  pub visitor_url: String,
+  /// 访客cookie过期前提前刷新的秒数
+  pub visitor_refresh_secs: u64,
+  /// 访客系统没有返回过期时间时，访客cookie的有效秒数
+  pub visitor_ttl_secs: u64,
+  /// cookie被判定为失效或被封禁后的隔离时长
+  pub cok_quarantine_secs: u64,
+  /// 单次请求因cookie失效换用其他cookie的最多尝试次数
+  pub cok_attempt_max: u32,
+  /// 单次HTTP请求的超时时间
+  pub request_timeout_secs: u64,
+  /// 全局限流每秒补充的请求令牌数
+  pub gain_rate_per_sec: f64,
+  /// 全局限流最多积累的请求令牌数，即允许的突发请求数
+  pub gain_burst: u32,
+  /// 单次请求遇到超时、5xx、429等暂时性失败时的最多发出次数
+  pub gain_attempt_max: u32,
+  /// 重试的初始退避毫秒数，每次失败后翻倍
+  pub gain_backoff_ms: u64,
+  /// 重试的最长退避秒数，Retry-After超过该值时同样截断
+  pub gain_backoff_max_secs: u64,
+  /// 信任的CA证书包（PEM），为空时使用内置的Mozilla根证书
+  pub tls_ca_pth: String,
+  /// 以`,`分隔的公钥指纹（证书SubjectPublicKeyInfo的SHA-256，base64编码），为空时不固定公钥
+  pub tls_spki_pins: String,
+  /// 以`,`分隔的需要校验公钥指纹的域名，包括其子域名
+  pub tls_pin_hosts: String,
+  /// 不校验服务器证书，仅用于调试
+  pub tls_insecure: bool,
+  /// 热门推荐图片库的根目录，图片按内容的SHA-256分两级子目录存储，目录按需创建
+  pub hot_timeline_pics_pth: String,
+  /// 并发下载热门推荐图片的最大数量
+  pub hot_timeline_pic_workers: usize,
+  /// 单张热门推荐图片的最多下载次数，达到后不再重试
+  pub hot_timeline_pic_attempt_max: u32,
+  /// 通过/pics提供去除EXIF/XMP等元数据后的副本，而不是原图
+  pub hot_timeline_pic_strip: bool,
+  /// 查找相似图片时上传图片的最大字节数
+  pub pic_upload_max_bytes: usize,
+  /// 定时爬取调度器检查任务的间隔
+  pub sched_tick_secs: u64,
+  /// 爬取热门推荐的默认页数
+  pub hot_timeline_page_cnt: u32,
+  /// 热门推荐每页的条数
+  pub hot_timeline_page_size: u32,
+  /// 爬取热门推荐的最大页数
+  pub hot_timeline_page_max: u32,
+  /// 爬取热门推荐评论的默认页数
+  pub hot_timeline_comm_page_cnt: u32,
+  /// 爬取热门推荐评论的最大页数
+  pub hot_timeline_comm_page_max: u32,
+  /// 爬取单条根评论下回复的最大页数
+  pub hot_timeline_comm_reply_page_max: u32,
+  /// 单条热门推荐最多保存的评论条数
+  pub hot_timeline_comm_max: usize,
+  /// 关键词监控匹配前折叠大小写
+  pub keyword_fold_case: bool,
+  /// 关键词监控匹配前将全角字符折叠为半角
+  pub keyword_fold_width: bool,
+  /// 关键词监控匹配前将常用繁体字折叠为简体字
+  pub keyword_fold_trad: bool,
+  /// 关键词监控匹配前删除零宽字符与插在文字之间的空白、分隔符号
+  pub keyword_fold_strip: bool,
+  /// `redact=true`时替换正文与评论中命中关键词的内容
+  pub redact_repl: String,
+  /// `redact=true`时重叠命中的取舍策略：leftmost_longest、leftmost_first或all
+  pub redact_overlap: String,
+}
+
+impl Default for WeiboPrefs {
+  fn default() -> Self {
+    Self {
+      db_pth: "./weibo.db".into(),
+      db_upsert_chk: 100,
+      bind_addr: "0.0.0.0:5800".into(),
+      log4rs_pth: "weibo-log4rs.yml".into(),
+      base_url: "https://weibo.com/ajax/".into(),
+      referer: "https://weibo.com/newlogin".into(),
+      user_agent: "Mozilla/5.0 (X11; Linux x86_64) \
+                   AppleWebKit/537.36 (KHTML, like Gecko) \
+                   Chrome/130.0.0.0 Safari/537.36".into(),
+      cok: String::new(),
+      visitor: true,
+      visitor_url: "https://passport.weibo.com/visitor/".into(),
+      visitor_refresh_secs: 600,
+      visitor_ttl_secs: 86400,
+      cok_quarantine_secs: 3600,
+      cok_attempt_max: 3,
+      request_timeout_secs: 30,
+      gain_rate_per_sec: 2.0,
+      gain_burst: 5,
+      gain_attempt_max: 4,
+      gain_backoff_ms: 500,
+      gain_backoff_max_secs: 60,
+      tls_ca_pth: String::new(),
+      tls_spki_pins: String::new(),
+      tls_pin_hosts: "weibo.com,sinaimg.cn".into(),
+      tls_insecure: false,
+      hot_timeline_pics_pth: "./weibo_hot_timeline_pics".into(),
+      hot_timeline_pic_workers: 4,
+      hot_timeline_pic_attempt_max: 5,
+      hot_timeline_pic_strip: false,
+      pic_upload_max_bytes: 10 * 1024 * 1024,
+      sched_tick_secs: 5,
+      hot_timeline_page_cnt: 1,
+      hot_timeline_page_size: 10,
+      hot_timeline_page_max: 50,
+      hot_timeline_comm_page_cnt: 5,
+      hot_timeline_comm_page_max: 50,
+      hot_timeline_comm_reply_page_max: 5,
+      hot_timeline_comm_max: 1000,
+      keyword_fold_case: false,
+      keyword_fold_width: false,
+      keyword_fold_trad: false,
+      keyword_fold_strip: false,
+      redact_repl: "***".into(),
+      redact_overlap: "leftmost_longest".into(),
+    }
+  }
+}
+
+/// 获取运行配置。main()启动时通过`prefs_init`载入，未载入时返回默认配置。
+pub fn prefs() -> &'static WeiboPrefs {
+  WEIBO_PREFS.get_or_init(WeiboPrefs::default)
+}
+
+/// 载入并校验运行配置，之后可通过`prefs()`获取
+///
+/// ## 参数
+/// - `prefs_args`: 命令行参数，不含程序名
+pub fn prefs_init(prefs_args: &[String]) -> Result<&'static WeiboPrefs, WeiboError> {
+  let weibo_prefs = prefs_load(prefs_args, &std::env::vars().collect())?;
+  Ok(WEIBO_PREFS.get_or_init(|| weibo_prefs))
+}
+
+/// 依次合并配置文件、环境变量与命令行参数并校验
+///
+/// ## 参数
+/// - `prefs_args`: 命令行参数，不含程序名
+/// - `prefs_envs`: 环境变量
+///
+/// ## 返回
+/// 任一配置来源有误或校验失败时返回描述全部问题的错误
+pub fn prefs_load(prefs_args: &[String], prefs_envs: &HashMap<String, String>,
+) -> Result<WeiboPrefs, WeiboError> {
+  let prefs_cli = prefs_cli_anly(prefs_args)?;
+
+  // 配置文件路径：命令行参数优先于环境变量，显式指定的文件必须存在
+  let prefs_pth = prefs_cli.iter().
+    find(|(prefs_key, _)| prefs_key == "prefs").
+    map(|(_, prefs_val)| prefs_val.clone()).
+    or_else(|| prefs_envs.get("WEIBO_PREFS").cloned());
+  let mut prefs_tbl = match &prefs_pth {
+    Some(prefs_pth) => prefs_file_r(prefs_pth)?,
+    None if std::path::Path::new(WEIBO_PREFS_PTH).exists() => prefs_file_r(WEIBO_PREFS_PTH)?,
+    None => toml::Table::new(),
+  };
+
+  let prefs_dft = toml::Table::try_from(WeiboPrefs::default()).map_err(|flaw| {
+    weibo_prefs_err!(flaw)
+  })?;
+  for prefs_key in prefs_dft.keys() {
+    let prefs_env = format!("WEIBO_{}", prefs_key.to_uppercase());
+    if let Some(prefs_val) = prefs_envs.get(&prefs_env) {
+      prefs_ovr(&mut prefs_tbl, &prefs_dft, prefs_key, prefs_val, &prefs_env)?;
+    }
+  }
+  for (prefs_key, prefs_val) in prefs_cli.iter().filter(|(prefs_key, _)| prefs_key != "prefs") {
+    let prefs_flag = format!("--{}", prefs_key.replace('_', "-"));
+    prefs_ovr(&mut prefs_tbl, &prefs_dft, prefs_key, prefs_val, &prefs_flag)?;
+  }
+
+  // 逐项单独反序列化，以便错误信息带上配置项名称
+  for (prefs_key, prefs_val) in prefs_tbl.iter() {
+    let mut prefs_chk = prefs_dft.clone();
+    prefs_chk.insert(prefs_key.clone(), prefs_val.clone());
+    prefs_chk.try_into::<WeiboPrefs>().map_err(|flaw| {
+      weibo_prefs_err!(format!("invalid config: {}: {}", prefs_key, flaw.message()))
+    })?;
+  }
+  let weibo_prefs: WeiboPrefs = prefs_tbl.try_into().map_err(|flaw: toml::de::Error| {
+    weibo_prefs_err!(format!("invalid config: {}", flaw.message()))
+  })?;
+  weibo_prefs.prefs_verify()?;
+  Ok(weibo_prefs)
+}
+
+/// 读取TOML配置文件
+///
+/// ## 参数
+/// - `prefs_pth`: 配置文件路径
+fn prefs_file_r(prefs_pth: &str) -> Result<toml::Table, WeiboError> {
+  let prefs_talk = std::fs::read_to_string(prefs_pth).map_err(|flaw| {
+    weibo_prefs_err!(format!("cannot read config file {}: {}", prefs_pth, flaw))
+  })?;
+  prefs_talk.parse::<toml::Table>().map_err(|flaw| {
+    weibo_prefs_err!(format!("cannot parse config file {}: {}", prefs_pth, flaw))
+  })
+}
+
+/// 将命令行参数解析为(配置项, 值)，支持`--key value`与`--key=value`
+///
+/// ## 参数
+/// - `prefs_args`: 命令行参数，不含程序名
+fn prefs_cli_anly(prefs_args: &[String]) -> Result<Vec<(String, String)>, WeiboError> {
+  let mut prefs_cli = vec![];
+  let mut prefs_arg_iter = prefs_args.iter();
+  while let Some(prefs_arg) = prefs_arg_iter.next() {
+    if WEIBO_PREFS_FLAGS.contains(&prefs_arg.as_str()) {
+      continue;
+    }
+    let Some(prefs_flag) = prefs_arg.strip_prefix("--") else {
+      return Err(weibo_prefs_err!(format!("unexpected argument: {}", prefs_arg)));
+    };
+    let (prefs_key, prefs_val) = match prefs_flag.split_once('=') {
+      Some((prefs_key, prefs_val)) => (prefs_key, prefs_val.to_string()),
+      None => {
+        let prefs_val = prefs_arg_iter.next().ok_or_else(|| {
+          weibo_prefs_err!(format!("missing value for {}", prefs_arg))
+        })?;
+        (prefs_flag, prefs_val.clone())
+      }
+    };
+    prefs_cli.push((prefs_key.replace('-', "_"), prefs_val));
+  }
+  Ok(prefs_cli)
+}
+
+/// 以字符串形式的值覆盖配置项，按默认配置中的类型转换
+///
+/// ## 参数
+/// - `prefs_tbl`: 待覆盖的配置
+/// - `prefs_dft`: 默认配置，用于确定配置项的类型
+/// - `prefs_key`: 配置项
+/// - `prefs_val`: 字符串形式的值
+/// - `prefs_src`: 值的来源，用于错误信息
+fn prefs_ovr(prefs_tbl: &mut toml::Table, prefs_dft: &toml::Table, prefs_key: &str,
+             prefs_val: &str, prefs_src: &str) -> Result<(), WeiboError> {
+  let prefs_typed = match prefs_dft.get(prefs_key) {
+    Some(toml::Value::Integer(_)) => toml::Value::Integer(prefs_val.parse().map_err(|_| {
+      weibo_prefs_err!(format!("{}: expected an integer, got '{}'", prefs_src, prefs_val))
+    })?),
+    Some(toml::Value::Float(_)) => toml::Value::Float(prefs_val.parse().map_err(|_| {
+      weibo_prefs_err!(format!("{}: expected a number, got '{}'", prefs_src, prefs_val))
+    })?),
+    Some(toml::Value::Boolean(_)) => toml::Value::Boolean(prefs_val.parse().map_err(|_| {
+      weibo_prefs_err!(format!("{}: expected true or false, got '{}'", prefs_src, prefs_val))
+    })?),
+    Some(_) => toml::Value::String(prefs_val.to_string()),
+    None => return Err(weibo_prefs_err!(format!("unknown option: {}", prefs_src))),
+  };
+  prefs_tbl.insert(prefs_key.to_string(), prefs_typed);
+  Ok(())
+}
+
+impl WeiboPrefs {
+  /// 校验配置的取值范围
+  ///
+  /// ## 返回
+  /// 校验失败时返回列出全部问题的错误
+  pub fn prefs_verify(&self) -> Result<(), WeiboError> {
+    let mut prefs_flaws: Vec<String> = vec![];
+    for (prefs_key, prefs_val) in [("db_pth", &self.db_pth), ("log4rs_pth", &self.log4rs_pth),
+                                   ("user_agent", &self.user_agent),
+                                   ("hot_timeline_pics_pth", &self.hot_timeline_pics_pth)] {
+      if prefs_val.trim().is_empty() {
+        prefs_flaws.push(format!("{} must not be empty", prefs_key));
+      }
+    }
+    if self.bind_addr.parse::<SocketAddr>().is_err() {
+      prefs_flaws.push(format!("bind_addr '{}' is not a valid socket address", self.bind_addr));
+    }
+    if !(self.base_url.starts_with("http://") || self.base_url.starts_with("https://")) ||
+      !self.base_url.ends_with('/') {
+      prefs_flaws.push(format!("base_url '{}' must be an http(s) URL ending with '/'",
+                               self.base_url));
+    }
+    if !(self.visitor_url.starts_with("http://") || self.visitor_url.starts_with("https://")) ||
+      !self.visitor_url.ends_with('/') {
+      prefs_flaws.push(format!("visitor_url '{}' must be an http(s) URL ending with '/'",
+                               self.visitor_url));
+    }
+    if self.visitor_ttl_secs <= self.visitor_refresh_secs {
+      prefs_flaws.push("visitor_ttl_secs must be greater than visitor_refresh_secs".to_string());
+    }
+    if !(self.referer.starts_with("http://") || self.referer.starts_with("https://")) {
+      prefs_flaws.push(format!("referer '{}' must be an http(s) URL", self.referer));
+    }
+    if self.cok.contains(['\r', '\n']) {
+      prefs_flaws.push("cok must not contain line breaks".to_string());
+    }
+    if self.cok_attempt_max == 0 {
+      prefs_flaws.push("cok_attempt_max must be positive".to_string());
+    }
+    if !(1..=1000).contains(&self.db_upsert_chk) {
+      prefs_flaws.push("db_upsert_chk must be between 1 and 1000".to_string());
+    }
+    if self.request_timeout_secs == 0 {
+      prefs_flaws.push("request_timeout_secs must be positive".to_string());
+    }
+    if !(self.gain_rate_per_sec.is_finite() && self.gain_rate_per_sec > 0.0) {
+      prefs_flaws.push("gain_rate_per_sec must be a positive number".to_string());
+    }
+    if self.gain_burst == 0 {
+      prefs_flaws.push("gain_burst must be positive".to_string());
+    }
+    if self.gain_attempt_max == 0 {
+      prefs_flaws.push("gain_attempt_max must be positive".to_string());
+    }
+    if self.gain_backoff_ms == 0 {
+      prefs_flaws.push("gain_backoff_ms must be positive".to_string());
+    }
+    if self.gain_backoff_max_secs * 1000 < self.gain_backoff_ms {
+      prefs_flaws.push("gain_backoff_max_secs must not be less than gain_backoff_ms".to_string());
+    }
+    if !self.tls_ca_pth.is_empty() && !std::path::Path::new(&self.tls_ca_pth).is_file() {
+      prefs_flaws.push(format!("tls_ca_pth '{}' is not a file", self.tls_ca_pth));
+    }
+    if let Err(WeiboError::PrefsError(flaw)) = anly_spki_pins(&self.tls_spki_pins) {
+      prefs_flaws.push(flaw);
+    }
+    if self.tls_insecure && !(self.tls_ca_pth.is_empty() && self.tls_spki_pins.is_empty()) {
+      prefs_flaws.push("tls_insecure cannot be combined with tls_ca_pth or tls_spki_pins".
+        to_string());
+    }
+    if !(1..=64).contains(&self.hot_timeline_pic_workers) {
+      prefs_flaws.push("hot_timeline_pic_workers must be between 1 and 64".to_string());
+    }
+    if self.hot_timeline_pic_attempt_max == 0 {
+      prefs_flaws.push("hot_timeline_pic_attempt_max must be positive".to_string());
+    }
+    if self.pic_upload_max_bytes == 0 {
+      prefs_flaws.push("pic_upload_max_bytes must be positive".to_string());
+    }
+    if self.sched_tick_secs == 0 {
+      prefs_flaws.push("sched_tick_secs must be positive".to_string());
+    }
+    if !(1..=100).contains(&self.hot_timeline_page_size) {
+      prefs_flaws.push("hot_timeline_page_size must be between 1 and 100".to_string());
+    }
+    if self.hot_timeline_page_cnt == 0 ||
+      self.hot_timeline_page_cnt > self.hot_timeline_page_max {
+      prefs_flaws.push(format!("hot_timeline_page_cnt must be between 1 and \
+                                hot_timeline_page_max ({})", self.hot_timeline_page_max));
+    }
+    if self.hot_timeline_comm_page_cnt == 0 ||
+      self.hot_timeline_comm_page_cnt > self.hot_timeline_comm_page_max {
+      prefs_flaws.push(format!("hot_timeline_comm_page_cnt must be between 1 and \
+                                hot_timeline_comm_page_max ({})",
+                               self.hot_timeline_comm_page_max));
+    }
+    if self.hot_timeline_comm_reply_page_max == 0 {
+      prefs_flaws.push("hot_timeline_comm_reply_page_max must be positive".to_string());
+    }
+    if self.hot_timeline_comm_max == 0 {
+      prefs_flaws.push("hot_timeline_comm_max must be positive".to_string());
+    }
+    if WmOverlap::anly_overlap(&self.redact_overlap).is_none() {
+      prefs_flaws.push(format!("redact_overlap '{}' must be leftmost_longest, leftmost_first \
+                                or all", self.redact_overlap));
+    }
+
+    if prefs_flaws.is_empty() {
+      Ok(())
+    } else {
+      Err(weibo_prefs_err!(format!("invalid config: {}", prefs_flaws.join("; "))))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 测试用的环境变量，(变量名, 值)
+  type PrefsTestEnvs<'a> = &'a [(&'a str, &'a str)];
+
+  /// 写入临时配置文件并返回路径
+  fn prefs_test_file(prefs_name: &str, prefs_talk: &str) -> String {
+    let prefs_pth = std::env::temp_dir().
+      join(format!("rs-salvo-{}-{}.toml", prefs_name, std::process::id()));
+    std::fs::write(&prefs_pth, prefs_talk).unwrap();
+    prefs_pth.display().to_string()
+  }
+
+  fn prefs_test_args(prefs_args: &[&str]) -> Vec<String> {
+    prefs_args.iter().map(|prefs_arg| prefs_arg.to_string()).collect()
+  }
+
+  fn prefs_test_envs(prefs_envs: PrefsTestEnvs) -> HashMap<String, String> {
+    prefs_envs.iter().map(|(prefs_key, prefs_val)| {
+      (prefs_key.to_string(), prefs_val.to_string())
+    }).collect()
+  }
+
+  #[test]
+  fn prefs_load_precedence() {
+    let prefs_pth = prefs_test_file(
+      "precedence", "request_timeout_secs = 11\ngain_rate_per_sec = 1.5\ntls_insecure = true\n");
+    // (命令行参数, 环境变量, 期望的request_timeout_secs)
+    let prefs_cases: [(&[&str], PrefsTestEnvs, u64); 7] = [
+      (&[], &[], 11),
+      (&[], &[("WEIBO_REQUEST_TIMEOUT_SECS", "22")], 22),
+      (&["--request-timeout-secs", "33"], &[], 33),
+      (&["--request-timeout-secs", "33"], &[("WEIBO_REQUEST_TIMEOUT_SECS", "22")], 33),
+      (&["--request_timeout_secs=44"], &[("WEIBO_REQUEST_TIMEOUT_SECS", "22")], 44),
+      // 不带参数值的开关不影响解析
+      (&["--request-timeout-secs", "33", "--migrate-dry-run"], &[], 33),
+      // 与配置项无关的环境变量被忽略
+      (&[], &[("WEIBO_NOT_A_PREF", "1"), ("REQUEST_TIMEOUT_SECS", "22")], 11),
+    ];
+    for (prefs_args, prefs_envs, request_timeout_secs) in prefs_cases {
+      let mut prefs_args = prefs_test_args(prefs_args);
+      prefs_args.extend(prefs_test_args(&["--prefs", &prefs_pth]));
+      let weibo_prefs = prefs_load(&prefs_args, &prefs_test_envs(prefs_envs)).unwrap();
+      assert_eq!(weibo_prefs.request_timeout_secs, request_timeout_secs,
+                 "{:?} {:?}", prefs_args, prefs_envs);
+      // 未覆盖的配置项取自配置文件，配置文件中没有的取默认值
+      assert_eq!(weibo_prefs.gain_rate_per_sec, 1.5);
+      assert!(weibo_prefs.tls_insecure);
+      assert_eq!(weibo_prefs.gain_burst, WeiboPrefs::default().gain_burst);
+    }
+  }
+
+  #[test]
+  fn prefs_load_file_selection() {
+    let prefs_cli_pth = prefs_test_file("cli", "gain_burst = 7\n");
+    let prefs_env_pth = prefs_test_file("env", "gain_burst = 8\n");
+    // --prefs优先于WEIBO_PREFS
+    let weibo_prefs = prefs_load(&prefs_test_args(&["--prefs", &prefs_cli_pth]),
+                                 &prefs_test_envs(&[("WEIBO_PREFS", &prefs_env_pth)])).unwrap();
+    assert_eq!(weibo_prefs.gain_burst, 7);
+    let weibo_prefs = prefs_load(&[], &prefs_test_envs(&[("WEIBO_PREFS", &prefs_env_pth)])).
+      unwrap();
+    assert_eq!(weibo_prefs.gain_burst, 8);
+    // 显式指定的配置文件必须存在
+    assert!(prefs_load(&prefs_test_args(&["--prefs", "/nonexistent/weibo.toml"]),
+                       &HashMap::new()).is_err());
+  }
+
+  #[test]
+  fn prefs_load_rejects_invalid() {
+    let prefs_pth = prefs_test_file("invalid", "");
+    let prefs_bad_pth = prefs_test_file("invalid-type", "request_timeout_secs = \"30\"\n");
+    let prefs_unknown_pth = prefs_test_file("invalid-key", "request_timeout = 30\n");
+    // (命令行参数, 环境变量, 错误信息中应包含的内容)
+    let prefs_cases: [(Vec<String>, PrefsTestEnvs, &str); 9] = [
+      (prefs_test_args(&["--prefs", &prefs_pth]), &[("WEIBO_REQUEST_TIMEOUT_SECS", "abc")],
+       "WEIBO_REQUEST_TIMEOUT_SECS"),
+      (prefs_test_args(&["--prefs", &prefs_pth, "--tls-insecure", "yes"]), &[], "--tls-insecure"),
+      (prefs_test_args(&["--prefs", &prefs_pth, "--no-such-pref", "1"]), &[], "--no-such-pref"),
+      (prefs_test_args(&["--prefs", &prefs_pth, "--request-timeout-secs"]), &[],
+       "missing value"),
+      (prefs_test_args(&["--prefs", &prefs_pth, "request-timeout-secs"]), &[],
+       "unexpected argument"),
+      (prefs_test_args(&["--prefs", &prefs_bad_pth]), &[], "request_timeout_secs"),
+      (prefs_test_args(&["--prefs", &prefs_unknown_pth]), &[], "request_timeout"),
+      // 高优先级的来源中的错误值同样被校验
+      (prefs_test_args(&["--prefs", &prefs_pth, "--hot-timeline-page-cnt", "0"]), &[],
+       "hot_timeline_page_cnt"),
+      (prefs_test_args(&["--prefs", &prefs_pth]), &[("WEIBO_BASE_URL", "ftp://weibo.com")],
+       "base_url"),
+    ];
+    for (prefs_args, prefs_envs, prefs_flaw_des) in prefs_cases {
+      let prefs_flaw = prefs_load(&prefs_args, &prefs_test_envs(prefs_envs)).unwrap_err();
+      assert!(prefs_flaw.to_string().contains(prefs_flaw_des), "{:?}: {}", prefs_args, prefs_flaw);
+    }
+  }
+}
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use hifitime::efmt::consts::ISO8601_DATE;
+use hifitime::prelude::Epoch;
+use hifitime::prelude::Formatter;
+use log::debug;
+use log::error;
+use log::info;
+use log::warn;
+use nyquest::AsyncClient;
+use rand::Rng;
+use rbatis::RBatis;
+use crate::dbs::*;
+use crate::exceptions::WeiboError;
+use crate::prefs::prefs;
+use crate::utils;
+use crate::utils::era_secs;
+use crate::utils::secs_era;
+use crate::weibo_sched_err;
+
+/// 定时爬取调度器，在main()中以独立的tokio任务启动。
+///
+/// 每隔`sched_tick_secs`秒从weibo_sched表读取任务配置，因此通过`/u/sched`修改的配置
+/// 无需重启即可生效。到期且上一次运行已结束的任务会派生为独立的tokio任务执行；
+/// 上一次运行尚未结束的任务本轮跳过，不会重叠执行。
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+pub async fn sched_loop(weibo_clt: AsyncClient, weibo_db_rb_conn: RBatis) {
+  // 正在运行中的任务名称
+  let sched_runs: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+  let mut sched_tick = tokio::time::interval(Duration::from_secs(prefs().sched_tick_secs));
+  loop {
+    sched_tick.tick().await;
+    if let Err(flaw) = sched_tick_once(&weibo_clt, &weibo_db_rb_conn, &sched_runs).await {
+      error!("sched: {}", flaw);
+    }
+  }
+}
+
+/// 校验任务配置能否被调度，供`/u/sched`在写入前使用
+///
+/// ## 参数
+/// - `weibo_sched`：定时爬取任务
+pub fn sched_verify(weibo_sched: &WeiboSched) -> Result<(), WeiboError> {
+  if !matches!(weibo_sched.job.as_str(),
+               "hot_search" | "hot_timeline" | "hot_timeline_comm" | "hot_timeline_pic") {
+    return Err(weibo_sched_err!(format!("unknown sched job {}", weibo_sched.job)));
+  }
+  // 停用的任务允许既无cron也无固定间隔，但已填写的cron仍需合法
+  if !weibo_sched.enabled && weibo_sched.cron.trim().is_empty() && weibo_sched.interval_secs == 0 {
+    return Ok(());
+  }
+  let nub_secs = Epoch::now()?.to_unix_seconds() as i64;
+  sched_next_secs(weibo_sched, nub_secs).map(|_| ())
+}
+
+/// 调度器的一轮检查
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `sched_runs`：正在运行中的任务名称
+async fn sched_tick_once(weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
+                         sched_runs: &Arc<Mutex<HashSet<String>>>) -> Result<(), WeiboError> {
+  let nub_secs = Epoch::now()?.to_unix_seconds() as i64;
+
+  for weibo_sched in WeiboSched::weibo_sched_r(weibo_db_rb_conn, None).await? {
+    if !weibo_sched.enabled {
+      continue;
+    }
+
+    // 尚未安排下次运行时间（首次启用或配置刚被修改）
+    let Some(next_secs) = era_secs(&weibo_sched.next_era) else {
+      // 固定间隔的任务从上次运行时间起算，重启后不会提前或重复运行；cron任务不补跑
+      let from_secs = if weibo_sched.cron.trim().is_empty() {
+        let lapse_secs = nub_secs - weibo_sched.interval_secs as i64;
+        era_secs(&weibo_sched.last_era).unwrap_or(lapse_secs).max(lapse_secs)
+      } else {
+        nub_secs
+      };
+      match sched_next_secs(&weibo_sched, from_secs) {
+        Ok(next_secs) => {
+          info!("sched: job {} next run at {}", weibo_sched.job, secs_era(next_secs));
+          WeiboSched::weibo_sched_era_u(
+            weibo_db_rb_conn, &weibo_sched.job, None, &secs_era(next_secs), None, None).await?;
+        }
+        Err(flaw) => warn!("sched: job {} cannot be scheduled, {}", weibo_sched.job, flaw),
+      }
+      continue;
+    };
+    if next_secs > nub_secs {
+      continue;
+    }
+
+    // 防止重叠：上一次运行尚未结束时跳过本轮
+    let Some(sched_run_guard) = SchedRunGuard::hold(sched_runs, &weibo_sched.job) else {
+      debug!("sched: job {} is still running, skipped", weibo_sched.job);
+      continue;
+    };
+
+    let weibo_clt = weibo_clt.clone();
+    let weibo_db_rb_conn = weibo_db_rb_conn.clone();
+    tokio::spawn(async move {
+      // 任务结束或panic时均会释放
+      let _sched_run_guard = sched_run_guard;
+      let last_era = secs_era(nub_secs);
+      info!("sched: job {} started", weibo_sched.job);
+      let sched_rst = sched_job_run(&weibo_clt, &weibo_db_rb_conn, &weibo_sched).await;
+
+      let fin_secs = Epoch::now().map(|era_val| era_val.to_unix_seconds() as i64).
+        unwrap_or(nub_secs);
+      let next_era = sched_next_secs(&weibo_sched, fin_secs).map(secs_era).unwrap_or_default();
+      let (last_sta, last_flaw) = match &sched_rst {
+        Ok(_) => {
+          info!("sched: job {} finished in {}s, next run at {}",
+                weibo_sched.job, fin_secs - nub_secs, next_era);
+          ("ok", String::new())
+        }
+        Err(flaw) => {
+          error!("sched: job {} failed in {}s, {}, next run at {}",
+                 weibo_sched.job, fin_secs - nub_secs, flaw, next_era);
+          ("flaw", flaw.to_string())
+        }
+      };
+      if let Err(flaw) = WeiboSched::weibo_sched_era_u(
+        &weibo_db_rb_conn, &weibo_sched.job, Some(&last_era), &next_era,
+        Some(last_sta), Some(&last_flaw)).await {
+        error!("sched: job {} cannot record its run, {}", weibo_sched.job, flaw);
+      }
+    });
+  }
+
+  Ok(())
+}
+
+/// 正在运行中的任务标记，drop时将任务名称移出`sched_runs`
+///
+/// 任务panic时同样会被drop，避免任务永远处于运行中；锁中毒时直接取回内部数据，
+/// 单个任务的panic不会影响其他任务的调度。
+struct SchedRunGuard {
+  sched_runs: Arc<Mutex<HashSet<String>>>,
+  job: String,
+}
+
+impl SchedRunGuard {
+  /// 标记任务为运行中，任务已在运行时返回None
+  ///
+  /// ## 参数
+  /// - `sched_runs`：正在运行中的任务名称
+  /// - `job`：任务名称
+  fn hold(sched_runs: &Arc<Mutex<HashSet<String>>>, job: &str) -> Option<Self> {
+    sched_runs.lock().unwrap_or_else(PoisonError::into_inner).
+      insert(job.to_string()).
+      then(|| Self { sched_runs: sched_runs.clone(), job: job.to_string() })
+  }
+}
+
+impl Drop for SchedRunGuard {
+  fn drop(&mut self) {
+    self.sched_runs.lock().unwrap_or_else(PoisonError::into_inner).remove(&self.job);
+  }
+}
+
+/// 执行一次定时爬取任务
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `weibo_sched`：定时爬取任务
+async fn sched_job_run(weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
+                       weibo_sched: &WeiboSched) -> Result<(), WeiboError> {
+  match weibo_sched.job.as_str() {
+    "hot_search" => utils::attain_ajax_hotsearch(weibo_clt, weibo_db_rb_conn).await,
+    "hot_timeline" => utils::attain_ajax_hottimeline(
+      weibo_clt, weibo_db_rb_conn, weibo_sched.pic, weibo_sched.comm,
+      prefs().hot_timeline_page_cnt, prefs().hot_timeline_page_size).await.map(|_| ()),
+    "hot_timeline_comm" => {
+      // 重新爬取当天热门推荐的评论
+      let nub_era = Formatter::new(Epoch::now()?, ISO8601_DATE).to_string();
+      let timeline_arrs = WeiboHotTimeline::weibo_hot_timeline_r(
+        weibo_db_rb_conn, None, None, None, Some(nub_era), &WeiboEraSpan::default(), false,
+        false).await?;
+      let mut timeline_flaw_cnt = 0;
+      for timeline_arri in timeline_arrs.iter() {
+        if let Err(flaw) = utils::attain_ajax_comments_hottimeline(
+          weibo_clt, weibo_db_rb_conn, &timeline_arri.timeline.mid,
+          &timeline_arri.timeline.mem_id, prefs().hot_timeline_comm_page_cnt).await {
+          warn!("sched: comments of {} cannot be crawled, {}", timeline_arri.timeline.mid, flaw);
+          timeline_flaw_cnt += 1;
+        }
+      }
+      if timeline_flaw_cnt > 0 {
+        return Err(weibo_sched_err!(format!("{} of {} comment crawls failed",
+                                            timeline_flaw_cnt, timeline_arrs.len())));
+      }
+      Ok(())
+    }
+    "hot_timeline_pic" => {
+      // 重试下载失败的图片，并为缺少感知哈希或元数据的图片补充
+      let (pic_suc_cnt, pic_flaw_cnt) = utils::attain_sinaimg_hot_timeline(
+        weibo_clt, weibo_db_rb_conn, None).await?;
+      utils::attain_pic_phash_hot_timeline(weibo_db_rb_conn).await?;
+      utils::attain_pic_meta_hot_timeline(weibo_db_rb_conn).await?;
+      if pic_flaw_cnt > 0 {
+        return Err(weibo_sched_err!(format!("{} of {} picture downloads failed",
+                                            pic_flaw_cnt, pic_suc_cnt + pic_flaw_cnt)));
+      }
+      Ok(())
+    }
+    _ => Err(weibo_sched_err!(format!("unknown sched job {}", weibo_sched.job))),
+  }
+}
+
+/// 计算任务的下次运行时间，cron表达式优先于固定间隔，并叠加随机抖动
+///
+/// ## 参数
+/// - `weibo_sched`：定时爬取任务
+/// - `from_secs`：起算时间，UNIX秒
+fn sched_next_secs(weibo_sched: &WeiboSched, from_secs: i64) -> Result<i64, WeiboError> {
+  let jitter_secs = if weibo_sched.jitter_secs > 0 {
+    rand::rng().random_range(0..=weibo_sched.jitter_secs as i64)
+  } else {
+    0
+  };
+
+  if !weibo_sched.cron.trim().is_empty() {
+    let sched_cron = SchedCron::parse(&weibo_sched.cron)?;
+    Ok(sched_cron.next_secs(from_secs)? + jitter_secs)
+  } else if weibo_sched.interval_secs > 0 {
+    Ok(from_secs + weibo_sched.interval_secs as i64 + jitter_secs)
+  } else {
+    Err(weibo_sched_err!("neither cron nor interval_secs is set"))
+  }
+}
+
+/// cron表达式，五个字段依次为分、时、日、月、周（0和7均为周日），按UTC计算
+///
+/// 每个字段支持`*`、`a`、`a-b`、`*/n`、`a-b/n`、`a/n`以及以逗号分隔的组合。
+/// 日和周同时受限时，两者满足其一即可，与常见的cron实现一致。
+#[derive(Debug)]
+struct SchedCron {
+  minutes: u64,
+  hours: u64,
+  days: u64,
+  months: u64,
+  weekdays: u64,
+  days_any: bool,
+  weekdays_any: bool,
+}
+
+impl SchedCron {
+  /// 解析cron表达式
+  fn parse(cron_talk: &str) -> Result<Self, WeiboError> {
+    let cron_fields: Vec<&str> = cron_talk.split_whitespace().collect();
+    if cron_fields.len() != 5 {
+      return Err(weibo_sched_err!(format!("cron {} should have 5 fields", cron_talk)));
+    }
+
+    let mut weekdays = Self::parse_field(cron_fields[4], 0, 7)?;
+    // 7与0同为周日
+    if weekdays & (1 << 7) != 0 {
+      weekdays = (weekdays | 1) & !(1 << 7);
+    }
+
+    Ok(Self {
+      minutes: Self::parse_field(cron_fields[0], 0, 59)?,
+      hours: Self::parse_field(cron_fields[1], 0, 23)?,
+      days: Self::parse_field(cron_fields[2], 1, 31)?,
+      months: Self::parse_field(cron_fields[3], 1, 12)?,
+      weekdays,
+      days_any: cron_fields[2].starts_with('*'),
+      weekdays_any: cron_fields[4].starts_with('*'),
+    })
+  }
+
+  /// 解析cron表达式的单个字段，返回允许取值的位图
+  fn parse_field(cron_field: &str, low: u32, high: u32) -> Result<u64, WeiboError> {
+    let field_flaw = || weibo_sched_err!(format!("invalid cron field {}", cron_field));
+
+    let mut field_bits: u64 = 0;
+    for field_part in cron_field.split(',') {
+      let (part_rng, part_step) = match field_part.split_once('/') {
+        Some((part_rng, part_step)) => (part_rng, Some(
+          part_step.parse::<u32>().map_err(|_| field_flaw())?)),
+        None => (field_part, None),
+      };
+      let (rng_start, rng_end) = if part_rng == "*" {
+        (low, high)
+      } else if let Some((rng_start, rng_end)) = part_rng.split_once('-') {
+        (rng_start.parse::<u32>().map_err(|_| field_flaw())?,
+         rng_end.parse::<u32>().map_err(|_| field_flaw())?)
+      } else {
+        let rng_start = part_rng.parse::<u32>().map_err(|_| field_flaw())?;
+        // a/n表示从a开始到最大值，每n个取一次
+        (rng_start, if part_step.is_some() { high } else { rng_start })
+      };
+
+      let part_step = part_step.unwrap_or(1);
+      if part_step == 0 || rng_start < low || rng_end > high || rng_start > rng_end {
+        return Err(field_flaw());
+      }
+      for field_val in (rng_start..=rng_end).step_by(part_step as usize) {
+        field_bits |= 1 << field_val;
+      }
+    }
+    Ok(field_bits)
+  }
+
+  /// 计算晚于`from_secs`的第一个满足表达式的整分钟
+  fn next_secs(&self, from_secs: i64) -> Result<i64, WeiboError> {
+    let mut next_secs = from_secs - from_secs.rem_euclid(60) + 60;
+    // 最多向后查找四年，覆盖只在闰年2月29日触发的表达式
+    let limit_secs = next_secs + 4 * 366 * 86400;
+
+    while next_secs < limit_secs {
+      let next_era = Epoch::from_unix_seconds(next_secs as f64);
+      let (_, month, day, hour, minute, _, _) = next_era.to_gregorian_utc();
+      // hifitime中周一为0，cron中周日为0
+      let weekday = (u8::from(next_era.weekday_utc()) + 1) % 7;
+
+      let day_fits = match (self.days_any, self.weekdays_any) {
+        (false, false) => self.days & (1 << day) != 0 || self.weekdays & (1 << weekday) != 0,
+        _ => self.days & (1 << day) != 0 && self.weekdays & (1 << weekday) != 0,
+      };
+      if self.months & (1 << month) == 0 || !day_fits {
+        next_secs += 86400 - (hour as i64 * 3600 + minute as i64 * 60);
+        continue;
+      }
+      if self.hours & (1 << hour) == 0 {
+        next_secs += 3600 - minute as i64 * 60;
+        continue;
+      }
+      if self.minutes & (1 << minute) == 0 {
+        next_secs += 60;
+        continue;
+      }
+      return Ok(next_secs);
+    }
+
+    Err(weibo_sched_err!("cron never fires"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 2024-01-01 00:00:00 UTC，周一
+  const ERA_2024_SECS: i64 = 1704067200;
+
+  fn sched_job(enabled: bool, interval_secs: u32, cron: &str) -> WeiboSched {
+    WeiboSched {
+      id: None,
+      job: "hot_search".to_string(),
+      enabled,
+      interval_secs,
+      cron: cron.to_string(),
+      jitter_secs: 0,
+      pic: false,
+      comm: false,
+      last_era: String::new(),
+      next_era: String::new(),
+      last_sta: String::new(),
+      last_flaw: String::new(),
+    }
+  }
+
+  #[test]
+  fn sched_cron_parse_fields() {
+    let sched_cron = SchedCron::parse("*/15 8-10 1,15 * 1-5").unwrap();
+    assert_eq!(sched_cron.minutes, (1 << 0) | (1 << 15) | (1 << 30) | (1 << 45));
+    assert_eq!(sched_cron.hours, (1 << 8) | (1 << 9) | (1 << 10));
+    assert_eq!(sched_cron.days, (1 << 1) | (1 << 15));
+    assert_eq!(sched_cron.months, 0b1_1111_1111_1110);
+    assert_eq!(sched_cron.weekdays, 0b11_1110);
+    assert!(!sched_cron.days_any);
+    assert!(!sched_cron.weekdays_any);
+
+    // a/n从a开始到最大值，a-b/n在范围内按步长
+    assert_eq!(SchedCron::parse_field("50/5", 0, 59).unwrap(), (1 << 50) | (1 << 55));
+    assert_eq!(SchedCron::parse_field("1-9/4", 0, 59).unwrap(), (1 << 1) | (1 << 5) | (1 << 9));
+    // 7与0同为周日
+    assert_eq!(SchedCron::parse("0 0 * * 7").unwrap().weekdays, 1);
+    assert_eq!(SchedCron::parse("0 0 * * 0,7").unwrap().weekdays, 1);
+  }
+
+  #[test]
+  fn sched_cron_parse_rejects_invalid() {
+    for cron_talk in [
+      "", "* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *",
+      "* * * * 8", "*/0 * * * *", "5-1 * * * *", "a * * * *", "1- * * * *", "*/x * * * *",
+      "1,,2 * * * *",
+    ] {
+      assert!(SchedCron::parse(cron_talk).is_err(), "{:?} should be rejected", cron_talk);
+    }
+  }
+
+  #[test]
+  fn sched_cron_next_secs() {
+    // (cron表达式, 起算时间, 期望的下次运行时间)
+    let cron_cases = [
+      // 严格晚于起算时间的下一个整分钟
+      ("* * * * *", ERA_2024_SECS, ERA_2024_SECS + 60),
+      ("* * * * *", ERA_2024_SECS + 59, ERA_2024_SECS + 60),
+      ("30 2 * * *", ERA_2024_SECS, ERA_2024_SECS + 2 * 3600 + 30 * 60),
+      // 当天已过，顺延到次日
+      ("0 0 * * *", ERA_2024_SECS, ERA_2024_SECS + 86400),
+      // 2024-01-06为周六
+      ("0 12 * * 6", ERA_2024_SECS, ERA_2024_SECS + 5 * 86400 + 12 * 3600),
+      // 日和周同时受限时满足其一即可：1月3日为周三，早于1月10日
+      ("0 0 10 * 3", ERA_2024_SECS, ERA_2024_SECS + 2 * 86400),
+      // 2024-02-29
+      ("0 0 29 2 *", ERA_2024_SECS, ERA_2024_SECS + 59 * 86400),
+      // 2024-03-01 00:00，跨月
+      ("0 0 1 3 *", ERA_2024_SECS, ERA_2024_SECS + 60 * 86400),
+    ];
+    for (cron_talk, from_secs, next_secs) in cron_cases {
+      assert_eq!(SchedCron::parse(cron_talk).unwrap().next_secs(from_secs).unwrap(), next_secs,
+                 "{}", cron_talk);
+    }
+
+    // 2月30日永远不会触发
+    assert!(SchedCron::parse("0 0 30 2 *").unwrap().next_secs(ERA_2024_SECS).is_err());
+  }
+
+  #[test]
+  fn sched_next_secs_prefers_cron() {
+    let sched_cron_job = sched_job(true, 600, "0 * * * *");
+    assert_eq!(sched_next_secs(&sched_cron_job, ERA_2024_SECS).unwrap(), ERA_2024_SECS + 3600);
+    let sched_interval_job = sched_job(true, 600, " ");
+    assert_eq!(sched_next_secs(&sched_interval_job, ERA_2024_SECS).unwrap(), ERA_2024_SECS + 600);
+  }
+
+  #[test]
+  fn sched_verify_disabled_without_schedule() {
+    assert!(sched_verify(&sched_job(false, 0, "")).is_ok());
+    assert!(sched_verify(&sched_job(true, 0, "")).is_err());
+    // 停用的任务填写了cron时仍需合法
+    assert!(sched_verify(&sched_job(false, 0, "61 * * * *")).is_err());
+    let mut sched_unknown_job = sched_job(false, 0, "");
+    sched_unknown_job.job = "hot_unknown".to_string();
+    assert!(sched_verify(&sched_unknown_job).is_err());
+  }
+
+  #[tokio::test]
+  async fn sched_run_guard_released_on_panic() {
+    let sched_runs: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+    let sched_run_guard = SchedRunGuard::hold(&sched_runs, "hot_search").unwrap();
+    // 已在运行中的任务不能重复标记
+    assert!(SchedRunGuard::hold(&sched_runs, "hot_search").is_none());
+
+    let sched_runs_clone = sched_runs.clone();
+    let sched_rst = tokio::spawn(async move {
+      let _sched_run_guard = sched_run_guard;
+      // 持锁时panic，使锁中毒
+      let _sched_runs_lock = sched_runs_clone.lock().unwrap();
+      panic!("sched job panicked");
+    }).await;
+    assert!(sched_rst.is_err());
+    assert!(sched_runs.is_poisoned());
+
+    assert!(sched_runs.lock().unwrap_or_else(PoisonError::into_inner).is_empty());
+    assert!(SchedRunGuard::hold(&sched_runs, "hot_search").is_some());
+  }
+}
@@ -0,0 +1,582 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use log::error;
+use nyquest_interface::Body;
+use nyquest_interface::Method;
+use nyquest_interface::client::ClientOptions;
+use nyquest_interface::r#async::AsyncBackend;
+use nyquest_interface::r#async::AsyncClient;
+use nyquest_interface::r#async::AsyncResponse;
+use nyquest_interface::r#async::Request;
+use nyquest_interface::r#async::futures_io::AsyncRead;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use rustls::ClientConfig;
+use rustls::DigitallySignedStruct;
+use rustls::RootCertStore;
+use rustls::SignatureScheme;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::HandshakeSignatureValid;
+use rustls::client::danger::ServerCertVerified;
+use rustls::client::danger::ServerCertVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::ServerName;
+use rustls::pki_types::UnixTime;
+use rustls::pki_types::pem::PemObject;
+use crate::exceptions::WeiboError;
+use crate::prefs::WeiboPrefs;
+use crate::weibo_prefs_err;
+
+/// 基于reqwest与rustls的nyquest异步后端，替代不带根证书、无法校验证书的curl后端。
+///
+/// 默认使用内置的Mozilla根证书校验服务器证书，配置`tls_ca_pth`时只信任该CA证书包；
+/// 配置`tls_spki_pins`时，对`tls_pin_hosts`中的域名及其子域名额外校验证书链中的公钥指纹。
+pub struct WeiboTlsBackend {
+  // 信任的根证书
+  tls_roots: Arc<RootCertStore>,
+  // 公钥指纹，即证书SubjectPublicKeyInfo的SHA-256
+  spki_pins: Arc<Vec<Vec<u8>>>,
+  // 需要校验公钥指纹的域名
+  pin_hosts: Arc<Vec<String>>,
+}
+
+/// nyquest异步客户端
+#[derive(Clone)]
+pub struct WeiboTlsClt {
+  reqwest_clt: reqwest::Client,
+  base_url: Option<String>,
+  max_response_buffer_size: Option<u64>,
+}
+
+/// nyquest异步响应，响应体在请求完成时一次性读入
+pub struct WeiboTlsReap {
+  status: u16,
+  headers: HeaderMap,
+  reap_byt: Vec<u8>,
+  reap_pos: usize,
+}
+
+/// 服务器证书校验器，未包含WebPKI校验器时即不校验证书的不安全模式
+#[derive(Debug)]
+struct WeiboTlsVerifier {
+  webpki_verifier: Option<Arc<WebPkiServerVerifier>>,
+  tls_provider: Arc<CryptoProvider>,
+  spki_pins: Arc<Vec<Vec<u8>>>,
+  pin_hosts: Arc<Vec<String>>,
+}
+
+/// 按配置载入根证书与公钥指纹，并注册为nyquest的后端，只能调用一次
+///
+/// ## 参数
+/// - `weibo_prefs`：运行配置
+pub fn tls_register(weibo_prefs: &WeiboPrefs) -> Result<(), WeiboError> {
+  let mut tls_roots = RootCertStore::empty();
+  if weibo_prefs.tls_ca_pth.is_empty() {
+    tls_roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+  } else {
+    let tls_ca_certs = CertificateDer::pem_file_iter(&weibo_prefs.tls_ca_pth).
+      and_then(|tls_ca_certs| tls_ca_certs.collect::<Result<Vec<_>, _>>()).
+      map_err(|flaw| {
+        weibo_prefs_err!(format!("tls_ca_pth '{}': {}", weibo_prefs.tls_ca_pth, flaw))
+      })?;
+    let (tls_ca_cnt, _) = tls_roots.add_parsable_certificates(tls_ca_certs);
+    if tls_ca_cnt == 0 {
+      return Err(weibo_prefs_err!(format!("tls_ca_pth '{}': no usable CA certificates",
+                                          weibo_prefs.tls_ca_pth)));
+    }
+  }
+
+  nyquest_interface::register_backend(WeiboTlsBackend {
+    tls_roots: Arc::new(tls_roots),
+    spki_pins: Arc::new(anly_spki_pins(&weibo_prefs.tls_spki_pins)?),
+    pin_hosts: Arc::new(anly_pin_hosts(&weibo_prefs.tls_pin_hosts)),
+  });
+  Ok(())
+}
+
+/// 解析以`,`分隔的需要校验公钥指纹的域名，统一为小写并去掉开头的`.`
+///
+/// ## 参数
+/// - `pin_hosts`：配置中的域名
+fn anly_pin_hosts(pin_hosts: &str) -> Vec<String> {
+  pin_hosts.split(',').
+    map(|pin_host| pin_host.trim().trim_start_matches('.').to_ascii_lowercase()).
+    filter(|pin_host| !pin_host.is_empty()).
+    collect()
+}
+
+/// 解析以`,`分隔的公钥指纹，每项为SHA-256的base64编码，可带curl风格的`sha256//`前缀
+///
+/// ## 参数
+/// - `spki_pins`：配置中的公钥指纹
+pub fn anly_spki_pins(spki_pins: &str) -> Result<Vec<Vec<u8>>, WeiboError> {
+  spki_pins.split(',').
+    map(|spki_pin| spki_pin.trim()).
+    filter(|spki_pin| !spki_pin.is_empty()).
+    map(|spki_pin| {
+      let spki_pin_byt = STANDARD.decode(spki_pin.trim_start_matches("sha256//")).ok().
+        filter(|spki_pin_byt| spki_pin_byt.len() == 32);
+      spki_pin_byt.ok_or_else(|| {
+        weibo_prefs_err!(format!("tls_spki_pins: '{}' is not a base64 SHA-256 digest",
+                                 spki_pin))
+      })
+    }).
+    collect()
+}
+
+impl AsyncBackend for WeiboTlsBackend {
+  type AsyncClient = WeiboTlsClt;
+
+  async fn create_async_client(&self, options: ClientOptions,
+  ) -> nyquest_interface::Result<Self::AsyncClient> {
+    let tls_provider = Arc::new(rustls::crypto::ring::default_provider());
+    let webpki_verifier = if options.ignore_certificate_errors {
+      None
+    } else {
+      Some(WebPkiServerVerifier::builder_with_provider(self.tls_roots.clone(),
+                                                       tls_provider.clone()).
+        build().
+        map_err(io::Error::other)?)
+    };
+    let tls_verifier = WeiboTlsVerifier {
+      webpki_verifier,
+      tls_provider: tls_provider.clone(),
+      spki_pins: self.spki_pins.clone(),
+      pin_hosts: self.pin_hosts.clone(),
+    };
+    let tls_cfg = ClientConfig::builder_with_provider(tls_provider).
+      with_safe_default_protocol_versions().
+      map_err(io::Error::other)?.
+      dangerous().
+      with_custom_certificate_verifier(Arc::new(tls_verifier)).
+      with_no_client_auth();
+
+    let mut clt_headers = HeaderMap::new();
+    for (header_name, header_val) in options.default_headers.iter() {
+      clt_headers.append(HeaderName::try_from(header_name.as_str()).map_err(io::Error::other)?,
+                         HeaderValue::try_from(header_val.as_str()).map_err(io::Error::other)?);
+    }
+    let mut clt_builder = reqwest::Client::builder().
+      use_preconfigured_tls(tls_cfg).
+      default_headers(clt_headers);
+    if let Some(user_agent) = options.user_agent.as_deref() {
+      clt_builder = clt_builder.user_agent(user_agent);
+    }
+    if let Some(request_timeout) = options.request_timeout {
+      clt_builder = clt_builder.timeout(request_timeout);
+    }
+    if !options.follow_redirects {
+      clt_builder = clt_builder.redirect(reqwest::redirect::Policy::none());
+    }
+    if !options.use_default_proxy {
+      clt_builder = clt_builder.no_proxy();
+    }
+    Ok(WeiboTlsClt {
+      reqwest_clt: clt_builder.build().map_err(io::Error::other)?,
+      base_url: options.base_url,
+      max_response_buffer_size: options.max_response_buffer_size,
+    })
+  }
+}
+
+impl AsyncClient for WeiboTlsClt {
+  type Response = WeiboTlsReap;
+
+  async fn request(&self, req: Request) -> nyquest_interface::Result<Self::Response> {
+    let req_method = match &req.method {
+      Method::Get => reqwest::Method::GET,
+      Method::Post => reqwest::Method::POST,
+      Method::Put => reqwest::Method::PUT,
+      Method::Delete => reqwest::Method::DELETE,
+      Method::Patch => reqwest::Method::PATCH,
+      Method::Head => reqwest::Method::HEAD,
+      Method::Other(req_method) => reqwest::Method::from_bytes(req_method.as_bytes()).
+        map_err(io::Error::other)?,
+    };
+    let req_url = tls_url_join(self.base_url.as_deref(), &req.relative_uri);
+    let mut req_builder = self.reqwest_clt.request(req_method, req_url);
+    for (header_name, header_val) in req.additional_headers.iter() {
+      req_builder = req_builder.header(header_name.as_ref(), header_val.as_ref());
+    }
+    req_builder = match req.body {
+      None => req_builder,
+      Some(Body::Bytes { content, content_type }) => {
+        req_builder.header("content-type", content_type.as_ref()).body(content.into_owned())
+      }
+      Some(Body::Form { fields }) => req_builder.form(&fields),
+      Some(Body::Stream { .. }) => {
+        return Err(io::Error::new(io::ErrorKind::Unsupported,
+                                  "streaming request bodies are not supported").into());
+      }
+    };
+
+    let mut reap = req_builder.send().await.map_err(anly_reqwest_err)?;
+    let status = reap.status().as_u16();
+    let headers = reap.headers().clone();
+    // 边读取边检查大小，超出max_response_buffer_size时立即放弃，不把超大的响应体读入内存
+    let reap_oversized = |reap_len: u64| {
+      self.max_response_buffer_size.is_some_and(|max_response_buffer_size| {
+        reap_len > max_response_buffer_size
+      })
+    };
+    if reap.content_length().is_some_and(reap_oversized) {
+      return Err(nyquest_interface::Error::ResponseTooLarge);
+    }
+    let mut reap_byt = Vec::new();
+    while let Some(reap_chunk) = reap.chunk().await.map_err(anly_reqwest_err)? {
+      if reap_oversized((reap_byt.len() + reap_chunk.len()) as u64) {
+        return Err(nyquest_interface::Error::ResponseTooLarge);
+      }
+      reap_byt.extend_from_slice(&reap_chunk);
+    }
+    Ok(WeiboTlsReap { status, headers, reap_byt, reap_pos: 0 })
+  }
+}
+
+impl AsyncResponse for WeiboTlsReap {
+  fn status(&self) -> u16 {
+    self.status
+  }
+
+  fn content_length(&self) -> Option<u64> {
+    Some(self.reap_byt.len() as u64)
+  }
+
+  fn get_header(&self, header: &str) -> nyquest_interface::Result<Vec<String>> {
+    Ok(self.headers.get_all(header).iter().
+      filter_map(|header_val| header_val.to_str().ok()).
+      map(|header_val| header_val.to_string()).
+      collect())
+  }
+
+  async fn text(self: Pin<&mut Self>) -> nyquest_interface::Result<String> {
+    let reap_byt = std::mem::take(&mut self.get_mut().reap_byt);
+    Ok(String::from_utf8_lossy(&reap_byt).into_owned())
+  }
+
+  async fn bytes(self: Pin<&mut Self>) -> nyquest_interface::Result<Vec<u8>> {
+    Ok(std::mem::take(&mut self.get_mut().reap_byt))
+  }
+}
+
+impl AsyncRead for WeiboTlsReap {
+  fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>,
+               buf: &mut [u8]) -> Poll<io::Result<usize>> {
+    let weibo_reap = self.get_mut();
+    let reap_rest = &weibo_reap.reap_byt[weibo_reap.reap_pos..];
+    let read_len = reap_rest.len().min(buf.len());
+    buf[..read_len].copy_from_slice(&reap_rest[..read_len]);
+    weibo_reap.reap_pos += read_len;
+    Poll::Ready(Ok(read_len))
+  }
+}
+
+impl ServerCertVerifier for WeiboTlsVerifier {
+  fn verify_server_cert(&self, end_entity: &CertificateDer<'_>,
+                        intermediates: &[CertificateDer<'_>], server_name: &ServerName<'_>,
+                        ocsp_response: &[u8],
+                        now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+    let Some(webpki_verifier) = &self.webpki_verifier else {
+      return Ok(ServerCertVerified::assertion());
+    };
+    webpki_verifier.verify_server_cert(end_entity, intermediates, server_name, ocsp_response,
+                                       now)?;
+
+    let ServerName::DnsName(server_host) = server_name else {
+      return Ok(ServerCertVerified::assertion());
+    };
+    let server_host = server_host.as_ref().to_ascii_lowercase();
+    let pin_matched = self.pin_hosts.iter().any(|pin_host| {
+      server_host == *pin_host || server_host.ends_with(&format!(".{}", pin_host))
+    });
+    if self.spki_pins.is_empty() || !pin_matched {
+      return Ok(ServerCertVerified::assertion());
+    }
+    // 证书链中任一证书的公钥指纹匹配即可，便于固定中间CA以跨越证书轮换
+    for tls_cert in std::iter::once(end_entity).chain(intermediates.iter()) {
+      let Ok(tls_cert) = webpki::EndEntityCert::try_from(tls_cert) else {
+        continue;
+      };
+      let spki_dgst = ring::digest::digest(&ring::digest::SHA256,
+                                           tls_cert.subject_public_key_info().as_ref());
+      if self.spki_pins.iter().any(|spki_pin| spki_pin.as_slice() == spki_dgst.as_ref()) {
+        return Ok(ServerCertVerified::assertion());
+      }
+    }
+    error!("{}: no certificate in the chain matches tls_spki_pins, refusing to connect",
+           server_host);
+    Err(rustls::Error::General(format!("{} public key pin mismatch", server_host)))
+  }
+
+  fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>,
+                            dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    rustls::crypto::verify_tls12_signature(message, cert, dss,
+                                           &self.tls_provider.signature_verification_algorithms)
+  }
+
+  fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>,
+                            dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    rustls::crypto::verify_tls13_signature(message, cert, dss,
+                                           &self.tls_provider.signature_verification_algorithms)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.tls_provider.signature_verification_algorithms.supported_schemes()
+  }
+}
+
+/// 拼接base_url与相对链接，规则与nyquest的curl后端一致：绝对链接原样使用，
+/// `/`开头的链接替换base_url的路径，其余链接替换base_url最后一个`/`之后的部分
+///
+/// ## 参数
+/// - `base_url`：客户端的base_url
+/// - `relative_uri`：请求的链接
+fn tls_url_join(base_url: Option<&str>, relative_uri: &str) -> String {
+  let relative_lower = relative_uri.get(..8).unwrap_or(relative_uri).to_ascii_lowercase();
+  let Some(base_url) = base_url.
+    filter(|_| !(relative_lower.starts_with("http://") || relative_lower.starts_with("https://")))
+  else {
+    return relative_uri.to_string();
+  };
+  let (base_proto, base_rest) = base_url.split_once("//").unwrap_or(("", base_url));
+  let base_host_pth = base_rest.split_once('?').map_or(base_rest, |(base_host_pth, _)| {
+    base_host_pth
+  });
+  if relative_uri.starts_with("//") {
+    format!("{}{}", base_proto, relative_uri)
+  } else if relative_uri.starts_with('/') {
+    let base_host = base_host_pth.split_once('/').map_or(base_host_pth, |(base_host, _)| {
+      base_host
+    });
+    format!("{}//{}{}", base_proto, base_host, relative_uri)
+  } else {
+    let base_dir = base_host_pth.rsplit_once('/').map_or(base_host_pth, |(base_dir, _)| {
+      base_dir
+    });
+    format!("{}//{}/{}", base_proto, base_dir, relative_uri)
+  }
+}
+
+/// 将reqwest的错误转换为nyquest的错误，超时之外的错误都视为IO错误并保留原因
+///
+/// ## 参数
+/// - `flaw`：reqwest的错误
+fn anly_reqwest_err(flaw: reqwest::Error) -> nyquest_interface::Error {
+  if flaw.is_timeout() {
+    return nyquest_interface::Error::RequestTimeout;
+  }
+  if flaw.is_builder() {
+    return nyquest_interface::Error::InvalidUrl;
+  }
+  let mut flaw_des = flaw.to_string();
+  let mut flaw_src = std::error::Error::source(&flaw);
+  while let Some(flaw_cause) = flaw_src {
+    flaw_des = format!("{}: {}", flaw_des, flaw_cause);
+    flaw_src = flaw_cause.source();
+  }
+  io::Error::other(flaw_des).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+  use std::io::Write;
+  use std::net::TcpListener;
+  use std::time::Duration;
+  use super::*;
+
+  /// 测试用的证书链：根CA -> 中间CA -> 叶证书（weibo.test、m.weibo.test、other.test），
+  /// 有效期自2026-10-18起一百年
+  const TLS_ROOT_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBmzCCAUGgAwIBAgIURP7mWLqJlAgsWuD4IzRzPRRqaGkwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPV2VpYm8gVGVzdCBSb290MCAXDTI2MTAxODExMzQ0N1oYDzIx
+MjYwOTI0MTEzNDQ3WjAaMRgwFgYDVQQDDA9XZWlibyBUZXN0IFJvb3QwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAARmjneuO3uVTMp8rD1aEfEj0amf9iV24s0ogOV/
+KVbcx7S7RHfiCdlN/JvOIXScZRpHJrDB+Z55ZSGyiOj+UJe+o2MwYTAdBgNVHQ4E
+FgQUqI+9t2TddG0AlXTNGhqP1ZGqzIMwHwYDVR0jBBgwFoAUqI+9t2TddG0AlXTN
+GhqP1ZGqzIMwDwYDVR0TAQH/BAUwAwEB/zAOBgNVHQ8BAf8EBAMCAQYwCgYIKoZI
+zj0EAwIDSAAwRQIhAOWGzwvvYfCg00UY2EOtGVtNdLkLPo6dhZxWyIHAxwcIAiBK
+4v6lrgM7Xdw4pf8uMCksFSKosHwUw6I3ERWhI8v4nw==
+-----END CERTIFICATE-----";
+  const TLS_INTER_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBpjCCAUygAwIBAgIUd9+Dch7ySB5DNSrmykyMI77i+VEwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPV2VpYm8gVGVzdCBSb290MCAXDTI2MTAxODExMzQ0N1oYDzIx
+MjYwOTI0MTEzNDQ3WjAiMSAwHgYDVQQDDBdXZWlibyBUZXN0IEludGVybWVkaWF0
+ZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABIQ9t8yeSMSLQS9yznAXzTDxNShI
+beYfZd7S5M8pnpNw9xsyn/xT04rf9sHhn8ITVUv4lJ04O27MxFWhEjN7GSyjZjBk
+MBIGA1UdEwEB/wQIMAYBAf8CAQAwDgYDVR0PAQH/BAQDAgEGMB0GA1UdDgQWBBQG
+g0kXCLWstCOcwikv3IBi3Lg7bTAfBgNVHSMEGDAWgBSoj723ZN10bQCVdM0aGo/V
+karMgzAKBggqhkjOPQQDAgNIADBFAiEA1GPfTnSf/ktYblYBJx0+zgE4Wj1hCaFL
+BP3eH8XqUigCIFarPu9JVo8J0NaW8bXQektw+aHr/pG523/CSvCCZnib
+-----END CERTIFICATE-----";
+  const TLS_LEAF_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIB5DCCAYmgAwIBAgIUGqOvVf31k4jbyRr6prPwE9VnQ/QwCgYIKoZIzj0EAwIw
+IjEgMB4GA1UEAwwXV2VpYm8gVGVzdCBJbnRlcm1lZGlhdGUwIBcNMjYxMDE4MTEz
+NDQ3WhgPMjEyNjA5MjQxMTM0NDdaMBUxEzARBgNVBAMMCndlaWJvLnRlc3QwWTAT
+BgcqhkjOPQIBBggqhkjOPQMBBwNCAAQpvX2AnOdJybkrdXwALP2JQr1zx4ap2XsH
+5MsT0omomC5DWalHe379kNBn0Oc1Yt4+M1v1BM76wM+roEozYd4Ao4GnMIGkMAwG
+A1UdEwEB/wQCMAAwDgYDVR0PAQH/BAQDAgeAMBMGA1UdJQQMMAoGCCsGAQUFBwMB
+MC8GA1UdEQQoMCaCCndlaWJvLnRlc3SCDG0ud2VpYm8udGVzdIIKb3RoZXIudGVz
+dDAfBgNVHSMEGDAWgBQGg0kXCLWstCOcwikv3IBi3Lg7bTAdBgNVHQ4EFgQUPsWj
+4oEYd+ObjJkrmN/0jlxj5/YwCgYIKoZIzj0EAwIDSQAwRgIhAMsjGKaiudAViEsm
+o/fAOmtzUb2HnMbMZ5TxA2a6KUEtAiEAvIGp7lcUS2Zkz4E9nt5+A8I2RMHXclu/
+nHIomhnSkgw=
+-----END CERTIFICATE-----";
+  /// 叶证书与中间CA的公钥指纹
+  const TLS_LEAF_PIN: &str = "rupZ7yYpMciG6QNXJuVvfHWuH6o84kfdKbc4aoKe6cw=";
+  const TLS_INTER_PIN: &str = "SoAf5jJ3c/VKCJ7nJGWLFR+fhiVvudu6aYqfMLSvq/o=";
+  /// 与证书链无关的公钥指纹
+  const TLS_OTHER_PIN: &str = "N+thNPew790iNMuvqAH6L1V8cgxU2UDh+3NVgTSLwB4=";
+
+  fn tls_verifier(spki_pins: &str, pin_hosts: &str) -> WeiboTlsVerifier {
+    let tls_provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls_roots = RootCertStore::empty();
+    tls_roots.add(CertificateDer::from_pem_slice(TLS_ROOT_PEM.as_bytes()).unwrap()).unwrap();
+    WeiboTlsVerifier {
+      webpki_verifier: Some(WebPkiServerVerifier::builder_with_provider(
+        Arc::new(tls_roots), tls_provider.clone()).build().unwrap()),
+      tls_provider,
+      spki_pins: Arc::new(anly_spki_pins(spki_pins).unwrap()),
+      pin_hosts: Arc::new(anly_pin_hosts(pin_hosts)),
+    }
+  }
+
+  fn tls_verify(tls_verifier: &WeiboTlsVerifier, server_host: &str) -> bool {
+    let tls_leaf = CertificateDer::from_pem_slice(TLS_LEAF_PEM.as_bytes()).unwrap();
+    let tls_inter = CertificateDer::from_pem_slice(TLS_INTER_PEM.as_bytes()).unwrap();
+    tls_verifier.verify_server_cert(
+      &tls_leaf, &[tls_inter], &ServerName::try_from(server_host.to_string()).unwrap(), &[],
+      UnixTime::since_unix_epoch(Duration::from_secs(1_830_000_000))).is_ok()
+  }
+
+  #[test]
+  fn anly_spki_pins_accepts_valid() {
+    assert!(anly_spki_pins("").unwrap().is_empty());
+    assert!(anly_spki_pins(" , ,").unwrap().is_empty());
+    let spki_pins = anly_spki_pins(
+      &format!(" sha256//{} ,{}", TLS_LEAF_PIN, TLS_INTER_PIN)).unwrap();
+    assert_eq!(spki_pins.len(), 2);
+    assert_eq!(spki_pins[0], STANDARD.decode(TLS_LEAF_PIN).unwrap());
+  }
+
+  #[test]
+  fn anly_spki_pins_rejects_malformed() {
+    for spki_pins in [
+      "not base64!", "sha256//", "AAAA", "c2hvcnQ=",
+      // SHA-1长度的指纹
+      "2jmj7l5rSw0yVb/vlWAYkK/YBwk=",
+      // 合法指纹之后混入非法指纹
+      &format!("{},sha256//@@@@", TLS_LEAF_PIN),
+    ] {
+      assert!(anly_spki_pins(spki_pins).is_err(), "{:?} should be rejected", spki_pins);
+    }
+  }
+
+  #[test]
+  fn anly_pin_hosts_normalizes() {
+    assert_eq!(anly_pin_hosts(" .Weibo.COM, weibo.cn ,,"), ["weibo.com", "weibo.cn"]);
+    assert!(anly_pin_hosts("").is_empty());
+  }
+
+  #[test]
+  fn tls_verifier_pins() {
+    // 未配置公钥指纹时只做WebPKI校验
+    assert!(tls_verify(&tls_verifier("", "weibo.test"), "weibo.test"));
+    // 固定叶证书或中间CA均可
+    assert!(tls_verify(&tls_verifier(TLS_LEAF_PIN, "weibo.test"), "weibo.test"));
+    assert!(tls_verify(&tls_verifier(TLS_INTER_PIN, "weibo.test"), "weibo.test"));
+    assert!(tls_verify(&tls_verifier(&format!("{},{}", TLS_OTHER_PIN, TLS_INTER_PIN),
+                                      "weibo.test"), "weibo.test"));
+    // 指纹不匹配时拒绝，子域名同样受约束
+    assert!(!tls_verify(&tls_verifier(TLS_OTHER_PIN, "weibo.test"), "weibo.test"));
+    assert!(!tls_verify(&tls_verifier(TLS_OTHER_PIN, ".weibo.test"), "m.weibo.test"));
+    // 不在tls_pin_hosts中的域名跳过公钥指纹校验
+    assert!(tls_verify(&tls_verifier(TLS_OTHER_PIN, "weibo.test"), "other.test"));
+    assert!(tls_verify(&tls_verifier(TLS_OTHER_PIN, "m.weibo.test"), "weibo.test"));
+    // 公钥指纹匹配也不能绕过WebPKI校验
+    assert!(!tls_verify(&tls_verifier(TLS_LEAF_PIN, "weibo.test"), "evil.test"));
+  }
+
+  #[test]
+  fn tls_url_join_rules() {
+    // (base_url, 请求的链接, 期望的链接)
+    let url_cases = [
+      (None, "/ajax/feed", "/ajax/feed"),
+      (None, "https://weibo.com/ajax/feed", "https://weibo.com/ajax/feed"),
+      (Some("https://weibo.com/ajax/"), "https://m.weibo.cn/api", "https://m.weibo.cn/api"),
+      (Some("https://weibo.com/ajax/"), "HTTP://m.weibo.cn/api", "HTTP://m.weibo.cn/api"),
+      (Some("https://weibo.com/ajax/feed"), "//wx1.sinaimg.cn/large/a.jpg",
+       "https://wx1.sinaimg.cn/large/a.jpg"),
+      (Some("https://weibo.com/ajax/feed?x=1"), "/ajax/side/hotSearch",
+       "https://weibo.com/ajax/side/hotSearch"),
+      (Some("https://weibo.com/ajax/feed"), "hottimeline", "https://weibo.com/ajax/hottimeline"),
+      (Some("https://weibo.com/ajax/"), "hottimeline", "https://weibo.com/ajax/hottimeline"),
+      (Some("https://weibo.com"), "ajax/hottimeline", "https://weibo.com/ajax/hottimeline"),
+      (Some("https://weibo.com/ajax/?next=/a/b"), "feed", "https://weibo.com/ajax/feed"),
+    ];
+    for (base_url, relative_uri, req_url) in url_cases {
+      assert_eq!(tls_url_join(base_url, relative_uri), req_url, "{:?} {}", base_url, relative_uri);
+    }
+  }
+
+  #[tokio::test]
+  async fn tls_clt_limits_streamed_reap() {
+    // 依次响应：无Content-Length的分块响应体、带Content-Length的响应体，各32字节
+    let tls_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tls_addr = tls_listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+      for tls_stream in tls_listener.incoming() {
+        let Ok(mut tls_stream) = tls_stream else {
+          continue;
+        };
+        let mut req_byt = [0u8; 1024];
+        let req_len = tls_stream.read(&mut req_byt).unwrap_or(0);
+        let reap_talk = if req_byt[..req_len].starts_with(b"GET /chunked") {
+          format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                   {}0\r\n\r\n", "8\r\n01234567\r\n".repeat(4))
+        } else {
+          format!("HTTP/1.1 200 OK\r\nContent-Length: 32\r\nConnection: close\r\n\r\n{}",
+                  "01234567".repeat(4))
+        };
+        tls_stream.write_all(reap_talk.as_bytes()).ok();
+      }
+    });
+
+    for max_response_buffer_size in [None, Some(32), Some(16)] {
+      let tls_backend = WeiboTlsBackend {
+        tls_roots: Arc::new(RootCertStore::empty()),
+        spki_pins: Arc::new(vec![]),
+        pin_hosts: Arc::new(vec![]),
+      };
+      let tls_clt = tls_backend.create_async_client(ClientOptions {
+        base_url: Some(format!("http://{}/", tls_addr)),
+        use_default_proxy: false,
+        ignore_certificate_errors: true,
+        max_response_buffer_size,
+        ..Default::default()
+      }).await.unwrap();
+      for relative_uri in ["/chunked", "/sized"] {
+        let reap_rst = tls_clt.request(Request {
+          method: Method::Get,
+          relative_uri: relative_uri.into(),
+          additional_headers: vec![],
+          body: None,
+        }).await;
+        match max_response_buffer_size {
+          Some(16) => assert!(matches!(reap_rst, Err(nyquest_interface::Error::ResponseTooLarge)),
+                              "{} should be too large", relative_uri),
+          _ => assert_eq!(reap_rst.unwrap().reap_byt, "01234567".repeat(4).as_bytes()),
+        }
+      }
+    }
+  }
+}
@@ -1,16 +1,34 @@
-use std::fs;
+use std::collections::HashSet;
+use std::sync::Arc;
 use hifitime::efmt::consts::ISO8601_DATE;
 use hifitime::prelude::Epoch;
 use hifitime::prelude::Formatter;
 use jzon::JsonValue;
+use jzon::object::Object;
+use log::warn;
 use nyquest::AsyncClient;
 use rbatis::RBatis;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use crate::dbs::*;
+use crate::ents::anly_text_ents;
 use crate::exceptions::WeiboError;
-use crate::prefs::WEIBO_HOT_TIMELINE_PICS_PTH;
+use crate::meta::anly_pic_meta;
+use crate::pics::WeiboPicFmt;
+use crate::pics::WeiboPicStor;
+use crate::pics::pics_locate;
+use crate::pics::pics_phash_blocking;
+use crate::pics::pics_stor;
+use crate::prefs::prefs;
+use crate::watch::WEIBO_WATCH_CACHE;
+use crate::watch::WeiboHitIlk;
+use crate::watch::WeiboWatch;
 use crate::weibo;
 use crate::weibo_jzon_err;
 
+/// 完整时间的存储格式，统一为UTC
+pub const ERA_FMT: &str = "%Y-%m-%d %H:%M:%S";
+
 /// 获取最新热搜并插入数据库
 ///
 /// ## 参数
@@ -19,7 +37,7 @@ use crate::weibo_jzon_err;
 pub async fn attain_ajax_hotsearch(
   weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis) -> Result<(), WeiboError> {
   // 热搜列表，应是JSON格式
-  let hotsearch_talk: String = weibo::gain_side_hotsearch(&weibo_clt).await?;
+  let hotsearch_talk: String = weibo::gain_side_hotsearch(weibo_clt).await?;
 
   let mut hot_search_arrs = vec![];
   let hot_search_jquin = jzon::parse(&hotsearch_talk)?;
@@ -33,8 +51,10 @@ pub async fn attain_ajax_hotsearch(
       || weibo_jzon_err!("/ajax/side/hotSearch data.realtime is not array"))?;
 
   // 当前时间
-  let nub_era = Epoch::now()?;
-  let nub_era = Formatter::new(nub_era, ISO8601_DATE);
+  let nub_epoch = Epoch::now()?;
+  let nub_era = Formatter::new(nub_epoch, ISO8601_DATE);
+  let snap_era = secs_era(nub_epoch.to_unix_seconds() as i64);
+  let mut hot_search_snap_arrs = vec![];
 
   for hot_search_realtime_arri in hot_search_realtime_arrs.iter() {
     // 判断是否是广告
@@ -53,43 +73,272 @@ pub async fn attain_ajax_hotsearch(
       .and_then(|val| val.as_str())
       .unwrap_or("");
 
+    // 热搜排名，优先使用realpos，没有时按广告以外的出现顺序计算
+    let realtime_rank = hot_search_realtime_arri.get("realpos")
+      .and_then(|val| val.as_u32())
+      .unwrap_or(hot_search_snap_arrs.len() as u32 + 1);
+
     hot_search_arrs.push(WeiboHotSearch::weibo_hot_search_c(
       realtime_title.to_string(),
       realtime_number,
       realtime_special.to_string(),
       nub_era.to_string())
     );
+    hot_search_snap_arrs.push(WeiboHotSearchSnap::weibo_hot_search_snap_c(
+      realtime_title.to_string(),
+      realtime_rank,
+      realtime_number,
+      realtime_special.to_string(),
+      nub_era.to_string(),
+      snap_era.clone())
+    );
   }
 
-  WeiboHotSearch::weibo_hot_search_u(weibo_db_rb_conn, hot_search_arrs).await
+  let keyword_hit_arrs = anly_keyword_hits(
+    WEIBO_WATCH_CACHE.watch().await.as_deref(), WeiboHitIlk::HotSearch,
+    hot_search_arrs.iter().map(|hot_search_arri| {
+      (hot_search_arri.title.as_str(), "", hot_search_arri.title.as_str())
+    }),
+    &snap_era);
+  WeiboHotSearch::weibo_hot_search_u(weibo_db_rb_conn, hot_search_arrs).await?;
+  WeiboHotSearchSnap::weibo_hot_search_snap_u(weibo_db_rb_conn, hot_search_snap_arrs).await?;
+  WeiboKeywordHit::weibo_keyword_hit_u(weibo_db_rb_conn, keyword_hit_arrs).await
 }
 
 /// 获取最新热门推荐并插入数据库
 ///
+/// 按max_id游标翻页。热门推荐会夹带已爬取过的旧微博，遇到第一个已存储的mid就停止会漏掉同一页中
+/// 的新微博，因此只有整页的mid都已爬取过时才提前停止，否则最多翻`page_cnt`页。
+///
 /// ## 参数
 /// - `weibo_clt`：nyquest异步HTTP客户端
 /// - `weibo_db_rb_conn`：rbatis数据库连接
 /// - `pic`：是否需要爬取图片
 /// - `comm`：是否需要爬取评论
+/// - `page_cnt`：最多爬取的页数，整页都是已爬取过的mid时提前停止
+/// - `page_size`：每页的热门推荐数量
+///
+/// ## 返回
+/// created_at无法解析的热门推荐与评论的数量
 pub async fn attain_ajax_hottimeline(weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
-                                     pic: bool, comm: bool) -> Result<(), WeiboError> {
-  // 热门推荐列表，应是JSON格式
-  let hottimeline_talk: String = weibo::gain_feed_hottimeline(&weibo_clt).await?;
-
+                                     pic: bool, comm: bool,
+                                     page_cnt: u32, page_size: u32) -> Result<usize, WeiboError> {
   let mut hot_timeline_arrs = vec![];
   let mut hot_timeline_pic_arrs = vec![];
   let mut hot_timeline_comm_arrs = vec![];
-  let hot_timeline_jquin = jzon::parse(&hottimeline_talk)?;
-  let hot_timeline_statuses = hot_timeline_jquin.get("statuses")
-    .ok_or_else(|| weibo_jzon_err!("/ajax/feed/hottimeline no field statuses"))?;
-  let hot_timeline_status_arrs: &Vec<JsonValue> = hot_timeline_statuses.as_array()
-    .ok_or_else(
-      || weibo_jzon_err!("/ajax/feed/hottimeline statuses is not array"))?;
+  // 本次爬取中已出现的mid，同一条热门推荐可能在相邻的页中重复出现
+  let mut timeline_mid_sets: HashSet<String> = HashSet::new();
+  // 下一页的游标，第一页为0
+  let mut timeline_max_id = "0".to_string();
+
+  for _ in 0..page_cnt.clamp(1, prefs().hot_timeline_page_max) {
+    // 热门推荐列表，应是JSON格式
+    let hottimeline_talk: String = weibo::gain_feed_hottimeline(
+      weibo_clt, weibo_db_rb_conn, &timeline_max_id, page_size).await?;
+
+    let hot_timeline_jquin = jzon::parse(&hottimeline_talk)?;
+    let hot_timeline_statuses = hot_timeline_jquin.get("statuses")
+      .ok_or_else(|| weibo_jzon_err!("/ajax/feed/hottimeline no field statuses"))?;
+    let hot_timeline_status_arrs: &Vec<JsonValue> = hot_timeline_statuses.as_array()
+      .ok_or_else(
+        || weibo_jzon_err!("/ajax/feed/hottimeline statuses is not array"))?;
+
+    // 本页中已存储在数据库中的mid。热门推荐会夹带旧的微博，只有整页都已爬取过时才说明后续的页
+    // 已经爬取过
+    let page_mid_arrs: Vec<String> = hot_timeline_status_arrs.iter().
+      filter_map(|val| val.get("mid").and_then(|val| val.as_str()).map(String::from)).
+      collect();
+    let page_mid_olds = WeiboHotTimeline::weibo_hot_timeline_mid_r(
+      weibo_db_rb_conn, &page_mid_arrs).await?;
+    let page_known = page_mid_known(&page_mid_arrs, &page_mid_olds, &timeline_mid_sets);
+
+    let crawl_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+    hot_timeline_arrs.extend(anly_hot_timeline_statuses(
+      hot_timeline_status_arrs, &mut timeline_mid_sets, pic, &mut hot_timeline_pic_arrs,
+      &crawl_era));
+
+    timeline_max_id = anly_max_id(&hot_timeline_jquin);
+    if page_known || hot_timeline_status_arrs.is_empty() ||
+      timeline_max_id.is_empty() || timeline_max_id == "0" {
+      break;
+    }
+  }
+
+  furnish_longtext_hot_timeline(weibo_clt, weibo_db_rb_conn, &mut hot_timeline_arrs).await?;
+
+  if comm {
+    for hot_timeline_arri in hot_timeline_arrs.iter() {
+      // 评论获取失败不影响本次爬取
+      match furnish_ajax_comments_hot_timeline(
+        weibo_clt, weibo_db_rb_conn, &hot_timeline_arri.mid, &hot_timeline_arri.mem_id,
+        prefs().hot_timeline_comm_page_cnt).await {
+        Ok(hot_timeline_comm) => hot_timeline_comm_arrs.extend(hot_timeline_comm),
+        Err(flaw) => warn!("comments of hot timeline {} cannot be crawled, {}",
+                           hot_timeline_arri.mid, flaw),
+      }
+    }
+  }
+  let era_flaw_cnt = hot_timeline_arrs.iter().
+    filter(|hot_timeline_arri| hot_timeline_arri.occur_utc.is_empty()).
+    count() + era_flaw_comm_cnt(&hot_timeline_comm_arrs);
+  if era_flaw_cnt > 0 {
+    warn!("created_at of {} hot timelines and comments cannot be parsed", era_flaw_cnt);
+  }
+  let timeline_mid_arrs: Vec<String> = hot_timeline_arrs.iter().
+    map(|hot_timeline_arri| hot_timeline_arri.mid.clone()).
+    collect();
+  // 每次爬取均记录互动数，用于查询互动数随时间的增长
+  let snap_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+  let hot_timeline_snap_arrs: Vec<WeiboHotTimelineSnap> = hot_timeline_arrs.iter().
+    map(|hot_timeline_arri| hot_timeline_arri.weibo_hot_timeline_snap(snap_era.clone())).
+    collect();
+  let hot_timeline_ent_arrs = anly_hot_timeline_ents(&hot_timeline_arrs);
+  let weibo_watch = WEIBO_WATCH_CACHE.watch().await;
+  let mut keyword_hit_arrs = anly_keyword_hits(
+    weibo_watch.as_deref(), WeiboHitIlk::Timeline,
+    hot_timeline_arrs.iter().map(|hot_timeline_arri| {
+      (hot_timeline_arri.mid.as_str(), hot_timeline_arri.mid.as_str(),
+       hot_timeline_arri.text.as_str())
+    }),
+    &snap_era);
+  keyword_hit_arrs.extend(anly_keyword_hits(
+    weibo_watch.as_deref(), WeiboHitIlk::Comm,
+    hot_timeline_comm_arrs.iter().map(|comm_arri| {
+      (comm_arri.comm_mid.as_str(), comm_arri.mid.as_str(), comm_arri.text.as_str())
+    }),
+    &snap_era));
+  WeiboHotTimeline::weibo_hot_timeline_u(weibo_db_rb_conn, hot_timeline_arrs).await?;
+  WeiboHotTimelineEnt::weibo_hot_timeline_ent_u(
+    weibo_db_rb_conn, &timeline_mid_arrs, hot_timeline_ent_arrs).await?;
+  WeiboHotTimelineSnap::weibo_hot_timeline_snap_u(weibo_db_rb_conn, hot_timeline_snap_arrs).await?;
+  if pic {
+    WeiboHotTimelinePic::weibo_hot_timeline_pic_u(weibo_db_rb_conn, hot_timeline_pic_arrs).await?;
+    // 图片下载失败不影响本次爬取，失败的图片记录在数据库中，稍后重试
+    let (_, pic_flaw_cnt) = attain_sinaimg_hot_timeline(
+      weibo_clt, weibo_db_rb_conn, Some(&timeline_mid_arrs)).await?;
+    if pic_flaw_cnt > 0 {
+      warn!("{} pictures of hot timeline cannot be downloaded", pic_flaw_cnt);
+    }
+  }
+  if comm {
+    let (comm_key_arrs, hot_timeline_comm_ent_arrs) =
+      anly_hot_timeline_comm_ents(&hot_timeline_comm_arrs);
+    WeiboHotTimelineComm::weibo_hot_timeline_comm_u(
+      weibo_db_rb_conn, hot_timeline_comm_arrs).await?;
+    WeiboHotTimelineCommEnt::weibo_hot_timeline_comm_ent_u(
+      weibo_db_rb_conn, &comm_key_arrs, hot_timeline_comm_ent_arrs).await?;
+  }
+  WeiboKeywordHit::weibo_keyword_hit_u(weibo_db_rb_conn, keyword_hit_arrs).await?;
+  Ok(era_flaw_cnt)
+}
+
+/// 判断一页热门推荐是否都已爬取过：每个mid都已存储在数据库中，或已在本次爬取的前几页中出现
+///
+/// ## 参数
+/// - `page_mid_arrs`：本页的mid
+/// - `page_mid_olds`：本页中已存储在数据库中的mid
+/// - `timeline_mid_sets`：本次爬取中此前的页已出现的mid
+fn page_mid_known(page_mid_arrs: &[String], page_mid_olds: &HashSet<String>,
+                  timeline_mid_sets: &HashSet<String>) -> bool {
+  !page_mid_arrs.is_empty() && page_mid_arrs.iter().all(|page_mid| {
+    page_mid_olds.contains(page_mid) || timeline_mid_sets.contains(page_mid)
+  })
+}
+
+/// 获取长微博的全文，替换热门推荐中截断的内容。已存储全文的长微博不再获取；获取失败时保留截断的
+/// 内容，之后再次爬取到时重试
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `hot_timeline_arrs`：本次爬取的热门推荐，展开的长微博的text与text_expanded会被修改
+async fn furnish_longtext_hot_timeline(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, hot_timeline_arrs: &mut [WeiboHotTimeline],
+) -> Result<(), WeiboError> {
+  furnish_longtext_olds_hot_timeline(weibo_db_rb_conn, hot_timeline_arrs).await?;
+
+  for hot_timeline_arri in hot_timeline_arrs.iter_mut() {
+    if !hot_timeline_arri.long_text || hot_timeline_arri.text_expanded {
+      continue;
+    }
+    let longtext_talk = weibo::gain_statuses_longtext(
+      weibo_clt, weibo_db_rb_conn, &hot_timeline_arri.mblogid).await;
+    match longtext_talk.and_then(|longtext_talk| anly_longtext(&longtext_talk)) {
+      Ok(timeline_text) => {
+        hot_timeline_arri.text = timeline_text;
+        hot_timeline_arri.text_expanded = true;
+      }
+      Err(flaw) => {
+        warn!("cannot expand long text of hot timeline {}: {}", hot_timeline_arri.mid, flaw);
+      }
+    }
+  }
+  Ok(())
+}
+
+/// 以已存储的全文替换长微博截断的内容，之后由内容提取的实体与关键词命中不会退回截断的内容
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `hot_timeline_arrs`：本次爬取的热门推荐，已存储全文的长微博的text与text_expanded会被修改
+async fn furnish_longtext_olds_hot_timeline(
+  weibo_db_rb_conn: &RBatis, hot_timeline_arrs: &mut [WeiboHotTimeline],
+) -> Result<(), WeiboError> {
+  let long_mid_arrs: Vec<String> = hot_timeline_arrs.iter().
+    filter(|hot_timeline_arri| hot_timeline_arri.long_text).
+    map(|hot_timeline_arri| hot_timeline_arri.mid.clone()).
+    collect();
+  let mut long_text_olds = WeiboHotTimeline::weibo_hot_timeline_expanded_r(
+    weibo_db_rb_conn, &long_mid_arrs).await?;
+
+  for hot_timeline_arri in hot_timeline_arrs.iter_mut() {
+    if let Some(timeline_text) = long_text_olds.remove(&hot_timeline_arri.mid) {
+      hot_timeline_arri.text = timeline_text;
+      hot_timeline_arri.text_expanded = true;
+    }
+  }
+  Ok(())
+}
+
+/// 从/ajax/statuses/longtext的返回中提取全文
+///
+/// ## 参数
+/// - `longtext_talk`：/ajax/statuses/longtext返回的JSON
+///
+/// ## 返回
+/// 全文，没有data.longTextContent或为空时返回错误
+fn anly_longtext(longtext_talk: &str) -> Result<String, WeiboError> {
+  let longtext_jquin = jzon::parse(longtext_talk)?;
+  longtext_jquin["data"]["longTextContent"].as_str().
+    filter(|longtext_content| !longtext_content.is_empty()).
+    map(String::from).
+    ok_or_else(|| weibo_jzon_err!("/ajax/statuses/longtext no field data.longTextContent"))
+}
+
+/// 从一页热门推荐中提取热门推荐信息，需要时同时提取图片信息
+///
+/// ## 参数
+/// - `hot_timeline_status_arrs`：/ajax/feed/hottimeline返回的statuses
+/// - `timeline_mid_sets`：本次爬取中已出现的mid，重复的热门推荐会被跳过
+/// - `pic`：是否需要爬取图片
+/// - `hot_timeline_pic_arrs`：提取到的图片信息追加到此
+/// - `crawl_era`：爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+///
+/// ## 返回
+/// 热门推荐信息WeiboHotTimeline列表
+fn anly_hot_timeline_statuses(
+  hot_timeline_status_arrs: &[JsonValue], timeline_mid_sets: &mut HashSet<String>, pic: bool,
+  hot_timeline_pic_arrs: &mut Vec<WeiboHotTimelinePic>, crawl_era: &str,
+) -> Vec<WeiboHotTimeline> {
+  let mut hot_timeline_arrs = vec![];
 
   for hot_timeline_status_arri in hot_timeline_status_arrs.iter() {
     let Some(timeline_mid) = hot_timeline_status_arri.get("mid").and_then(|val| val.as_str()) else {
       continue
     };
+    if !timeline_mid_sets.insert(timeline_mid.to_string()) {
+      continue;
+    }
     let Some(timeline_mblogid) = hot_timeline_status_arri.get("mblogid").and_then(|val| val.as_str()
     ) else {
       continue
@@ -103,8 +352,10 @@ pub async fn attain_ajax_hottimeline(weibo_clt: &AsyncClient, weibo_db_rb_conn:
       let timeline_pic_infos: Option<&JsonValue> = hot_timeline_status_arri.get("pic_infos");
       let timeline_mix_media_infos: Option<&JsonValue> =
         hot_timeline_status_arri.get("mix_media_info");
-      hot_timeline_pic_arrs.extend(furnish_sinaimg_hot_timeline(
-        &weibo_clt, timeline_mid, timeline_pic_infos, timeline_mix_media_infos).await?);
+      if let Some(timeline_pic_arrs) = anly_hot_timeline_4pic(
+        timeline_mid, timeline_pic_infos, timeline_mix_media_infos) {
+        hot_timeline_pic_arrs.extend(timeline_pic_arrs);
+      }
     }
 
     let timeline_text = hot_timeline_status_arri.get("text_raw").and_then(
@@ -113,19 +364,11 @@ pub async fn attain_ajax_hottimeline(weibo_clt: &AsyncClient, weibo_db_rb_conn:
     let timeline_mem_name = timeline_mem.get("screen_name").and_then(|val| val.as_str()
     ).unwrap_or("");
 
-    let timeline_era = hot_timeline_status_arri.get("created_at").and_then(|v| v.as_str()).
-      and_then(|era_talk| {
-        Epoch::from_format_str(&era_talk.replace("+0800 ", ""), "%a %b %d %H:%M:%S %Y").
-          map(|era_val| Formatter::new(era_val, ISO8601_DATE).to_string()).ok()
-      }).
-      or_else(|| {
-        Epoch::now().map(|era_val| Formatter::new(era_val, ISO8601_DATE).to_string()).ok()
-      });
-    let timeline_era = if let Some(timeline_era) = timeline_era {
-      timeline_era
-    } else {
-      continue;
-    };
+    let (timeline_era, timeline_utc, timeline_offset) = anly_occur(
+      hot_timeline_status_arri.get("created_at").and_then(|val| val.as_str()), crawl_era,
+      "hot timeline", timeline_mid);
+
+    let timeline_metr = anly_hot_timeline_metr(hot_timeline_status_arri, timeline_mem);
 
     hot_timeline_arrs.push(WeiboHotTimeline::weibo_hot_timeline_c(
       timeline_mid.to_string(),
@@ -134,27 +377,14 @@ pub async fn attain_ajax_hottimeline(weibo_clt: &AsyncClient, weibo_db_rb_conn:
       timeline_mem_id.to_string(),
       timeline_mem_name.to_string(),
       timeline_era,
+      timeline_utc,
+      timeline_offset,
+      crawl_era.to_string(),
+      timeline_metr,
     ));
   }
 
-  if comm {
-    for hot_timeline_arri in hot_timeline_arrs.iter() {
-      let hot_timeline_comm = furnish_ajax_comments_hot_timeline(
-        &weibo_clt, &hot_timeline_arri.mid, &hot_timeline_arri.mem_id).await.ok();
-      if let Some(hot_timeline_comm) = hot_timeline_comm {
-        hot_timeline_comm_arrs.extend(hot_timeline_comm);
-      }
-    }
-  }
-  WeiboHotTimeline::weibo_hot_timeline_u(weibo_db_rb_conn, hot_timeline_arrs).await?;
-  if pic {
-    WeiboHotTimelinePic::weibo_hot_timeline_pic_u(weibo_db_rb_conn, hot_timeline_pic_arrs).await?;
-  }
-  if comm {
-    WeiboHotTimelineComm::weibo_hot_timeline_comm_u(
-      weibo_db_rb_conn, hot_timeline_comm_arrs).await?;
-  }
-  Ok(())
+  hot_timeline_arrs
 }
 
 /// 获取最新热门推荐的评论并插入数据库
@@ -164,72 +394,517 @@ pub async fn attain_ajax_hottimeline(weibo_clt: &AsyncClient, weibo_db_rb_conn:
 /// - `weibo_db_rb_conn`：rbatis数据库连接
 /// - `timeline_mid`：热门推荐的mid
 /// - `timeline_uid`：热门推荐的用户id
+/// - `comm_page_cnt`：最多获取的评论页数
+///
+/// ## 返回
+/// created_at无法解析的评论的数量
 pub async fn attain_ajax_comments_hottimeline(
   weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
-  timeline_mid: &str, timeline_uid: &str) -> Result<(), WeiboError> {
+  timeline_mid: &str, timeline_uid: &str, comm_page_cnt: u32) -> Result<usize, WeiboError> {
   let hot_timeline_comm_arrs = furnish_ajax_comments_hot_timeline(
-    &weibo_clt, timeline_mid, timeline_uid).await?;
-  WeiboHotTimelineComm::weibo_hot_timeline_comm_u(weibo_db_rb_conn, hot_timeline_comm_arrs).await
+    weibo_clt, weibo_db_rb_conn, timeline_mid, timeline_uid, comm_page_cnt).await?;
+  let era_flaw_cnt = era_flaw_comm_cnt(&hot_timeline_comm_arrs);
+  if era_flaw_cnt > 0 {
+    warn!("created_at of {} comments of {} cannot be parsed", era_flaw_cnt, timeline_mid);
+  }
+  let (comm_key_arrs, hot_timeline_comm_ent_arrs) =
+    anly_hot_timeline_comm_ents(&hot_timeline_comm_arrs);
+  let keyword_hit_arrs = anly_keyword_hits(
+    WEIBO_WATCH_CACHE.watch().await.as_deref(), WeiboHitIlk::Comm,
+    hot_timeline_comm_arrs.iter().map(|comm_arri| {
+      (comm_arri.comm_mid.as_str(), comm_arri.mid.as_str(), comm_arri.text.as_str())
+    }),
+    &secs_era(Epoch::now()?.to_unix_seconds() as i64));
+  WeiboHotTimelineComm::weibo_hot_timeline_comm_u(weibo_db_rb_conn, hot_timeline_comm_arrs).
+    await?;
+  WeiboHotTimelineCommEnt::weibo_hot_timeline_comm_ent_u(
+    weibo_db_rb_conn, &comm_key_arrs, hot_timeline_comm_ent_arrs).await?;
+  WeiboKeywordHit::weibo_keyword_hit_u(weibo_db_rb_conn, keyword_hit_arrs).await?;
+  Ok(era_flaw_cnt)
+}
+
+/// 在本次爬取的文本中查找监控的关键词
+///
+/// ## 参数
+/// - `weibo_watch`：关键词监控匹配器，没有关键词时为None
+/// - `hit_ilk`：文本类型
+/// - `hit_texts`：每段文本的(命中的文本标识, 热门推荐的mid, 文本内容)
+/// - `hit_era`：命中的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+fn anly_keyword_hits<'a>(weibo_watch: Option<&WeiboWatch>, hit_ilk: WeiboHitIlk,
+                         hit_texts: impl Iterator<Item = (&'a str, &'a str, &'a str)>,
+                         hit_era: &str) -> Vec<WeiboKeywordHit> {
+  let Some(weibo_watch) = weibo_watch else {
+    return vec![];
+  };
+  hit_texts.flat_map(|(hit_key, timeline_mid, hit_text)| {
+    weibo_watch.watch_search(hit_text).into_iter().map(move |(keyword, hit_site)| {
+      WeiboKeywordHit::weibo_keyword_hit_c(keyword, hit_ilk, hit_key.to_string(),
+                                           timeline_mid.to_string(), hit_site,
+                                           hit_era.to_string())
+    })
+  }).
+    collect()
+}
+
+/// 统计created_at无法解析的评论的数量，即UTC时间为空的评论
+///
+/// ## 参数
+/// - `hot_timeline_comm_arrs`：本次爬取的评论
+fn era_flaw_comm_cnt(hot_timeline_comm_arrs: &[WeiboHotTimelineComm]) -> usize {
+  hot_timeline_comm_arrs.iter().filter(|comm_arri| comm_arri.comm_utc.is_empty()).count()
 }
 
-/// 获取热门推荐的图片信息
+/// 以有界的并发下载尚未下载成功的热门推荐图片，每张图片的下载结果都写入数据库。
+///
+/// 同时下载的图片不超过`hot_timeline_pic_workers`张，下载次数达到
+/// `hot_timeline_pic_attempt_max`的图片不再重试。
 ///
 /// ## 参数
 /// - `weibo_clt`：nyquest异步HTTP客户端
-/// - `timeline_mid`：热门推荐的mid
-/// - `timeline_pic_infos`：热门推荐中的pic_infos，如果文本中没有视频，则从此解析图片
-/// - `timeline_mix_media_infos`：热门推荐中的mix_media_infos，如果文本中有视屏，则从此解析图片
-async fn furnish_sinaimg_hot_timeline(weibo_clt: &AsyncClient,
-                                      timeline_mid: &str, timeline_pic_infos: Option<&JsonValue>,
-                                      timeline_mix_media_infos: Option<&JsonValue>,
-) -> Result<Vec<WeiboHotTimelinePic>, WeiboError> {
-  if let Some(hot_timeline_pic_arrs) = anly_hot_timeline_4pic(
-    timeline_mid, timeline_pic_infos, timeline_mix_media_infos) {
-    for hot_timeline_pic_arri in hot_timeline_pic_arrs.iter() {
-      // 存储到本地的图片文件路径
-      let pic_pth = format!("{}/{}-{}.jpg",
-                            WEIBO_HOT_TIMELINE_PICS_PTH,
-                            &hot_timeline_pic_arri.mid, &hot_timeline_pic_arri.pic_id);
-      // TODO: 修改为异步任务
-      let timeline_pic_ctn = weibo::gain_sinaimg(&weibo_clt, &hot_timeline_pic_arri.pic_url).await?;
-      // 将图片存储到本地，忽略存储结果情况，不要影响整个循环
-      fs::write(&pic_pth, timeline_pic_ctn).ok();
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `timeline_mid_arrs`：只下载这些热门推荐的图片，为空时下载全部待重试的图片
+///
+/// ## 返回
+/// 下载成功与失败的图片数量
+pub async fn attain_sinaimg_hot_timeline(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, timeline_mid_arrs: Option<&Vec<String>>,
+) -> Result<(usize, usize), WeiboError> {
+  let hot_timeline_pic_arrs = WeiboHotTimelinePic::weibo_hot_timeline_pic_dl_r(
+    weibo_db_rb_conn, timeline_mid_arrs, prefs().hot_timeline_pic_attempt_max).await?;
+
+  let pic_dl_sema = Arc::new(Semaphore::new(prefs().hot_timeline_pic_workers));
+  let mut pic_dl_sets = JoinSet::new();
+  for hot_timeline_pic_arri in hot_timeline_pic_arrs {
+    let Some(pic_id) = hot_timeline_pic_arri.id else {
+      continue;
+    };
+    let weibo_clt = weibo_clt.clone();
+    let weibo_db_rb_conn = weibo_db_rb_conn.clone();
+    let pic_dl_sema = pic_dl_sema.clone();
+    pic_dl_sets.spawn(async move {
+      let _pic_dl_permit = pic_dl_sema.acquire_owned().await;
+      let dl_rst = furnish_sinaimg_hot_timeline(&weibo_clt, &hot_timeline_pic_arri).await;
+      if let Err(flaw) = &dl_rst {
+        warn!("picture {} of {} cannot be downloaded, {}",
+              hot_timeline_pic_arri.pic_id, hot_timeline_pic_arri.mid, flaw);
+      }
+      let dl_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+      WeiboHotTimelinePic::weibo_hot_timeline_pic_dl_u(
+        &weibo_db_rb_conn, pic_id, &dl_rst, &dl_era).await?;
+      if let Ok(pic_stor) = &dl_rst {
+        WeiboHotTimelinePicMeta::weibo_hot_timeline_pic_meta_u(
+          &weibo_db_rb_conn, &pic_stor.hash, &pic_stor.meta, &dl_era).await?;
+      }
+      Ok::<bool, WeiboError>(dl_rst.is_ok())
+    });
+  }
+
+  let mut pic_suc_cnt = 0;
+  let mut pic_flaw_cnt = 0;
+  while let Some(pic_dl_rst) = pic_dl_sets.join_next().await {
+    match pic_dl_rst {
+      Ok(Ok(true)) => pic_suc_cnt += 1,
+      Ok(Ok(false)) => pic_flaw_cnt += 1,
+      Ok(Err(flaw)) => {
+        warn!("picture download cannot be recorded, {}", flaw);
+        pic_flaw_cnt += 1;
+      }
+      Err(flaw) => {
+        warn!("picture download task failed, {}", flaw);
+        pic_flaw_cnt += 1;
+      }
     }
-    return Ok(hot_timeline_pic_arrs);
   }
-  Ok(vec![])
+  Ok((pic_suc_cnt, pic_flaw_cnt))
 }
 
-/// 获取最新热门推荐的评论
+/// 为尚未计算感知哈希的已下载图片补算感知哈希，例如升级前下载的图片
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+///
+/// ## 返回
+/// 补算成功的图片数量，内容相同的图片只计一次
+pub async fn attain_pic_phash_hot_timeline(weibo_db_rb_conn: &RBatis) -> Result<usize, WeiboError> {
+  let hot_timeline_pic_arrs = WeiboHotTimelinePic::weibo_hot_timeline_pic_phash_r(
+    weibo_db_rb_conn, false).await?;
+
+  let mut pic_hash_keys: HashSet<String> = HashSet::new();
+  let mut pic_phash_cnt = 0;
+  for hot_timeline_pic_arri in hot_timeline_pic_arrs {
+    if !pic_hash_keys.insert(hot_timeline_pic_arri.pic_hash.clone()) {
+      continue;
+    }
+    let Some(pic_fmt) = WeiboPicFmt::from_ext(&hot_timeline_pic_arri.pic_fmt) else {
+      continue;
+    };
+    let Some(pic_pth) = pics_locate(&hot_timeline_pic_arri.pic_hash, pic_fmt).await else {
+      warn!("picture {} is missing from the picture store", hot_timeline_pic_arri.pic_hash);
+      continue;
+    };
+    let pic_phash = match tokio::fs::read(&pic_pth).await {
+      Ok(pic_byt) => pics_phash_blocking(pic_byt).await,
+      Err(_) => None,
+    };
+    let Some(pic_phash) = pic_phash else {
+      warn!("perceptual hash of picture {} cannot be computed", hot_timeline_pic_arri.pic_hash);
+      continue;
+    };
+    WeiboHotTimelinePic::weibo_hot_timeline_pic_phash_u(
+      weibo_db_rb_conn, &hot_timeline_pic_arri.pic_hash, &pic_phash).await?;
+    pic_phash_cnt += 1;
+  }
+  Ok(pic_phash_cnt)
+}
+
+/// 为尚未解析元数据的已下载图片补充解析EXIF/XMP元数据，例如升级前下载的图片
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+///
+/// ## 返回
+/// 解析成功的图片数量，内容相同的图片只计一次
+pub async fn attain_pic_meta_hot_timeline(weibo_db_rb_conn: &RBatis) -> Result<usize, WeiboError> {
+  let hot_timeline_pic_arrs = WeiboHotTimelinePicMeta::weibo_hot_timeline_pic_meta_pending_r(
+    weibo_db_rb_conn).await?;
+
+  let mut pic_meta_cnt = 0;
+  for hot_timeline_pic_arri in hot_timeline_pic_arrs {
+    let Some(pic_fmt) = WeiboPicFmt::from_ext(&hot_timeline_pic_arri.pic_fmt) else {
+      continue;
+    };
+    let pic_byt = match pics_locate(&hot_timeline_pic_arri.pic_hash, pic_fmt).await {
+      Some(pic_pth) => tokio::fs::read(&pic_pth).await.ok(),
+      None => None,
+    };
+    let Some(pic_byt) = pic_byt else {
+      warn!("picture {} is missing from the picture store", hot_timeline_pic_arri.pic_hash);
+      continue;
+    };
+    let pic_meta = anly_pic_meta(pic_fmt, &pic_byt);
+    let meta_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+    WeiboHotTimelinePicMeta::weibo_hot_timeline_pic_meta_u(
+      weibo_db_rb_conn, &hot_timeline_pic_arri.pic_hash, &pic_meta, &meta_era).await?;
+    pic_meta_cnt += 1;
+  }
+  Ok(pic_meta_cnt)
+}
+
+/// 下载一张热门推荐图片并按内容存入图片库
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `hot_timeline_pic`：热门推荐图片
+///
+/// ## 返回
+/// 成功则返回存入图片库的图片
+async fn furnish_sinaimg_hot_timeline(weibo_clt: &AsyncClient,
+                                      hot_timeline_pic: &WeiboHotTimelinePic,
+) -> Result<WeiboPicStor, WeiboError> {
+  let timeline_pic_ctn = weibo::gain_sinaimg(weibo_clt, &hot_timeline_pic.pic_url).await?;
+  pics_stor(&timeline_pic_ctn).await
+}
+
+/// 获取最新热门推荐的评论，按max_id逐页获取，并二次获取被截断的楼中楼回复
 ///
 /// ## 参数
 /// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
 /// - `timeline_mid`：热门推荐的mid
 /// - `timeline_uid`：热门推荐的用户id
+/// - `comm_page_cnt`：最多获取的评论页数，评论总数不超过`hot_timeline_comm_max`
 async fn furnish_ajax_comments_hot_timeline(
-  weibo_clt: &AsyncClient, timeline_mid: &str, timeline_uid: &str,
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
+  timeline_mid: &str, timeline_uid: &str, comm_page_cnt: u32,
 ) -> Result<Vec<WeiboHotTimelineComm>, WeiboError> {
-  // 热门推荐评论列表，应是JSON格式
-  let hottimeline_comm_talk: String = weibo::gain_status_build_comments(
-    &weibo_clt, timeline_mid, timeline_uid).await?;
+  let (mut hot_timeline_comms, comm_senior_arrs) = furnish_comment_pages(
+    timeline_mid, comm_page_cnt.clamp(1, prefs().hot_timeline_comm_page_max),
+    |comm_max_id| async move {
+      weibo::gain_status_build_comments(
+        weibo_clt, weibo_db_rb_conn, timeline_mid, timeline_uid, &comm_max_id).await
+    }).await?;
+  // 已获取的评论mid，楼中楼的预览回复在二次获取时会重复出现
+  let mut comm_mid_sets: HashSet<String> = hot_timeline_comms.iter().
+    map(|comm_arri| comm_arri.comm_mid.clone()).
+    collect();
 
-  let mut hot_timeline_comms = vec![];
-  let hot_timeline_comm_jquin = jzon::parse(&hottimeline_comm_talk)?;
+  // 二次获取被截断的楼中楼回复，失败时保留已获取的评论
+  for comm_senior_id in comm_senior_arrs.iter() {
+    if hot_timeline_comms.len() >= prefs().hot_timeline_comm_max {
+      break;
+    }
+    match furnish_ajax_comment_replies_hot_timeline(
+      weibo_clt, weibo_db_rb_conn, timeline_mid, timeline_uid, comm_senior_id).await {
+      Ok(hot_timeline_comm) => {
+        hot_timeline_comms.extend(hot_timeline_comm.into_iter().filter(
+          |comm_arri| comm_mid_sets.insert(comm_arri.comm_mid.clone())));
+      }
+      Err(flaw) => warn!("replies of comment {} cannot be crawled, {}", comm_senior_id, flaw),
+    }
+  }
+
+  hot_timeline_comms.truncate(prefs().hot_timeline_comm_max);
+  Ok(hot_timeline_comms)
+}
+
+/// 分页获取热门推荐的根评论与内嵌的回复，第一页之后的页失败时保留已获取的评论
+///
+/// ## 参数
+/// - `timeline_mid`：热门推荐的mid
+/// - `comm_page_cnt`：最多获取的页数
+/// - `comm_gain_fn`：按游标获取一页评论，第一页的游标为0
+///
+/// ## 返回
+/// 成功则返回去重后的评论，与回复被截断的根评论的id
+async fn furnish_comment_pages<F, Fut>(
+  timeline_mid: &str, comm_page_cnt: u32, mut comm_gain_fn: F,
+) -> Result<(Vec<WeiboHotTimelineComm>, Vec<String>), WeiboError>
+where
+  F: FnMut(String) -> Fut,
+  Fut: Future<Output = Result<String, WeiboError>>,
+{
+  let mut hot_timeline_comms: Vec<WeiboHotTimelineComm> = vec![];
+  let mut comm_mid_sets: HashSet<String> = HashSet::new();
+  // 回复被截断的根评论的id
+  let mut comm_senior_arrs: Vec<String> = vec![];
+  // 下一页的游标，第一页为0
+  let mut comm_max_id = "0".to_string();
+
+  for comm_page in 1..=comm_page_cnt {
+    // 热门推荐评论列表，应是JSON格式
+    let comm_page_rst = match comm_gain_fn(comm_max_id.clone()).await {
+      Ok(hottimeline_comm_talk) => anly_comments_page(timeline_mid, &hottimeline_comm_talk),
+      Err(flaw) => Err(flaw),
+    };
+    let (comm_page_arrs, comm_page_seniors, comm_page_max_id) = match comm_page_rst {
+      Ok(comm_page_val) => comm_page_val,
+      // 第一页失败时交给调用方处理，之后的页失败时保留已获取的评论
+      Err(flaw) if comm_page == 1 => return Err(flaw),
+      Err(flaw) => {
+        warn!("comments of hot timeline {} page {} cannot be crawled, {} comments kept, {}",
+              timeline_mid, comm_page, hot_timeline_comms.len(), flaw);
+        break;
+      }
+    };
+    let comm_page_empty = comm_page_arrs.is_empty();
+    hot_timeline_comms.extend(comm_page_arrs.into_iter().filter(
+      |comm_arri| comm_mid_sets.insert(comm_arri.comm_mid.clone())));
+    comm_senior_arrs.extend(comm_page_seniors);
+
+    comm_max_id = comm_page_max_id;
+    if hot_timeline_comms.len() >= prefs().hot_timeline_comm_max ||
+      comm_page_empty || comm_max_id.is_empty() || comm_max_id == "0" {
+      break;
+    }
+  }
+
+  Ok((hot_timeline_comms, comm_senior_arrs))
+}
+
+/// 解析一页根评论
+///
+/// ## 参数
+/// - `timeline_mid`：热门推荐的mid
+/// - `hottimeline_comm_talk`：评论接口返回的JSON
+///
+/// ## 返回
+/// 成功则返回本页的评论、回复被截断的根评论的id与下一页的游标
+fn anly_comments_page(
+  timeline_mid: &str, hottimeline_comm_talk: &str,
+) -> Result<(Vec<WeiboHotTimelineComm>, Vec<String>, String), WeiboError> {
+  let crawl_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+  let hot_timeline_comm_jquin = jzon::parse(hottimeline_comm_talk)?;
   let comm_datas = hot_timeline_comm_jquin.get("data")
-    .ok_or_else(|| weibo_jzon_err!("/ajax/feed/hottimeline no field data"))?;
+    .ok_or_else(|| weibo_jzon_err!("/ajax/statuses/buildComments no field data"))?;
   let comm_data_arrs: &Vec<JsonValue> = comm_datas.as_array()
-    .ok_or_else(|| weibo_jzon_err!("/ajax/feed/hottimeline data is not array"))?;
+    .ok_or_else(|| weibo_jzon_err!("/ajax/statuses/buildComments data is not array"))?;
 
+  let mut hot_timeline_comms = vec![];
+  let mut comm_senior_arrs = vec![];
   for comm_data_arri in comm_data_arrs {
-    let hot_timeline_comm = anly_hot_timeline_comm(timeline_mid, comm_data_arri);
-    if let Some(hot_timeline_comm) = hot_timeline_comm {
+    // 根评论的回复总数多于内嵌的回复数时，说明内嵌的回复被截断
+    let comm_reply_cnt = comm_data_arri.get("total_number").
+      and_then(|val| val.as_usize()).unwrap_or(0);
+    let comm_reply_inner_cnt = comm_data_arri.get("comments").
+      and_then(|val| val.as_array()).map_or(0, |val| val.len());
+    if comm_reply_cnt > comm_reply_inner_cnt
+      && let Some(comm_senior_id) = comm_data_arri.get("idstr").and_then(|val| val.as_str()) {
+      comm_senior_arrs.push(comm_senior_id.to_string());
+    }
+
+    if let Some(hot_timeline_comm) = anly_hot_timeline_comm(
+      timeline_mid, comm_data_arri, &crawl_era) {
       hot_timeline_comms.extend(hot_timeline_comm);
     }
   }
+  Ok((hot_timeline_comms, comm_senior_arrs, anly_max_id(&hot_timeline_comm_jquin)))
+}
+
+/// 获取热门推荐中某条根评论的全部楼中楼回复，第一页之后的页失败时保留已获取的回复
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `timeline_mid`：热门推荐的mid
+/// - `timeline_uid`：热门推荐的用户id
+/// - `comm_senior_id`：根评论的id
+async fn furnish_ajax_comment_replies_hot_timeline(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis,
+  timeline_mid: &str, timeline_uid: &str, comm_senior_id: &str,
+) -> Result<Vec<WeiboHotTimelineComm>, WeiboError> {
+  let mut hot_timeline_comms = vec![];
+  // 下一页的游标，第一页为0
+  let mut comm_max_id = "0".to_string();
+
+  for comm_page in 1..=prefs().hot_timeline_comm_reply_page_max {
+    // 楼中楼回复列表，应是JSON格式
+    let comm_page_rst = match weibo::gain_status_build_comment_replies(
+      weibo_clt, weibo_db_rb_conn, comm_senior_id, timeline_uid, &comm_max_id).await {
+      Ok(hottimeline_comm_talk) => anly_comment_replies_page(timeline_mid, &hottimeline_comm_talk),
+      Err(flaw) => Err(flaw),
+    };
+    let (comm_page_arrs, comm_page_max_id) = match comm_page_rst {
+      Ok(comm_page_val) => comm_page_val,
+      // 第一页失败时交给调用方处理，之后的页失败时保留已获取的回复
+      Err(flaw) if comm_page == 1 => return Err(flaw),
+      Err(flaw) => {
+        warn!("replies of comment {} page {} cannot be crawled, {} replies kept, {}",
+              comm_senior_id, comm_page, hot_timeline_comms.len(), flaw);
+        break;
+      }
+    };
+    let comm_page_empty = comm_page_arrs.is_empty();
+    hot_timeline_comms.extend(comm_page_arrs);
+
+    comm_max_id = comm_page_max_id;
+    if comm_page_empty || comm_max_id.is_empty() || comm_max_id == "0" {
+      break;
+    }
+  }
 
   Ok(hot_timeline_comms)
 }
 
+/// 解析一页楼中楼回复
+///
+/// ## 参数
+/// - `timeline_mid`：热门推荐的mid
+/// - `hottimeline_comm_talk`：楼中楼回复接口返回的JSON
+///
+/// ## 返回
+/// 成功则返回本页的回复与下一页的游标
+fn anly_comment_replies_page(
+  timeline_mid: &str, hottimeline_comm_talk: &str,
+) -> Result<(Vec<WeiboHotTimelineComm>, String), WeiboError> {
+  let crawl_era = secs_era(Epoch::now()?.to_unix_seconds() as i64);
+  let hot_timeline_comm_jquin = jzon::parse(hottimeline_comm_talk)?;
+  let comm_datas = hot_timeline_comm_jquin.get("data")
+    .ok_or_else(|| weibo_jzon_err!("/ajax/statuses/buildComments no field data"))?;
+  let comm_data_arrs: &Vec<JsonValue> = comm_datas.as_array()
+    .ok_or_else(|| weibo_jzon_err!("/ajax/statuses/buildComments data is not array"))?;
+
+  let mut hot_timeline_comms = vec![];
+  for comm_data_arri in comm_data_arrs {
+    if let Some(hot_timeline_comm) = anly_hot_timeline_comm(
+      timeline_mid, comm_data_arri, &crawl_era) {
+      hot_timeline_comms.extend(hot_timeline_comm);
+    }
+  }
+  Ok((hot_timeline_comms, anly_max_id(&hot_timeline_comm_jquin)))
+}
+
+/// 从分页接口的返回中提取下一页的游标max_id
+///
+/// ## 参数
+/// - `page_jquin`：分页接口返回的JSON
+///
+/// ## 返回
+/// 下一页的游标，可能是数字或字符串，没有时返回空字符串
+fn anly_max_id(page_jquin: &JsonValue) -> String {
+  match page_jquin.get("max_id") {
+    Some(max_id) => max_id.as_u64().map(|val| val.to_string()).
+      or_else(|| max_id.as_str().map(String::from)).
+      unwrap_or_default(),
+    None => String::new(),
+  }
+}
+
+/// 从热门推荐信息中提取互动数、来源、IP属地与发布者的认证和粉丝数，缺失的字段取默认值
+///
+/// ## 参数
+/// - `hot_timeline_status_arri`：/ajax/feed/hottimeline返回的一条status
+/// - `timeline_mem`：status中的user
+fn anly_hot_timeline_metr(hot_timeline_status_arri: &JsonValue, timeline_mem: &Object)
+  -> WeiboHotTimelineMetr {
+  let timeline_source = hot_timeline_status_arri.get("source").and_then(|val| val.as_str()).
+    unwrap_or("");
+  let timeline_region_name = hot_timeline_status_arri.get("region_name").
+    and_then(|val| val.as_str()).unwrap_or("");
+  WeiboHotTimelineMetr {
+    repost_cnt: anly_cnt(hot_timeline_status_arri.get("reposts_count")),
+    comm_cnt: anly_cnt(hot_timeline_status_arri.get("comments_count")),
+    like_cnt: anly_cnt(hot_timeline_status_arri.get("attitudes_count")),
+    source: anly_html_text(timeline_source),
+    region_name: timeline_region_name.trim_start_matches("发布于").trim().to_string(),
+    long_text: hot_timeline_status_arri.get("isLongText").and_then(|val| val.as_bool()).
+      unwrap_or(false),
+    mem_verified: timeline_mem.get("verified").and_then(|val| val.as_bool()).unwrap_or(false),
+    mem_verified_ilk: timeline_mem.get("verified_type").and_then(|val| val.as_i32()).
+      unwrap_or(-1),
+    // followers_count可能是“1.2万”这样的字符串，缺失时使用followers_count_str
+    mem_follower_cnt: anly_cnt(timeline_mem.get("followers_count").
+      or_else(|| timeline_mem.get("followers_count_str"))),
+  }
+}
+
+/// 解析微博的计数，可能是数字，也可能是“1.2万”、“3亿”、“100万+”这样的字符串
+///
+/// ## 参数
+/// - `cnt_val`：计数字段
+///
+/// ## 返回
+/// 计数，无法解析时返回0，超出u32时取u32::MAX
+fn anly_cnt(cnt_val: Option<&JsonValue>) -> u32 {
+  let Some(cnt_val) = cnt_val else {
+    return 0;
+  };
+  if let Some(cnt) = cnt_val.as_u64() {
+    return cnt.min(u32::MAX as u64) as u32;
+  }
+  let Some(cnt_talk) = cnt_val.as_str() else {
+    return 0;
+  };
+  let cnt_talk = cnt_talk.trim().trim_end_matches('+').replace(',', "");
+  let (cnt_num, cnt_unit) = if let Some(cnt_num) = cnt_talk.strip_suffix('万') {
+    (cnt_num, 1e4)
+  } else if let Some(cnt_num) = cnt_talk.strip_suffix('亿') {
+    (cnt_num, 1e8)
+  } else {
+    (cnt_talk.as_str(), 1.0)
+  };
+  cnt_num.trim().parse::<f64>().
+    map(|cnt| (cnt * cnt_unit).round().clamp(0.0, u32::MAX as f64) as u32).
+    unwrap_or(0)
+}
+
+/// 去掉HTML标签，只保留文本，比如source可能是<a href="...">iPhone客户端</a>
+///
+/// ## 参数
+/// - `html_talk`：可能包含HTML标签的文本
+fn anly_html_text(html_talk: &str) -> String {
+  let mut html_text = String::with_capacity(html_talk.len());
+  let mut html_tag = false;
+  for html_char in html_talk.chars() {
+    match html_char {
+      '<' => html_tag = true,
+      '>' if html_tag => html_tag = false,
+      _ if !html_tag => html_text.push(html_char),
+      _ => {}
+    }
+  }
+  html_text.trim().to_string()
+}
+
 /// 从热门推荐信息中提取图片信息
 ///
 /// ## 参数
@@ -272,18 +947,17 @@ fn anly_hot_timeline_4pic(timeline_mid: &str, timeline_pic_infos: Option<&JsonVa
     if let Some(mix_media_info_itms) = timeline_mix_media_infos.get("items").
       and_then(|val| val.as_array()) {
       for mix_media_info_itmi in mix_media_info_itms {
-        if let Some("pic") = mix_media_info_itmi.get("type").and_then(|val| val.as_str()) {
-          if let Some(mix_media_info_pic) = mix_media_info_itmi.get("data").
+        if let Some("pic") = mix_media_info_itmi.get("type").and_then(|val| val.as_str())
+          && let Some(mix_media_info_pic) = mix_media_info_itmi.get("data").
             and_then(|val| val.as_object()) {
-            let Some(pic_info_pic_id) = mix_media_info_pic.get("pic_id").and_then(|v| v.as_str())
-            else {
-              continue;
-            };
-            if let Some(pic_url) = anly_pic_info_large(mix_media_info_pic.get("large")) {
-              hot_timeline_pic_arrs.push(WeiboHotTimelinePic::weibo_hot_timeline_pic_c(
-                timeline_mid.to_string(), pic_info_pic_id.to_string(), pic_url,
-              ));
-            }
+          let Some(pic_info_pic_id) = mix_media_info_pic.get("pic_id").and_then(|v| v.as_str())
+          else {
+            continue;
+          };
+          if let Some(pic_url) = anly_pic_info_large(mix_media_info_pic.get("large")) {
+            hot_timeline_pic_arrs.push(WeiboHotTimelinePic::weibo_hot_timeline_pic_c(
+              timeline_mid.to_string(), pic_info_pic_id.to_string(), pic_url,
+            ));
           }
         }
       }
@@ -298,66 +972,421 @@ fn anly_hot_timeline_4pic(timeline_mid: &str, timeline_pic_infos: Option<&JsonVa
 /// ## 参数
 /// - `timeline_mid`：热门推荐的mid
 /// - `timeline_comm_info`：热门推荐评论的信息
+/// - `crawl_era`：爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
 ///
 /// ## 返回
 /// 热门推荐评论信息WeiboHotTimelineComm列表
-fn anly_hot_timeline_comm(
-  timeline_mid: &str, timeline_comm_info: &JsonValue) -> Option<Vec<WeiboHotTimelineComm>> {
+fn anly_hot_timeline_comm(timeline_mid: &str, timeline_comm_info: &JsonValue,
+                          crawl_era: &str) -> Option<Vec<WeiboHotTimelineComm>> {
   let mut comm_arrs: Vec<WeiboHotTimelineComm> = Vec::new();
-  let Some(timeline_comm_info) = timeline_comm_info.as_object() else {
-    return None;
-  };
+  let timeline_comm_info = timeline_comm_info.as_object()?;
 
   // 评论的mid
-  let Some(comm_mid) = timeline_comm_info.get("mid").and_then(|val| val.as_str()) else {
-    return None;
-  };
+  let comm_mid = timeline_comm_info.get("mid").and_then(|val| val.as_str())?;
   // 评论的根mid
-  let Some(comm_senior_id) = timeline_comm_info.get("rootidstr").and_then(|val| val.as_str()
-  ) else {
-    return None;
-  };
+  let comm_senior_id = timeline_comm_info.get("rootidstr").and_then(|val| val.as_str())?;
   // 是否是评论回复
   let reply: bool = comm_mid != comm_senior_id;
 
   // 评论用户
-  let Some(timeline_mem) = timeline_comm_info.get("user").and_then(|val| val.as_object())
-  else { return None; };
+  let timeline_mem = timeline_comm_info.get("user").and_then(|val| val.as_object())?;
 
   // 评论内容
   let comm_text = timeline_comm_info.get("text_raw").and_then(|val| val.as_str()).unwrap_or("");
 
   // 评论时间
-  let comm_era = timeline_comm_info.get("created_at").and_then(|v| v.as_str()).
-    and_then(|era_talk| {
-      Epoch::from_format_str(&era_talk.replace("+0800 ", ""), "%a %b %d %H:%M:%S %Y").
-        map(|era_val| Formatter::new(era_val, ISO8601_DATE).to_string()).ok()
-    }).
-    or_else(|| {
-      Epoch::now().map(|era_val| Formatter::new(era_val, ISO8601_DATE).to_string()).ok()
-    });
-  let comm_era = if let Some(timeline_era) = comm_era {
-    timeline_era
-  } else { return None; };
+  let (comm_era, comm_utc, comm_offset) = anly_occur(
+    timeline_comm_info.get("created_at").and_then(|val| val.as_str()), crawl_era,
+    "comment", comm_mid);
 
   let comm_mem_id = timeline_mem.get("idstr").and_then(|val| val.as_str()).unwrap_or("");
   let comm_mem_name = timeline_mem.get("screen_name").and_then(|val| val.as_str()
   ).unwrap_or("");
 
+  // 评论的点赞数与回复数
+  let comm_like_cnt = timeline_comm_info.get("like_counts").and_then(|val| val.as_u32()).
+    unwrap_or(0);
+  let comm_reply_cnt = timeline_comm_info.get("total_number").and_then(|val| val.as_u32()).
+    unwrap_or(0);
+
   comm_arrs.push(WeiboHotTimelineComm::weibo_hot_timeline_comm_c(
     timeline_mid.to_string(),
     comm_mid.to_string(), comm_text.to_string(),
     comm_mem_id.to_string(), comm_mem_name.to_string(),
-    comm_era.to_string(),
-    reply, comm_senior_id.to_string()));
+    comm_era,
+    reply, comm_senior_id.to_string(),
+    comm_like_cnt, comm_reply_cnt,
+    comm_utc, comm_offset, crawl_era.to_string()));
 
   // 评论回复
   let comm_comms: Vec<WeiboHotTimelineComm> = timeline_comm_info.get("comments").
     and_then(|v| v.as_array()).
     into_iter().flatten().
-    flat_map(|comm_commi| anly_hot_timeline_comm(timeline_mid, comm_commi)).flatten().
+    flat_map(|comm_commi| anly_hot_timeline_comm(timeline_mid, comm_commi, crawl_era)).flatten().
     collect();
   comm_arrs.extend(comm_comms);
 
   Some(comm_arrs)
 }
+
+/// 从热门推荐的正文中提取话题、提及与链接
+///
+/// ## 参数
+/// - `hot_timeline_arrs`：本次爬取的热门推荐，长微博已展开
+fn anly_hot_timeline_ents(hot_timeline_arrs: &[WeiboHotTimeline]) -> Vec<WeiboHotTimelineEnt> {
+  hot_timeline_arrs.iter().
+    flat_map(|hot_timeline_arri| {
+      anly_text_ents(&hot_timeline_arri.text).into_iter().map(|weibo_ent| {
+        WeiboHotTimelineEnt::weibo_hot_timeline_ent_c(hot_timeline_arri.mid.clone(), weibo_ent)
+      })
+    }).
+    collect()
+}
+
+/// 从评论中提取话题、提及与链接
+///
+/// ## 参数
+/// - `hot_timeline_comm_arrs`：本次爬取的评论
+///
+/// ## 返回
+/// 评论的(mid, comm_mid)与评论的全部实体
+fn anly_hot_timeline_comm_ents(hot_timeline_comm_arrs: &[WeiboHotTimelineComm])
+  -> (Vec<(String, String)>, Vec<WeiboHotTimelineCommEnt>) {
+  let comm_key_arrs = hot_timeline_comm_arrs.iter().
+    map(|comm_arri| (comm_arri.mid.clone(), comm_arri.comm_mid.clone())).
+    collect();
+  let comm_ent_arrs = hot_timeline_comm_arrs.iter().
+    flat_map(|comm_arri| {
+      anly_text_ents(&comm_arri.text).into_iter().map(|weibo_ent| {
+        WeiboHotTimelineCommEnt::weibo_hot_timeline_comm_ent_c(
+          comm_arri.mid.clone(), comm_arri.comm_mid.clone(), weibo_ent)
+      })
+    }).
+    collect();
+  (comm_key_arrs, comm_ent_arrs)
+}
+
+/// UNIX秒转换为完整时间字符串，格式为YYYY-MM-DD HH:MM:SS（UTC）
+///
+/// ## 参数
+/// - `secs`：UNIX秒
+pub fn secs_era(secs: i64) -> String {
+  let (year, month, day, hour, minute, second, _) =
+    Epoch::from_unix_seconds(secs as f64).to_gregorian_utc();
+  format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second)
+}
+
+/// 解析微博的created_at，形如`Sat Oct 17 10:00:00 +0800 2026`
+///
+/// ## 参数
+/// - `era_talk`：created_at
+///
+/// ## 返回
+/// 依次为发布者时区的日期（YYYY-MM-DD）、UTC完整时间（YYYY-MM-DD HH:MM:SS）与时区偏移（±HH:MM），
+/// 格式错误时返回None
+pub fn anly_created_at(era_talk: &str) -> Option<(String, String, String)> {
+  let era_parts: Vec<&str> = era_talk.split_whitespace().collect();
+  let [_, era_mon, era_day, era_time, era_offset, era_year] = era_parts[..] else {
+    return None;
+  };
+  // hifitime会把无法解析的年份当作0年
+  if era_year.len() != 4 || !era_year.bytes().all(|year_byt| year_byt.is_ascii_digit()) {
+    return None;
+  }
+  let offset_secs = anly_era_offset(era_offset)?;
+  // 不解析星期：hifitime按TAI校验星期，当天最后几十秒会被误判为星期不匹配
+  let local_secs = Epoch::from_format_str(
+    &format!("{era_mon} {era_day} {era_time} {era_year}"), "%b %d %H:%M:%S %Y").
+    ok()?.to_unix_seconds().round() as i64;
+  let local_era = secs_era(local_secs);
+  Some((local_era[.."YYYY-MM-DD".len()].to_string(),
+        secs_era(local_secs - offset_secs),
+        format!("{}:{}", &era_offset[..3], &era_offset[3..])))
+}
+
+/// 解析形如`+0800`、`-0530`的时区偏移
+///
+/// ## 参数
+/// - `offset_talk`：时区偏移
+///
+/// ## 返回
+/// 时区偏移的秒数，格式错误或超出±14:00时返回None
+fn anly_era_offset(offset_talk: &str) -> Option<i64> {
+  let offset_sign = match offset_talk.as_bytes() {
+    [b'+', ..] => 1,
+    [b'-', ..] => -1,
+    _ => return None,
+  };
+  let offset_num = offset_talk.get(1..)?;
+  if offset_num.len() != 4 || !offset_num.bytes().all(|offset_byt| offset_byt.is_ascii_digit()) {
+    return None;
+  }
+  let offset_hour: i64 = offset_num[..2].parse().ok()?;
+  let offset_minute: i64 = offset_num[2..].parse().ok()?;
+  if offset_minute >= 60 || offset_hour * 60 + offset_minute > 14 * 60 {
+    return None;
+  }
+  Some(offset_sign * (offset_hour * 3600 + offset_minute * 60))
+}
+
+/// 解析热门推荐或评论的created_at。无法解析时记录日志，日期以爬取的日期代替，UTC时间与时区偏移
+/// 留空，爬取结束时按UTC时间为空统计解析失败的数量
+///
+/// ## 参数
+/// - `era_talk`：created_at，可能缺失
+/// - `crawl_era`：爬取的时间，格式为YYYY-MM-DD HH:MM:SS（UTC）
+/// - `occur_des`：用于日志的数据类型
+/// - `occur_mid`：用于日志的mid
+///
+/// ## 返回
+/// 依次为日期、UTC完整时间与时区偏移
+fn anly_occur(era_talk: Option<&str>, crawl_era: &str, occur_des: &str, occur_mid: &str)
+  -> (String, String, String) {
+  match era_talk.and_then(anly_created_at) {
+    Some(occur) => occur,
+    None => {
+      warn!("created_at {:?} of {} {} cannot be parsed", era_talk.unwrap_or(""), occur_des,
+            occur_mid);
+      (crawl_era[.."YYYY-MM-DD".len()].to_string(), String::new(), String::new())
+    }
+  }
+}
+
+/// 完整时间字符串转换为UNIX秒
+///
+/// ## 参数
+/// - `era_talk`：完整时间字符串，格式为YYYY-MM-DD HH:MM:SS（UTC）
+///
+/// ## 返回
+/// 空字符串或格式错误时返回None
+pub fn era_secs(era_talk: &str) -> Option<i64> {
+  if era_talk.is_empty() {
+    return None;
+  }
+  Epoch::from_format_str(era_talk, ERA_FMT).map(|era_val| era_val.to_unix_seconds() as i64).ok()
+}
+
+/// HTTP日期转换为UNIX秒，用于Set-Cookie的Expires与Retry-After
+///
+/// ## 参数
+/// - `http_era`：形如`Thu, 15-Oct-2026 09:00:00 GMT`或`Thu, 15 Oct 2026 09:00:00 GMT`
+///
+/// ## 返回
+/// 格式错误时返回None
+pub fn http_era_secs(http_era: &str) -> Option<i64> {
+  const MONTH_NAMES: [&str; 12] =
+    ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+  let http_era = http_era.split_once(',').map_or(http_era, |(_, http_era)| http_era).
+    replace('-', " ");
+  let http_era_arrs: Vec<&str> = http_era.split_whitespace().collect();
+  let [day, month, year, hms, ..] = http_era_arrs.as_slice() else {
+    return None;
+  };
+  let month = MONTH_NAMES.iter().position(|month_name| month_name.eq_ignore_ascii_case(month))?;
+  era_secs(&format!("{}-{:02}-{:0>2} {}", year, month + 1, day, hms))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dbs::tests::dbs_test_conn;
+  use crate::dbs::tests::dbs_test_timeline;
+  use super::*;
+
+  fn long_hot_timeline(timeline_mid: &str, timeline_text: &str) -> WeiboHotTimeline {
+    WeiboHotTimeline::weibo_hot_timeline_c(
+      timeline_mid.to_string(), timeline_mid.to_string(), timeline_text.to_string(),
+      "u1".to_string(), "n".to_string(), "2026-10-18".to_string(), String::new(), String::new(),
+      String::new(), WeiboHotTimelineMetr { long_text: true, ..Default::default() })
+  }
+
+  #[tokio::test]
+  async fn furnish_longtext_olds_keeps_full_text_ents() {
+    let weibo_db_rb_conn = dbs_test_conn("longtext-olds").await;
+    dbs_test_timeline(&weibo_db_rb_conn, "m1", "#长微博# 全文 #结尾# @张三").await;
+    weibo_db_rb_conn.exec(
+      "update weibo_hot_timeline set long_text = 1, text_expanded = 1 where mid = 'm1'", vec![]).
+      await.unwrap();
+
+    // 再次爬取到的内容是截断的，未存储全文的长微博保持原样
+    let mut hot_timeline_arrs = vec![long_hot_timeline("m1", "#长微博# 全…"),
+                                     long_hot_timeline("m2", "#未展开# 全…")];
+    furnish_longtext_olds_hot_timeline(&weibo_db_rb_conn, &mut hot_timeline_arrs).await.unwrap();
+    assert_eq!(hot_timeline_arrs[0].text, "#长微博# 全文 #结尾# @张三");
+    assert!(hot_timeline_arrs[0].text_expanded);
+    assert_eq!(hot_timeline_arrs[1].text, "#未展开# 全…");
+    assert!(!hot_timeline_arrs[1].text_expanded);
+
+    // 替换实体后仍是由全文提取的实体
+    let timeline_mid_arrs = vec!["m1".to_string()];
+    WeiboHotTimelineEnt::weibo_hot_timeline_ent_u(
+      &weibo_db_rb_conn, &timeline_mid_arrs, anly_hot_timeline_ents(&hot_timeline_arrs[..1])).
+      await.unwrap();
+    WeiboHotTimeline::weibo_hot_timeline_u(&weibo_db_rb_conn, hot_timeline_arrs).await.unwrap();
+    let timeline_ents: Vec<String> = WeiboHotTimelineEnt::select_all(&weibo_db_rb_conn).await.
+      unwrap().into_iter().map(|timeline_ent| timeline_ent.ent).collect();
+    assert_eq!(timeline_ents, ["长微博", "结尾", "张三"]);
+    let timeline_texts: Vec<String> = WeiboHotTimeline::select_by_map(
+      &weibo_db_rb_conn, rbs::value! {"mid": "m1"}).await.unwrap().into_iter().
+      map(|timeline_arri| timeline_arri.text).collect();
+    assert_eq!(timeline_texts, ["#长微博# 全文 #结尾# @张三"]);
+  }
+
+  #[test]
+  fn page_mid_known_needs_whole_page() {
+    let mid_sets = |mids: &[&str]| -> HashSet<String> {
+      mids.iter().map(|mid| mid.to_string()).collect()
+    };
+    let page_mid_arrs: Vec<String> = ["m1", "m2", "m3"].iter().map(|mid| mid.to_string()).collect();
+    // 夹带一条旧微博时继续爬取下一页
+    assert!(!page_mid_known(&page_mid_arrs, &mid_sets(&["m2"]), &mid_sets(&[])));
+    assert!(page_mid_known(&page_mid_arrs, &mid_sets(&["m1", "m2", "m3"]), &mid_sets(&[])));
+    // 本次爬取的前几页中出现过的mid同样视为已爬取
+    assert!(page_mid_known(&page_mid_arrs, &mid_sets(&["m1"]), &mid_sets(&["m2", "m3"])));
+    assert!(!page_mid_known(&[], &mid_sets(&["m1"]), &mid_sets(&[])));
+  }
+
+  #[test]
+  fn anly_comment_replies_page_parses_replies() {
+    let (comm_arrs, comm_max_id) = anly_comment_replies_page("t1", r#"{"data": [
+      {"mid": "c2", "rootidstr": "c1", "text_raw": "回复",
+       "created_at": "Sat Oct 18 08:00:00 +0800 2026",
+       "user": {"idstr": "u2", "screen_name": "n2"}, "like_counts": 3},
+      {"mid": "c3", "text_raw": "缺少rootidstr"}
+    ], "max_id": 42}"#).unwrap();
+    assert_eq!(comm_arrs.len(), 1);
+    assert_eq!((comm_arrs[0].comm_mid.as_str(), comm_arrs[0].senior_id.as_str()), ("c2", "c1"));
+    assert!(comm_arrs[0].reply);
+    assert_eq!(comm_max_id, "42");
+
+    let (comm_arrs, comm_max_id) = anly_comment_replies_page("t1", r#"{"data": []}"#).unwrap();
+    assert!(comm_arrs.is_empty() && comm_max_id.is_empty());
+    assert!(anly_comment_replies_page("t1", r#"{"ok": 0}"#).is_err());
+    assert!(anly_comment_replies_page("t1", r#"{"data": {}}"#).is_err());
+    assert!(anly_comment_replies_page("t1", "<html>").is_err());
+  }
+
+  #[tokio::test]
+  async fn furnish_comment_pages_keeps_comments_when_later_page_fails() {
+    let comm_page_talk = r#"{"data": [
+      {"mid": "c1", "idstr": "c1", "rootidstr": "c1", "text_raw": "根评论",
+       "user": {"idstr": "u1", "screen_name": "n1"}, "total_number": 5,
+       "comments": [{"mid": "c2", "rootidstr": "c1", "text_raw": "回复",
+                     "user": {"idstr": "u2", "screen_name": "n2"}}]}
+    ], "max_id": 7}"#;
+    // (各页的返回, 期望的评论mid, 期望获取的游标)，None表示返回错误
+    let comm_page_cases = [
+      ([Some(comm_page_talk), None], Some(vec!["c1", "c2"]), vec!["0", "7"]),
+      ([Some(comm_page_talk), Some("<html>")], Some(vec!["c1", "c2"]), vec!["0", "7"]),
+      ([None, Some(comm_page_talk)], None, vec!["0"]),
+      ([Some(r#"{"data": {}}"#), Some(comm_page_talk)], None, vec!["0"]),
+    ];
+    for (comm_page_talks, comm_mids, comm_max_ids) in comm_page_cases {
+      let mut comm_page_talks = comm_page_talks.into_iter();
+      let mut comm_gain_max_ids = vec![];
+      let comm_page_rst = furnish_comment_pages("t1", 3, |comm_max_id| {
+        comm_gain_max_ids.push(comm_max_id);
+        let comm_page_talk = comm_page_talks.next().unwrap();
+        async move {
+          comm_page_talk.map(String::from).ok_or_else(|| weibo_jzon_err!("page failed"))
+        }
+      }).await;
+      assert_eq!(comm_gain_max_ids, comm_max_ids);
+      match comm_mids {
+        Some(comm_mids) => {
+          let (comm_arrs, comm_senior_arrs) = comm_page_rst.unwrap();
+          let comm_arr_mids: Vec<&str> = comm_arrs.iter().
+            map(|comm_arri| comm_arri.comm_mid.as_str()).
+            collect();
+          assert_eq!(comm_arr_mids, comm_mids);
+          assert_eq!(comm_senior_arrs, ["c1"]);
+        }
+        None => assert!(comm_page_rst.is_err()),
+      }
+    }
+  }
+
+  #[test]
+  fn anly_cnt_parses_counts() {
+    // (计数字段, 期望的计数)
+    let cnt_cases: [(JsonValue, u32); 14] = [
+      (jzon::from("1.2万"), 12000),
+      (jzon::from("10万+"), 100000),
+      (jzon::from("3亿"), 300000000),
+      (jzon::from(" 1,234 "), 1234),
+      (jzon::from("0.5万"), 5000),
+      (jzon::from("100"), 100),
+      (jzon::from(42), 42),
+      (jzon::from(0), 0),
+      (jzon::from(u64::MAX), u32::MAX),
+      (jzon::from("50亿"), u32::MAX),
+      (jzon::from("-3"), 0),
+      (jzon::from("很多"), 0),
+      (jzon::from(""), 0),
+      (JsonValue::Null, 0),
+    ];
+    for (cnt_val, cnt) in cnt_cases {
+      assert_eq!(anly_cnt(Some(&cnt_val)), cnt, "{}", cnt_val);
+    }
+    assert_eq!(anly_cnt(None), 0);
+    assert_eq!(anly_cnt(Some(&jzon::array![1])), 0);
+  }
+
+  #[test]
+  fn anly_html_text_strips_tags() {
+    assert_eq!(anly_html_text("<a href=\"https://app.weibo.com/t/feed/abc\" rel=\"nofollow\">\
+                               iPhone客户端</a>"), "iPhone客户端");
+    assert_eq!(anly_html_text(" 微博 weibo.com "), "微博 weibo.com");
+    assert_eq!(anly_html_text("<b>超话</b><i>社区</i>"), "超话社区");
+    assert_eq!(anly_html_text("1 > 0"), "1 > 0");
+    assert_eq!(anly_html_text("<a href=\"x\">未闭合"), "未闭合");
+    assert_eq!(anly_html_text("<br/>"), "");
+    assert_eq!(anly_html_text(""), "");
+  }
+
+  #[test]
+  fn anly_created_at_converts_to_utc() {
+    // (created_at, 发布者时区的日期, UTC完整时间, 时区偏移)
+    let era_cases = [
+      ("Sat Oct 17 10:00:00 +0800 2026", "2026-10-17", "2026-10-17 02:00:00", "+08:00"),
+      // 东八区的凌晨在UTC仍是前一天
+      ("Sun Oct 18 01:30:00 +0800 2026", "2026-10-18", "2026-10-17 17:30:00", "+08:00"),
+      ("Fri Oct 16 22:15:00 -0530 2026", "2026-10-16", "2026-10-17 03:45:00", "-05:30"),
+      ("Thu Dec 31 23:59:59 -0100 2026", "2026-12-31", "2027-01-01 00:59:59", "-01:00"),
+      ("Fri Jan 01 00:00:00 +0000 2027", "2027-01-01", "2027-01-01 00:00:00", "+00:00"),
+      // 星期不参与解析
+      ("Mon Oct 17 10:00:00 +0800 2026", "2026-10-17", "2026-10-17 02:00:00", "+08:00"),
+    ];
+    for (era_talk, local_era, utc_era, offset_era) in era_cases {
+      assert_eq!(anly_created_at(era_talk),
+                 Some((local_era.to_string(), utc_era.to_string(), offset_era.to_string())),
+                 "{}", era_talk);
+    }
+  }
+
+  #[test]
+  fn anly_created_at_rejects_unparsable() {
+    for era_talk in [
+      "", "2026-10-17 10:00:00", "Sat Oct 17 10:00:00 2026", "Sat Oct 17 10:00:00 +0800 2026 x",
+      "Sat Oct 17 10:00:00 0800 2026", "Sat Oct 17 10:00:00 +08:00 2026",
+      "Sat Oct 17 10:00:00 +1500 2026", "Sat Foo 17 10:00:00 +0800 2026",
+      "Sat Oct 17 25:00:00 +0800 2026", "Sat Oct 17 10:00 +0800 2026",
+      "Sat Oct 17 10:00:00 +0800 YYYY", "Sat Oct 17 10:00:00 +0800 26",
+      "Sat Oct 32 10:00:00 +0800 2026", "Sat Feb 30 10:00:00 +0800 2026",
+    ] {
+      assert_eq!(anly_created_at(era_talk), None, "{:?}", era_talk);
+    }
+  }
+
+  #[test]
+  fn anly_era_offset_parses_offsets() {
+    let offset_cases = [
+      ("+0800", Some(8 * 3600)), ("-0530", Some(-(5 * 3600 + 30 * 60))), ("+0000", Some(0)),
+      ("-0000", Some(0)), ("+1400", Some(14 * 3600)), ("-1400", Some(-14 * 3600)),
+      ("+1401", None), ("+0860", None), ("0800", None), ("+080", None), ("+08000", None),
+      ("+08:00", None), ("+０８００", None), ("+", None), ("", None),
+    ];
+    for (offset_talk, offset_secs) in offset_cases {
+      assert_eq!(anly_era_offset(offset_talk), offset_secs, "{:?}", offset_talk);
+    }
+  }
+}
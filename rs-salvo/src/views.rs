@@ -1,13 +1,27 @@
+use base64::Engine;
 use jzon::object;
 use jzon::JsonValue;
 use nyquest::AsyncClient;
 use rbatis::RBatis;
+use salvo::fs::NamedFile;
+use salvo::http::header::CACHE_CONTROL;
 use salvo::http::header::CONTENT_TYPE;
+use salvo::http::mime;
 use salvo::http::HeaderValue;
 use salvo::prelude::*;
 use crate::dbs::*;
+use crate::ents::WeiboEntIlk;
 use crate::exceptions::WeiboError;
+use crate::pics;
+use crate::prefs::prefs;
+use crate::sched;
 use crate::utils;
+use crate::watch::WEIBO_WATCH_CACHE;
+use crate::watch::keyword_fold;
+use crate::watch::WeiboHitIlk;
+
+/// 关键词的最大字符数
+const KEYWORD_CHAR_MAX: usize = 64;
 
 #[handler]
 pub async fn hello() -> String {
@@ -23,6 +37,8 @@ struct RespBd {
   info: Option<JsonValue>,
   // 程序是否成功
   suc: bool,
+  // 请求redact时，内容是否经过关键词替换；没有关键词时为false，未请求时不输出
+  redacted: Option<bool>,
 }
 
 impl RespBd {
@@ -34,6 +50,19 @@ impl RespBd {
     Self {
       info: Some(resp_info.into()),
       suc: true,
+      redacted: None,
+    }
+  }
+
+  /// 可能经过关键词替换的成功响应
+  ///
+  /// ## 参数
+  /// - `resp_info`: 响应内容
+  /// - `redacted`: 请求redact时内容是否经过替换，未请求时为None
+  pub fn redact_resp(resp_info: impl Into<JsonValue>, redacted: Option<bool>) -> Self {
+    Self {
+      redacted,
+      ..Self::suc_resp(resp_info)
     }
   }
 
@@ -47,10 +76,15 @@ impl RespBd {
       WeiboError::JzonError(_) => "cannot analyse weibo",
       WeiboError::SalvoError(_) => "service error",
       WeiboError::RbatisError(_) => "database error",
+      WeiboError::SchedError(_) => "schedule error",
+      WeiboError::MigrError(_) => "database error",
+      WeiboError::PrefsError(_) => "service error",
+      WeiboError::IoError(_) => "storage error",
     };
     Self {
       info: Some(err_des.into()),
       suc: false,
+      redacted: None,
     }
   }
 }
@@ -73,18 +107,22 @@ impl Writer for WeiboError {
   }
 }
 
-impl Into<JsonValue> for RespBd {
-  fn into(self) -> JsonValue {
-    object! {
-      info: self.info,
-      suc: self.suc
+impl From<RespBd> for JsonValue {
+  fn from(resp_bd: RespBd) -> Self {
+    let mut resp_jzon = object! {
+      info: resp_bd.info,
+      suc: resp_bd.suc
+    };
+    if let Some(redacted) = resp_bd.redacted {
+      resp_jzon["redacted"] = redacted.into();
     }
+    resp_jzon
   }
 }
 
-impl Into<JsonValue> for WeiboError {
-  fn into(self) -> JsonValue {
-    self.to_string().into()
+impl From<WeiboError> for JsonValue {
+  fn from(flaw: WeiboError) -> Self {
+    flaw.to_string().into()
   }
 }
 
@@ -94,19 +132,47 @@ pub async fn hot_search_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd
   struct ReqBdHotSearchR {
     weibo_title: Option<String>,
     occur_era: Option<String>,
+    from_era: Option<String>,
+    to_era: Option<String>,
   }
   // 使用salvo内置的json解析（serde-json）
   let req_bd_hot_search_r: ReqBdHotSearchR = req.parse_json().await?;
+  let occur_span = era_span_verify(req_bd_hot_search_r.from_era, req_bd_hot_search_r.to_era)?;
   let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
     "cannot connect to the database".to_string(),
   ))?;
   let weibo_hot_search_arrs = WeiboHotSearch::weibo_hot_search_r(
-    weibo_db_rb_conn, req_bd_hot_search_r.weibo_title, req_bd_hot_search_r.occur_era).await?;
+    weibo_db_rb_conn, req_bd_hot_search_r.weibo_title, req_bd_hot_search_r.occur_era,
+    &occur_span).await?;
   // 使用salvo内置的Json返回（serde-json）
   // Ok(Json(weibo_hot_search_arrs))
   Ok(RespBd::suc_resp(weibo_hot_search_arrs))
 }
 
+#[handler]
+pub async fn hot_search_history_r(
+  req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  #[derive(Debug, serde::Deserialize)]
+  struct ReqBdHotSearchHistoryR {
+    weibo_title: String,
+    occur_era: Option<String>,
+    from_era: Option<String>,
+    to_era: Option<String>,
+  }
+  let req_bd_hot_search_history_r: ReqBdHotSearchHistoryR = req.parse_json().await?;
+  // 指定某一天时，覆盖from_era与to_era
+  let (snap_from_era, snap_to_era) = match req_bd_hot_search_history_r.occur_era {
+    Some(occur_era) => (Some(occur_era.clone()), Some(occur_era)),
+    None => (req_bd_hot_search_history_r.from_era, req_bd_hot_search_history_r.to_era),
+  };
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_hot_search_snap_arrs = WeiboHotSearchSnap::weibo_hot_search_snap_r(
+    weibo_db_rb_conn, req_bd_hot_search_history_r.weibo_title, snap_from_era, snap_to_era).await?;
+  Ok(RespBd::suc_resp(weibo_hot_search_snap_arrs))
+}
+
 #[handler]
 pub async fn hot_search_u(depot: &mut Depot) -> Result<RespBd, WeiboError> {
   let weibo_clt: &AsyncClient = depot.get("weibo_clt").unwrap();
@@ -128,9 +194,12 @@ pub async fn hot_timeline_r(req: &mut Request, depot: &mut Depot) -> Result<Resp
   let mut timeline_mem_id: Option<String> = None;
   let mut timeline_mem_name: Option<String> = None;
   let mut timeline_occur_era: Option<String> = None;
+  let mut timeline_occur_span = WeiboEraSpan::default();
   let mut pic: bool = false;
   let mut comm: bool = false;
+  let mut redact: bool = false;
   if let Some(req_bd_hot_timeline_r) = jzon_parse_req_bd(req).await?.as_object() {
+    timeline_occur_span = anly_era_span(req_bd_hot_timeline_r)?;
     timeline_mid = req_bd_hot_timeline_r.get("timeline_mid").
       and_then(|val| val.as_str()).
       map(|val| val.to_string());
@@ -149,30 +218,84 @@ pub async fn hot_timeline_r(req: &mut Request, depot: &mut Depot) -> Result<Resp
     comm = req_bd_hot_timeline_r.get("comm").
       and_then(|val| val.as_bool()).
       ok_or_else(|| WeiboError::SalvoError("no valid comm".to_string()))?;
+    redact = req_bd_hot_timeline_r.get("redact").
+      and_then(|val| val.as_bool()).unwrap_or(false);
   }
   let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
     "cannot connect to the database".to_string(),
   ))?;
-  let weibo_hot_timeline_arrs = WeiboHotTimeline::weibo_hot_timeline_r(
+  let mut weibo_hot_timeline_arrs = WeiboHotTimeline::weibo_hot_timeline_r(
     weibo_db_rb_conn, timeline_mid, timeline_mem_id, timeline_mem_name, timeline_occur_era,
-    pic, comm).await?;
-  Ok(RespBd::suc_resp(weibo_hot_timeline_arrs))
+    &timeline_occur_span, pic, comm).await?;
+  // 替换正文与评论中命中关键词的内容，没有关键词时原样返回，响应中的redacted为false
+  let weibo_watch = if redact { WEIBO_WATCH_CACHE.watch().await } else { None };
+  if let Some(weibo_watch) = &weibo_watch {
+    for timeline_arri in &mut weibo_hot_timeline_arrs {
+      timeline_arri.timeline.text = weibo_watch.watch_redact(&timeline_arri.timeline.text);
+      for timeline_comm_arri in &mut timeline_arri.comms {
+        timeline_comm_arri.text = weibo_watch.watch_redact(&timeline_comm_arri.text);
+      }
+    }
+  }
+  Ok(RespBd::redact_resp(weibo_hot_timeline_arrs, redact.then_some(weibo_watch.is_some())))
 }
 
+#[handler]
+pub async fn hot_timeline_history_r(
+  req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  #[derive(Debug, serde::Deserialize)]
+  struct ReqBdHotTimelineHistoryR {
+    timeline_mid: String,
+    occur_era: Option<String>,
+    from_era: Option<String>,
+    to_era: Option<String>,
+  }
+  let req_bd_hot_timeline_history_r: ReqBdHotTimelineHistoryR = req.parse_json().await?;
+  // 指定某一天时，覆盖from_era与to_era
+  let (snap_from_era, snap_to_era) = match req_bd_hot_timeline_history_r.occur_era {
+    Some(occur_era) => (Some(occur_era.clone()), Some(occur_era)),
+    None => (req_bd_hot_timeline_history_r.from_era, req_bd_hot_timeline_history_r.to_era),
+  };
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_hot_timeline_snap_arrs = WeiboHotTimelineSnap::weibo_hot_timeline_snap_r(
+    weibo_db_rb_conn, req_bd_hot_timeline_history_r.timeline_mid, snap_from_era, snap_to_era).
+    await?;
+  Ok(RespBd::suc_resp(weibo_hot_timeline_snap_arrs))
+}
+
+/// 爬取热门推荐。请求体中的`timeline_page`与`timeline_page_size`为本次的页数与每页条数，
+/// 未提供时使用`hot_timeline_page_cnt`与`hot_timeline_page_size`配置。整页的mid都已爬取过时
+/// 提前停止翻页，只有部分旧微博的页会继续翻，最多`hot_timeline_page_max`页。
 #[handler]
 pub async fn hot_timeline_u(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
   let weibo_clt: &AsyncClient = depot.get("weibo_clt").unwrap();
   let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
   let mut timeline_pic = false;
   let mut timeline_comm = false;
+  let mut timeline_page = prefs().hot_timeline_page_cnt;
+  let mut timeline_page_size = prefs().hot_timeline_page_size;
   if let Some(req_bd_hot_timeline_u) = jzon_parse_req_bd(req).await?.as_object() {
     timeline_pic = req_bd_hot_timeline_u.get("timeline_pic").
-      and_then(|val| val.as_bool()).unwrap_or_else(|| false);
+      and_then(|val| val.as_bool()).unwrap_or(false);
     timeline_comm = req_bd_hot_timeline_u.get("timeline_comm").
-      and_then(|val| val.as_bool()).unwrap_or_else(|| false);
+      and_then(|val| val.as_bool()).unwrap_or(false);
+    timeline_page = req_bd_hot_timeline_u.get("timeline_page").
+      and_then(|val| val.as_u32()).unwrap_or(prefs().hot_timeline_page_cnt);
+    if let Some(req_page_size) = req_bd_hot_timeline_u.get("timeline_page_size") {
+      timeline_page_size = req_page_size.as_u32().
+        filter(|page_size| (1..=100).contains(page_size)).
+        ok_or_else(|| WeiboError::SalvoError(
+          "timeline_page_size must be between 1 and 100".to_string()))?;
+    }
   }
-  utils::attain_ajax_hottimeline(weibo_clt, weibo_db_rb_conn, timeline_pic, timeline_comm).await?;
-  Ok(RespBd::suc_resp("ok".to_string()))
+  let era_flaw_cnt = utils::attain_ajax_hottimeline(
+    weibo_clt, weibo_db_rb_conn, timeline_pic, timeline_comm, timeline_page,
+    timeline_page_size).await?;
+  Ok(RespBd::suc_resp(object! {
+    era_flaw_cnt: era_flaw_cnt
+  }))
 }
 
 #[handler]
@@ -202,6 +325,22 @@ pub async fn hot_timeline_d(req: &mut Request, depot: &mut Depot) -> Result<Resp
   Ok(RespBd::suc_resp("ok".to_string()))
 }
 
+#[handler]
+pub async fn hot_timeline_pic_u(depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_clt: &AsyncClient = depot.get("weibo_clt").unwrap();
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+  let (pic_suc_cnt, pic_flaw_cnt) = utils::attain_sinaimg_hot_timeline(
+    weibo_clt, weibo_db_rb_conn, None).await?;
+  let pic_phash_cnt = utils::attain_pic_phash_hot_timeline(weibo_db_rb_conn).await?;
+  let pic_meta_cnt = utils::attain_pic_meta_hot_timeline(weibo_db_rb_conn).await?;
+  Ok(RespBd::suc_resp(object! {
+    pic_suc_cnt: pic_suc_cnt,
+    pic_flaw_cnt: pic_flaw_cnt,
+    pic_phash_cnt: pic_phash_cnt,
+    pic_meta_cnt: pic_meta_cnt
+  }))
+}
+
 #[handler]
 pub async fn hot_timeline_comm_r(
   req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
@@ -210,7 +349,10 @@ pub async fn hot_timeline_comm_r(
   let mut timeline_mem_id: Option<String> = None;
   let mut timeline_mem_name: Option<String> = None;
   let mut timeline_comm_era: Option<String> = None;
+  let mut timeline_comm_span = WeiboEraSpan::default();
+  let mut redact: bool = false;
   if let Some(req_bd_hot_timeline_comm_r) = jzon_parse_req_bd(req).await?.as_object() {
+    timeline_comm_span = anly_era_span(req_bd_hot_timeline_comm_r)?;
     timeline_mid_arrs = req_bd_hot_timeline_comm_r.get("timeline_mid_arrs").
       and_then(|arrs| arrs.as_array()).
       map(|arrs| {
@@ -228,14 +370,24 @@ pub async fn hot_timeline_comm_r(
     timeline_comm_era = req_bd_hot_timeline_comm_r.get("timeline_comm_era").
       and_then(|val| val.as_str()).
       map(|val| val.to_string());
+    redact = req_bd_hot_timeline_comm_r.get("redact").
+      and_then(|val| val.as_bool()).unwrap_or(false);
   }
   let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
     "cannot connect to the database".to_string(),
   ))?;
-  let weibo_hot_search_arrs = WeiboHotTimelineComm::weibo_hot_timeline_comm_r(
+  let mut weibo_hot_search_arrs = WeiboHotTimelineComm::weibo_hot_timeline_comm_r(
     weibo_db_rb_conn, timeline_mid_arrs.as_ref(),
-    timeline_comm_mid, timeline_mem_id, timeline_mem_name, timeline_comm_era).await?;
-  Ok(RespBd::suc_resp(weibo_hot_search_arrs))
+    timeline_comm_mid, timeline_mem_id, timeline_mem_name, timeline_comm_era,
+    &timeline_comm_span).await?;
+  // 替换评论中命中关键词的内容，没有关键词时原样返回，响应中的redacted为false
+  let weibo_watch = if redact { WEIBO_WATCH_CACHE.watch().await } else { None };
+  if let Some(weibo_watch) = &weibo_watch {
+    for timeline_comm_arri in &mut weibo_hot_search_arrs {
+      timeline_comm_arri.text = weibo_watch.watch_redact(&timeline_comm_arri.text);
+    }
+  }
+  Ok(RespBd::redact_resp(weibo_hot_search_arrs, redact.then_some(weibo_watch.is_some())))
 }
 
 #[handler]
@@ -251,9 +403,13 @@ pub async fn hot_timeline_comm_u(
     let timeline_uid = req_bd_hot_timeline_comm_u.get("timeline_uid").
       and_then(|val| val.as_str()).
       ok_or_else(|| WeiboError::SalvoError("no valid timeline_uid".to_string()))?;
-    utils::attain_ajax_comments_hottimeline(
-      weibo_clt, weibo_db_rb_conn, timeline_mid, timeline_uid).await?;
-    Ok(RespBd::suc_resp("ok".to_string()))
+    let comm_page = req_bd_hot_timeline_comm_u.get("comm_page").
+      and_then(|val| val.as_u32()).unwrap_or(prefs().hot_timeline_comm_page_cnt);
+    let era_flaw_cnt = utils::attain_ajax_comments_hottimeline(
+      weibo_clt, weibo_db_rb_conn, timeline_mid, timeline_uid, comm_page).await?;
+    Ok(RespBd::suc_resp(object! {
+      era_flaw_cnt: era_flaw_cnt
+    }))
   } else {
     Err(WeiboError::SalvoError("invalid search condition".to_string()))
   }
@@ -282,19 +438,564 @@ pub async fn hot_timeline_comm_d(
   Ok(RespBd::suc_resp("ok".to_string()))
 }
 
+#[handler]
+pub async fn topics_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  ent_cnt_r(req, depot, WeiboEntIlk::Topic, "topic").await
+}
+
+#[handler]
+pub async fn mentions_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  ent_cnt_r(req, depot, WeiboEntIlk::Mention, "mention").await
+}
+
+/// 按天统计话题或提及的出现次数
+///
+/// ## 参数
+/// - `ent_ilk`: 实体类型
+/// - `ent_key`: 请求体中筛选实体的键，值不含`#`与`@`
+async fn ent_cnt_r(req: &mut Request, depot: &mut Depot, ent_ilk: WeiboEntIlk,
+                   ent_key: &str) -> Result<RespBd, WeiboError> {
+  let mut weibo_ent: Option<String> = None;
+  let mut occur_span = WeiboEraSpan::default();
+  if let Some(req_bd_ent_cnt_r) = jzon_parse_req_bd(req).await?.as_object() {
+    weibo_ent = req_bd_ent_cnt_r.get(ent_key).
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    occur_span = anly_era_span(req_bd_ent_cnt_r)?;
+  }
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_ent_cnt_arrs = WeiboHotTimelineEntCnt::weibo_hot_timeline_ent_cnt_r(
+    weibo_db_rb_conn, ent_ilk, weibo_ent, &occur_span).await?;
+  Ok(RespBd::suc_resp(weibo_ent_cnt_arrs))
+}
+
+#[handler]
+pub async fn sched_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let mut sched_job: Option<String> = None;
+  if let Some(req_bd_sched_r) = jzon_parse_req_bd(req).await?.as_object() {
+    sched_job = req_bd_sched_r.get("sched_job").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+  }
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_sched_arrs = WeiboSched::weibo_sched_r(weibo_db_rb_conn, sched_job).await?;
+  Ok(RespBd::suc_resp(weibo_sched_arrs))
+}
+
+#[handler]
+pub async fn sched_u(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let Some(req_bd_sched_u) = jzon_parse_req_bd(req).await?.as_object().cloned() else {
+    return Err(WeiboError::SalvoError("invalid sched condition".to_string()));
+  };
+  let sched_job = req_bd_sched_u.get("sched_job").
+    and_then(|val| val.as_str()).
+    ok_or_else(|| WeiboError::SalvoError("no valid sched_job".to_string()))?;
+  let Some(mut weibo_sched) = WeiboSched::weibo_sched_r(
+    weibo_db_rb_conn, Some(sched_job.to_string())).await?.pop() else {
+    return Err(WeiboError::SalvoError(format!("no sched job named {}", sched_job)));
+  };
+
+  // 未提供的字段保持原有配置
+  if let Some(sched_enabled) = req_bd_sched_u.get("sched_enabled").and_then(|val| val.as_bool()) {
+    weibo_sched.enabled = sched_enabled;
+  }
+  if let Some(sched_interval_secs) = req_bd_sched_u.get("sched_interval_secs").
+    and_then(|val| val.as_u32()) {
+    weibo_sched.interval_secs = sched_interval_secs;
+  }
+  if let Some(sched_cron) = req_bd_sched_u.get("sched_cron").and_then(|val| val.as_str()) {
+    weibo_sched.cron = sched_cron.to_string();
+  }
+  if let Some(sched_jitter_secs) = req_bd_sched_u.get("sched_jitter_secs").
+    and_then(|val| val.as_u32()) {
+    weibo_sched.jitter_secs = sched_jitter_secs;
+  }
+  if let Some(sched_pic) = req_bd_sched_u.get("sched_pic").and_then(|val| val.as_bool()) {
+    weibo_sched.pic = sched_pic;
+  }
+  if let Some(sched_comm) = req_bd_sched_u.get("sched_comm").and_then(|val| val.as_bool()) {
+    weibo_sched.comm = sched_comm;
+  }
+
+  sched::sched_verify(&weibo_sched)?;
+  WeiboSched::weibo_sched_u(weibo_db_rb_conn, &weibo_sched).await?;
+  Ok(RespBd::suc_resp("ok".to_string()))
+}
+
+#[handler]
+pub async fn cok_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let mut cok_sta: Option<String> = None;
+  if let Some(req_bd_cok_r) = jzon_parse_req_bd(req).await?.as_object() {
+    cok_sta = req_bd_cok_r.get("cok_sta").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+  }
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_cok_arrs = WeiboCok::weibo_cok_r(weibo_db_rb_conn, cok_sta).await?;
+  Ok(RespBd::suc_resp(weibo_cok_arrs))
+}
+
+#[handler]
+pub async fn cok_u(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let Some(req_bd_cok_u) = jzon_parse_req_bd(req).await?.as_object().cloned() else {
+    return Err(WeiboError::SalvoError("invalid cok condition".to_string()));
+  };
+  let cok = req_bd_cok_u.get("cok").
+    and_then(|val| val.as_str()).
+    map(|val| val.trim()).
+    filter(|val| !val.is_empty() && !val.contains(['\r', '\n'])).
+    ok_or_else(|| WeiboError::SalvoError("no valid cok".to_string()))?;
+  let cok_label = req_bd_cok_u.get("cok_label").and_then(|val| val.as_str()).unwrap_or("");
+
+  let add_era = utils::secs_era(hifitime::Epoch::now()?.to_unix_seconds() as i64);
+  WeiboCok::weibo_cok_u(weibo_db_rb_conn, cok, cok_label, &add_era).await?;
+  Ok(RespBd::suc_resp("ok".to_string()))
+}
+
+#[handler]
+pub async fn cok_d(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let cok_id = jzon_parse_req_bd(req).await?["cok_id"].as_usize().
+    ok_or_else(|| WeiboError::SalvoError("no valid cok_id".to_string()))?;
+  WeiboCok::weibo_cok_d(weibo_db_rb_conn, cok_id).await?;
+  Ok(RespBd::suc_resp("ok".to_string()))
+}
+
+#[handler]
+pub async fn keyword_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let mut keyword_list: Option<String> = None;
+  if let Some(req_bd_keyword_r) = jzon_parse_req_bd(req).await?.as_object() {
+    keyword_list = req_bd_keyword_r.get("keyword_list").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+  }
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_keyword_arrs = WeiboKeyword::weibo_keyword_r(weibo_db_rb_conn, keyword_list).await?;
+  Ok(RespBd::suc_resp(weibo_keyword_arrs))
+}
+
+#[handler]
+pub async fn keyword_u(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let Some(req_bd_keyword_u) = jzon_parse_req_bd(req).await?.as_object().cloned() else {
+    return Err(WeiboError::SalvoError("invalid keyword condition".to_string()));
+  };
+  let keyword_list = anly_keyword_list(&req_bd_keyword_u)?;
+  // 规范化后为空的关键词不能匹配任何内容
+  let keyword_fold = keyword_fold();
+  let keyword_arrs: Vec<String> = req_bd_keyword_u.get("keywords").
+    and_then(|val| val.as_array()).
+    ok_or_else(|| WeiboError::SalvoError("no valid keywords".to_string()))?.
+    iter().
+    map(|val| {
+      val.as_str().
+        map(|val| val.trim()).
+        filter(|val| {
+          !val.contains(['\r', '\n']) && val.chars().count() <= KEYWORD_CHAR_MAX &&
+            !keyword_fold.fold_text(val).chars.is_empty()
+        }).
+        map(String::from).
+        ok_or_else(|| WeiboError::SalvoError(format!("invalid keyword {}", val)))
+    }).
+    collect::<Result<_, _>>()?;
+  if keyword_arrs.is_empty() {
+    return Err(WeiboError::SalvoError("no valid keywords".to_string()));
+  }
+
+  let add_era = utils::secs_era(hifitime::Epoch::now()?.to_unix_seconds() as i64);
+  WeiboKeyword::weibo_keyword_u(weibo_db_rb_conn, &keyword_list, &keyword_arrs, &add_era).await?;
+  let keyword_cnt = WEIBO_WATCH_CACHE.watch_compile(weibo_db_rb_conn).await?;
+  Ok(RespBd::suc_resp(object! {keyword_cnt: keyword_cnt}))
+}
+
+#[handler]
+pub async fn keyword_d(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let Some(req_bd_keyword_d) = jzon_parse_req_bd(req).await?.as_object().cloned() else {
+    return Err(WeiboError::SalvoError("invalid keyword condition".to_string()));
+  };
+  let keyword_list = anly_keyword_list(&req_bd_keyword_d)?;
+  // 未提供keyword时删除整个列表
+  let keyword = req_bd_keyword_d.get("keyword").
+    and_then(|val| val.as_str()).
+    map(|val| val.trim().to_string());
+
+  WeiboKeyword::weibo_keyword_d(weibo_db_rb_conn, keyword_list, keyword).await?;
+  let keyword_cnt = WEIBO_WATCH_CACHE.watch_compile(weibo_db_rb_conn).await?;
+  Ok(RespBd::suc_resp(object! {keyword_cnt: keyword_cnt}))
+}
+
+#[handler]
+pub async fn keyword_hits_r(req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let mut keyword: Option<String> = None;
+  let mut keyword_list: Option<String> = None;
+  let mut hit_ilk: Option<WeiboHitIlk> = None;
+  let mut timeline_mid: Option<String> = None;
+  let mut hit_span = WeiboEraSpan::default();
+  if let Some(req_bd_keyword_hits_r) = jzon_parse_req_bd(req).await?.as_object() {
+    keyword = req_bd_keyword_hits_r.get("keyword").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    keyword_list = req_bd_keyword_hits_r.get("keyword_list").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    if let Some(hit_ilk_val) = req_bd_keyword_hits_r.get("hit_ilk").and_then(|val| val.as_str()) {
+      hit_ilk = Some(WeiboHitIlk::anly_hit_ilk(hit_ilk_val).
+        ok_or_else(|| WeiboError::SalvoError("invalid hit_ilk".to_string()))?);
+    }
+    timeline_mid = req_bd_keyword_hits_r.get("timeline_mid").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    hit_span = anly_era_span(req_bd_keyword_hits_r)?;
+  }
+  let weibo_db_rb_conn = depot.get("weibo_db_rb_conn").map_err(|_| WeiboError::SalvoError(
+    "cannot connect to the database".to_string(),
+  ))?;
+  let weibo_keyword_hit_arrs = WeiboKeywordHit::weibo_keyword_hit_r(
+    weibo_db_rb_conn, keyword, keyword_list, hit_ilk, timeline_mid, &hit_span).await?;
+  Ok(RespBd::suc_resp(weibo_keyword_hit_arrs))
+}
+
+#[handler]
+pub async fn hot_timeline_pic_meta_r(
+  req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let mut timeline_mid: Option<String> = None;
+  let mut pic_hash: Option<String> = None;
+  let mut gps_flag: Option<bool> = None;
+  if let Some(req_bd_hot_timeline_pic_meta_r) = jzon_parse_req_bd(req).await?.as_object() {
+    timeline_mid = req_bd_hot_timeline_pic_meta_r.get("timeline_mid").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    pic_hash = req_bd_hot_timeline_pic_meta_r.get("pic_hash").
+      and_then(|val| val.as_str()).
+      map(|val| val.to_string());
+    gps_flag = req_bd_hot_timeline_pic_meta_r.get("gps_flag").
+      and_then(|val| val.as_bool());
+  }
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+  let weibo_hot_timeline_pic_meta_arrs = WeiboHotTimelinePicMeta::weibo_hot_timeline_pic_meta_r(
+    weibo_db_rb_conn, timeline_mid, pic_hash, gps_flag).await?;
+  Ok(RespBd::suc_resp(weibo_hot_timeline_pic_meta_arrs))
+}
+
+/// 查找与指定图片相似的已下载图片。指定`pic_id`（可同时指定`timeline_mid`）时以已下载的图片为准，
+/// 否则以`pic_b64`上传的图片为准；按感知哈希的汉明距离由近到远返回距离不超过`pic_dist`的图片。
+#[handler]
+pub async fn hot_timeline_pic_similar_r(
+  req: &mut Request, depot: &mut Depot) -> Result<RespBd, WeiboError> {
+  let req_bd_hot_timeline_pic_similar_r = jzon_parse_req_bd_max(
+    req, prefs().pic_upload_max_bytes / 3 * 4 + 1024).await?;
+  let pic_dist = match req_bd_hot_timeline_pic_similar_r["pic_dist"].as_u32() {
+    Some(pic_dist) if pic_dist <= 64 => pic_dist,
+    Some(_) => return Err(WeiboError::SalvoError("no valid pic_dist".to_string())),
+    None => 10,
+  };
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+
+  let pic_phash = if let Some(timeline_pic_id) =
+    req_bd_hot_timeline_pic_similar_r["pic_id"].as_str() {
+    let timeline_mid = req_bd_hot_timeline_pic_similar_r["timeline_mid"].as_str();
+    let hot_timeline_pic = WeiboHotTimelinePic::weibo_hot_timeline_pic_stor_r(
+      weibo_db_rb_conn, timeline_mid, Some(timeline_pic_id), None).await?.
+      ok_or_else(|| WeiboError::SalvoError("picture not found".to_string()))?;
+    if hot_timeline_pic.pic_phash.is_empty() {
+      return Err(WeiboError::SalvoError("picture has no perceptual hash".to_string()));
+    }
+    hot_timeline_pic.pic_phash
+  } else if let Some(pic_b64) = req_bd_hot_timeline_pic_similar_r["pic_b64"].as_str() {
+    let pic_byt = base64::engine::general_purpose::STANDARD.decode(pic_b64).
+      map_err(|_| WeiboError::SalvoError("no valid pic_b64".to_string()))?;
+    if pic_byt.len() > prefs().pic_upload_max_bytes {
+      return Err(WeiboError::SalvoError("uploaded picture is too large".to_string()));
+    }
+    pics::pics_phash_blocking(pic_byt).await.
+      ok_or_else(|| WeiboError::SalvoError("uploaded picture cannot be decoded".to_string()))?
+  } else {
+    return Err(WeiboError::SalvoError("no valid pic_id or pic_b64".to_string()));
+  };
+
+  let mut pic_similar_arrs: Vec<(u32, WeiboHotTimelinePic)> =
+    WeiboHotTimelinePic::weibo_hot_timeline_pic_phash_r(weibo_db_rb_conn, true).await?.
+      into_iter().
+      filter_map(|pic_arri| {
+        let pic_arri_dist = pics::pics_phash_dist(&pic_phash, &pic_arri.pic_phash)?;
+        (pic_arri_dist <= pic_dist).then_some((pic_arri_dist, pic_arri))
+      }).
+      collect();
+  pic_similar_arrs.sort_by_key(|(pic_arri_dist, pic_arri)| (*pic_arri_dist, pic_arri.id));
+
+  Ok(RespBd::suc_resp(object! {
+    pic_phash: pic_phash,
+    pics: pic_similar_arrs.into_iter().map(|(pic_arri_dist, pic_arri)| {
+      let mut pic_arri_jquin = JsonValue::from(pic_arri);
+      // 不暴露服务器上的存储路径
+      pic_arri_jquin.remove("dl_pth");
+      pic_arri_jquin["pic_dist"] = pic_arri_dist.into();
+      pic_arri_jquin
+    }).collect::<Vec<JsonValue>>()
+  }))
+}
+
+/// 按mid与图片id获取已下载的热门推荐图片
+#[handler]
+pub async fn pic_r(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+  let timeline_mid = req.param::<String>("mid").unwrap_or_default();
+  let timeline_pic_id = req.param::<String>("pic_id").unwrap_or_default();
+  if !pics::pics_key_verify(&timeline_mid) || !pics::pics_key_verify(&timeline_pic_id) {
+    return pic_flaw_w(res, StatusCode::BAD_REQUEST, "invalid picture identifier");
+  }
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+  let hot_timeline_pic = WeiboHotTimelinePic::weibo_hot_timeline_pic_stor_r(
+    weibo_db_rb_conn, Some(&timeline_mid), Some(&timeline_pic_id), None).await;
+  pic_send_w(req, res, hot_timeline_pic).await
+}
+
+/// 按内容的哈希获取已下载的热门推荐图片
+#[handler]
+pub async fn pic_hash_r(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+  let pic_hash = req.param::<String>("pic_hash").unwrap_or_default();
+  if !pics::pics_hash_verify(&pic_hash) {
+    return pic_flaw_w(res, StatusCode::BAD_REQUEST, "invalid picture hash");
+  }
+  let weibo_db_rb_conn: &RBatis = depot.get("weibo_db_rb_conn").unwrap();
+  let hot_timeline_pic = WeiboHotTimelinePic::weibo_hot_timeline_pic_stor_r(
+    weibo_db_rb_conn, None, None, Some(&pic_hash)).await;
+  pic_send_w(req, res, hot_timeline_pic).await
+}
+
+/// 发送图片库中的图片文件，支持ETag、Last-Modified与Range。开启`hot_timeline_pic_strip`时发送去除元数据的副本。
+/// 文件名即内容的哈希，内容不会变化，允许客户端长期缓存。
+///
+/// ## 参数
+/// - `req`: salvo请求
+/// - `res`: salvo响应
+/// - `hot_timeline_pic`: 查找到的热门推荐图片
+async fn pic_send_w(req: &Request, res: &mut Response,
+                    hot_timeline_pic: Result<Option<WeiboHotTimelinePic>, WeiboError>) {
+  let hot_timeline_pic = match hot_timeline_pic {
+    Ok(Some(hot_timeline_pic)) => hot_timeline_pic,
+    Ok(None) => return pic_flaw_w(res, StatusCode::NOT_FOUND, "picture not found"),
+    Err(flaw) => return pic_resp_w(res, StatusCode::INTERNAL_SERVER_ERROR, RespBd::err_resp(flaw)),
+  };
+  let Some(pic_fmt) = pics::WeiboPicFmt::from_ext(&hot_timeline_pic.pic_fmt) else {
+    return pic_flaw_w(res, StatusCode::NOT_FOUND, "picture not found");
+  };
+  // 开启hot_timeline_pic_strip时只提供去除元数据的副本
+  let pic_pth = if prefs().hot_timeline_pic_strip {
+    pics::pics_strip_locate(&hot_timeline_pic.pic_hash, pic_fmt).await
+  } else {
+    pics::pics_locate(&hot_timeline_pic.pic_hash, pic_fmt).await
+  };
+  let Some(pic_pth) = pic_pth else {
+    return pic_flaw_w(res, StatusCode::NOT_FOUND, "picture not found");
+  };
+  let Ok(pic_mime) = pic_fmt.pic_mime().parse::<mime::Mime>() else {
+    return pic_flaw_w(res, StatusCode::INTERNAL_SERVER_ERROR, "service error");
+  };
+  match NamedFile::builder(pic_pth).content_type(pic_mime).build().await {
+    Ok(pic_file) => {
+      res.headers.insert(CACHE_CONTROL,
+                         HeaderValue::from_static("public, max-age=31536000, immutable"));
+      pic_file.send(req.headers(), res).await;
+    }
+    Err(_) => pic_flaw_w(res, StatusCode::NOT_FOUND, "picture not found"),
+  }
+}
+
+/// 图片接口的失败响应，与其他接口一样返回RespBd，但带上对应的状态码
+///
+/// ## 参数
+/// - `res`: salvo响应
+/// - `pic_code`: 状态码
+/// - `pic_des`: 失败原因
+fn pic_flaw_w(res: &mut Response, pic_code: StatusCode, pic_des: &str) {
+  pic_resp_w(res, pic_code, RespBd {
+    info: Some(pic_des.into()),
+    suc: false,
+    redacted: None,
+  });
+}
+
+/// 以JSON写出图片接口的RespBd，并带上对应的状态码
+///
+/// ## 参数
+/// - `res`: salvo响应
+/// - `pic_code`: 状态码
+/// - `resp_bd`: 响应内容
+fn pic_resp_w(res: &mut Response, pic_code: StatusCode, resp_bd: RespBd) {
+  res.status_code(pic_code);
+  res.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json; charset=utf-8"));
+  res.render(jzon::stringify(resp_bd));
+}
+
 /// 使用jzon代替salvo内置的serde解析请求体
 ///
 /// ## 参数
 /// - `req`: salvo请求
 async fn jzon_parse_req_bd(req: &mut Request) -> Result<JsonValue, WeiboError> {
+  let req_bd_max = req.secure_max_size();
+  jzon_parse_req_bd_max(req, req_bd_max).await
+}
+
+/// 从请求体中提取关键词列表keyword_list，未提供时为default
+///
+/// ## 参数
+/// - `req_bd`: jzon解析的请求体
+fn anly_keyword_list(req_bd: &jzon::object::Object) -> Result<String, WeiboError> {
+  match req_bd.get("keyword_list") {
+    None => Ok("default".to_string()),
+    Some(keyword_list) => keyword_list.as_str().
+      map(|val| val.trim()).
+      filter(|val| !val.is_empty()).
+      map(String::from).
+      ok_or_else(|| WeiboError::SalvoError("invalid keyword_list".to_string())),
+  }
+}
+
+/// 从请求体中提取时间范围from_era与to_era
+///
+/// ## 参数
+/// - `req_bd`: jzon解析的请求体
+fn anly_era_span(req_bd: &jzon::object::Object) -> Result<WeiboEraSpan, WeiboError> {
+  era_span_verify(
+    req_bd.get("from_era").and_then(|val| val.as_str()).map(String::from),
+    req_bd.get("to_era").and_then(|val| val.as_str()).map(String::from))
+}
+
+/// 检查时间范围的格式，只接受YYYY-MM-DD或YYYY-MM-DD HH:MM:SS（UTC）
+///
+/// ## 参数
+/// - `from_era`: 时间的下限，可选
+/// - `to_era`: 时间的上限，可选
+fn era_span_verify(from_era: Option<String>,
+                   to_era: Option<String>) -> Result<WeiboEraSpan, WeiboError> {
+  for (era_key, era_val) in [("from_era", &from_era), ("to_era", &to_era)] {
+    if let Some(era_val) = era_val
+      && utils::era_secs(era_val).or_else(|| utils::era_secs(&format!("{era_val} 00:00:00"))).
+      is_none() {
+      return Err(WeiboError::SalvoError(format!("invalid {era_key}")));
+    }
+  }
+  Ok(WeiboEraSpan { from_era, to_era })
+}
+
+/// 使用jzon解析请求体，允许超过salvo默认上限的请求体，用于上传图片等
+///
+/// ## 参数
+/// - `req`: salvo请求
+/// - `req_bd_max`: 请求体的最大字节数
+async fn jzon_parse_req_bd_max(req: &mut Request,
+                               req_bd_max: usize) -> Result<JsonValue, WeiboError> {
   let Some(req_ctn_ilk) = req.content_type() else {
     return Err(WeiboError::SalvoError("invalid content-type".to_string()));
   };
   if req_ctn_ilk.subtype() != "json" {
     return Err(WeiboError::SalvoError("invalid content-type".to_string()));
   }
-  let req_pay = req.payload().await?;
-  jzon::parse(std::str::from_utf8(&req_pay).
+  let req_pay = req.payload_with_max_size(req_bd_max).await?;
+  jzon::parse(std::str::from_utf8(req_pay).
     map_err(|flaw| WeiboError::SalvoError(flaw.to_string()))?
   ).map_err(|flaw| WeiboError::SalvoError(flaw.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+  use salvo::affix_state;
+  use salvo::test::ResponseExt;
+  use salvo::test::TestClient;
+  use crate::dbs::tests::dbs_test_conn;
+  use crate::dbs::tests::dbs_test_timeline;
+  use super::*;
+
+  /// 向测试服务发送请求并解析响应
+  ///
+  /// ## 参数
+  /// - `salvo_svc`：测试服务
+  /// - `req_pth`：请求路径
+  /// - `req_bd`：请求体
+  async fn views_test_post(salvo_svc: &Service, req_pth: &str, req_bd: &str) -> JsonValue {
+    let resp_bd = TestClient::post(format!("http://127.0.0.1{}", req_pth)).raw_json(req_bd).
+      send(salvo_svc).await.
+      take_string().await.unwrap();
+    jzon::parse(&resp_bd).unwrap()
+  }
+
+  #[tokio::test]
+  async fn hot_timeline_redact_replaces_timeline_and_comm_text() {
+    let weibo_db_rb_conn = dbs_test_conn("views-redact").await;
+    dbs_test_timeline(&weibo_db_rb_conn, "m1", "今天去了北京玩").await;
+    weibo_db_rb_conn.exec(
+      "insert into weibo_hot_timeline_comm \
+       (mid, comm_mid, text, mem_id, mem_name, comm_era, reply, senior_id) \
+       values ('m1', 'c1', '北京好玩吗', 'u9', 'c', '2026-10-18', 0, 'c1')",
+      vec![]).
+      await.unwrap();
+    let salvo_svc = Service::new(Router::new().
+      hoop(affix_state::insert("weibo_db_rb_conn", weibo_db_rb_conn.clone())).
+      push(Router::with_path("r/hot_timeline").post(hot_timeline_r)).
+      push(Router::with_path("r/hot_timeline_comm").post(hot_timeline_comm_r)));
+    let timeline_bd = r#"{"timeline_mid": "m1", "pic": false, "comm": true, "redact": true}"#;
+    let comm_bd = r#"{"timeline_comm_mid": "c1", "redact": true}"#;
+
+    // 没有关键词时原样返回，并标明没有替换
+    WEIBO_WATCH_CACHE.watch_compile(&weibo_db_rb_conn).await.unwrap();
+    let timeline_resp = views_test_post(&salvo_svc, "/r/hot_timeline", timeline_bd).await;
+    assert_eq!(timeline_resp["redacted"], false);
+    assert_eq!(timeline_resp["info"][0]["text"], "今天去了北京玩");
+    assert_eq!(timeline_resp["info"][0]["comms"][0]["text"], "北京好玩吗");
+    let comm_resp = views_test_post(&salvo_svc, "/r/hot_timeline_comm", comm_bd).await;
+    assert_eq!(comm_resp["redacted"], false);
+    assert_eq!(comm_resp["info"][0]["text"], "北京好玩吗");
+
+    WeiboKeyword::weibo_keyword_u(&weibo_db_rb_conn, "default", &["北京".to_string()],
+                                  "2026-10-18 00:00:00").await.unwrap();
+    WEIBO_WATCH_CACHE.watch_compile(&weibo_db_rb_conn).await.unwrap();
+    let timeline_resp = views_test_post(&salvo_svc, "/r/hot_timeline", timeline_bd).await;
+    assert_eq!(timeline_resp["redacted"], true);
+    assert_eq!(timeline_resp["info"][0]["text"], "今天去了***玩");
+    assert_eq!(timeline_resp["info"][0]["comms"][0]["text"], "***好玩吗");
+    let comm_resp = views_test_post(&salvo_svc, "/r/hot_timeline_comm", comm_bd).await;
+    assert_eq!(comm_resp["redacted"], true);
+    assert_eq!(comm_resp["info"][0]["text"], "***好玩吗");
+
+    // 未请求替换时不输出redacted
+    let timeline_resp = views_test_post(
+      &salvo_svc, "/r/hot_timeline", r#"{"timeline_mid": "m1", "pic": false, "comm": true}"#).
+      await;
+    assert!(!timeline_resp.has_key("redacted"));
+    assert_eq!(timeline_resp["info"][0]["text"], "今天去了北京玩");
+  }
+
+  #[tokio::test]
+  async fn pic_send_w_flaws_are_json() {
+    let pic_flaws = [
+      (Ok(None), StatusCode::NOT_FOUND, "picture not found"),
+      (Err(WeiboError::RbatisError("locked".to_string())), StatusCode::INTERNAL_SERVER_ERROR,
+       "database error"),
+    ];
+    for (hot_timeline_pic, pic_code, pic_des) in pic_flaws {
+      let mut res = Response::new();
+      pic_send_w(&Request::new(), &mut res, hot_timeline_pic).await;
+      assert_eq!(res.status_code, Some(pic_code));
+      assert_eq!(res.headers[CONTENT_TYPE], "application/json; charset=utf-8");
+      let resp_bd = jzon::parse(&res.take_string().await.unwrap()).unwrap();
+      assert_eq!(resp_bd["suc"], false);
+      assert_eq!(resp_bd["info"], pic_des);
+    }
+  }
+}
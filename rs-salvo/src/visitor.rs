@@ -0,0 +1,302 @@
+use jzon::JsonValue;
+use log::info;
+use nyquest::AsyncClient;
+use tokio::sync::Mutex;
+use crate::exceptions::WeiboError;
+use crate::utils::http_era_secs;
+use crate::weibo;
+use crate::weibo_jzon_err;
+
+/// 访客系统返回成功时的retcode
+const VISITOR_RETCODE_SUC: i64 = 20000000;
+
+/// 全局的访客cookie缓存
+pub static WEIBO_VISITOR_CACHE: WeiboVisitorCache = WeiboVisitorCache::new();
+
+/// 通过访客系统获取的访客cookie
+#[derive(Clone, Debug)]
+pub struct WeiboVisitor {
+  // 请求时携带的cookie，形如`SUB=...; SUBP=...`
+  pub cok: String,
+  // 过期的时间戳（秒）
+  pub expire_secs: i64,
+}
+
+/// 访客cookie缓存，过期前`refresh_secs`秒内视为需要刷新。
+/// 刷新时持有锁，并发请求只会触发一次握手。
+pub struct WeiboVisitorCache {
+  visitor: Mutex<Option<WeiboVisitor>>,
+}
+
+impl WeiboVisitorCache {
+  pub const fn new() -> Self {
+    Self {
+      visitor: Mutex::const_new(None),
+    }
+  }
+
+  /// 获取缓存的访客cookie，没有缓存或即将过期时重新握手
+  ///
+  /// ## 参数
+  /// - `weibo_clt`：nyquest异步HTTP客户端
+  /// - `visitor_url`：访客系统的基础URL，以`/`结尾
+  /// - `nub_secs`：当前的时间戳（秒）
+  /// - `refresh_secs`：提前刷新的秒数
+  /// - `ttl_secs`：响应中没有过期时间时，访客cookie的有效秒数
+  pub async fn visitor_cok(&self, weibo_clt: &AsyncClient, visitor_url: &str, nub_secs: i64,
+                           refresh_secs: i64, ttl_secs: i64) -> Result<String, WeiboError> {
+    let mut visitor = self.visitor.lock().await;
+    if let Some(weibo_visitor) = visitor.as_ref()
+      && weibo_visitor.expire_secs - refresh_secs > nub_secs {
+      return Ok(weibo_visitor.cok.clone());
+    }
+
+    let weibo_visitor = visitor_handshake(weibo_clt, visitor_url, nub_secs, ttl_secs).await?;
+    info!("visitor cookie refreshed, expires at {}", weibo_visitor.expire_secs);
+    let visitor_cok = weibo_visitor.cok.clone();
+    *visitor = Some(weibo_visitor);
+    Ok(visitor_cok)
+  }
+
+  /// 丢弃缓存的访客cookie，下次获取时重新握手。用于访客cookie被判定为失效时。
+  pub async fn visitor_expire(&self) {
+    *self.visitor.lock().await = None;
+  }
+}
+
+/// 执行访客系统的genvisitor与incarnate握手，获取新的访客cookie
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `visitor_url`：访客系统的基础URL，以`/`结尾
+/// - `nub_secs`：当前的时间戳（秒）
+/// - `ttl_secs`：响应中没有过期时间时，访客cookie的有效秒数
+pub async fn visitor_handshake(weibo_clt: &AsyncClient, visitor_url: &str, nub_secs: i64,
+                               ttl_secs: i64) -> Result<WeiboVisitor, WeiboError> {
+  let genvisitor_talk = weibo::gain_visitor_genvisitor(weibo_clt, visitor_url).await?;
+  let genvisitor_data = anly_visitor_jsonp(&genvisitor_talk, "/visitor/genvisitor")?;
+  let tid = genvisitor_data["tid"].as_str().
+    filter(|val| !val.is_empty()).
+    ok_or_else(|| weibo_jzon_err!("/visitor/genvisitor no field tid"))?;
+  let w = if genvisitor_data["new_tid"].as_bool().unwrap_or(false) { 3 } else { 2 };
+  let c = format!("{:03}", genvisitor_data["confidence"].as_u32().unwrap_or(100));
+
+  let (incarnate_talk, incarnate_set_coks) =
+    weibo::gain_visitor_incarnate(weibo_clt, visitor_url, tid, w, &c).await?;
+  let incarnate_data = anly_visitor_jsonp(&incarnate_talk, "/visitor/visitor")?;
+
+  // sub与subp优先取自返回内容，没有时取自Set-Cookie
+  let sub = incarnate_data["sub"].as_str().map(String::from).
+    or_else(|| anly_set_cok_val(&incarnate_set_coks, "SUB")).
+    filter(|val| !val.is_empty()).
+    ok_or_else(|| weibo_jzon_err!("/visitor/visitor no field sub"))?;
+  let subp = incarnate_data["subp"].as_str().map(String::from).
+    or_else(|| anly_set_cok_val(&incarnate_set_coks, "SUBP")).
+    unwrap_or_default();
+  let visitor_cok = if subp.is_empty() {
+    format!("SUB={}", sub)
+  } else {
+    format!("SUB={}; SUBP={}", sub, subp)
+  };
+
+  let expire_secs = anly_set_cok_expire(&incarnate_set_coks, "SUB", nub_secs).
+    unwrap_or(nub_secs + ttl_secs);
+  Ok(WeiboVisitor {
+    cok: visitor_cok,
+    expire_secs,
+  })
+}
+
+/// 解析访客系统返回的JSONP，检查retcode并返回其中的data
+///
+/// ## 参数
+/// - `visitor_talk`：形如`window.cb && cb({...});`的返回内容
+/// - `visitor_des`：用于错误信息的接口名称
+fn anly_visitor_jsonp(visitor_talk: &str, visitor_des: &str) -> Result<JsonValue, WeiboError> {
+  let (Some(jsonp_bgn), Some(jsonp_end)) = (visitor_talk.find('('), visitor_talk.rfind(')')) else {
+    return Err(weibo_jzon_err!(format!("{} is not jsonp", visitor_des)));
+  };
+  if jsonp_bgn >= jsonp_end {
+    return Err(weibo_jzon_err!(format!("{} is not jsonp", visitor_des)));
+  }
+  let mut visitor_jquin = jzon::parse(&visitor_talk[jsonp_bgn + 1..jsonp_end])?;
+  let visitor_retcode = visitor_jquin["retcode"].as_i64().unwrap_or(0);
+  if visitor_retcode != VISITOR_RETCODE_SUC {
+    return Err(weibo_jzon_err!(format!("{} retcode is {}, {}", visitor_des, visitor_retcode,
+                                       visitor_jquin["msg"].as_str().unwrap_or(""))));
+  }
+  Ok(visitor_jquin["data"].take())
+}
+
+/// 从Set-Cookie中取出指定cookie的值
+///
+/// ## 参数
+/// - `set_coks`：响应中的Set-Cookie
+/// - `cok_name`：cookie名称
+fn anly_set_cok_val(set_coks: &[String], cok_name: &str) -> Option<String> {
+  set_coks.iter().find_map(|set_cok| {
+    let (set_cok_name, set_cok_val) = set_cok.split(';').next()?.split_once('=')?;
+    (set_cok_name.trim() == cok_name).then(|| set_cok_val.trim().to_string())
+  })
+}
+
+/// 从Set-Cookie中取出指定cookie的过期时间戳，支持Max-Age与Expires
+///
+/// ## 参数
+/// - `set_coks`：响应中的Set-Cookie
+/// - `cok_name`：cookie名称
+/// - `nub_secs`：当前的时间戳（秒），用于Max-Age
+fn anly_set_cok_expire(set_coks: &[String], cok_name: &str, nub_secs: i64) -> Option<i64> {
+  let set_cok = set_coks.iter().find(|set_cok| {
+    set_cok.split(';').next().and_then(|set_cok_pair| set_cok_pair.split_once('=')).
+      is_some_and(|(set_cok_name, _)| set_cok_name.trim() == cok_name)
+  })?;
+
+  let mut expire_secs = None;
+  for set_cok_attr in set_cok.split(';').skip(1) {
+    let Some((attr_name, attr_val)) = set_cok_attr.split_once('=') else {
+      continue;
+    };
+    match attr_name.trim().to_ascii_lowercase().as_str() {
+      // Max-Age优先于Expires
+      "max-age" => return attr_val.trim().parse::<i64>().ok().map(|val| nub_secs + val),
+      "expires" => expire_secs = http_era_secs(attr_val.trim()),
+      _ => {}
+    }
+  }
+  expire_secs
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::BufRead;
+  use std::io::BufReader;
+  use std::io::Write;
+  use std::net::TcpListener;
+  use std::sync::Arc;
+  use std::sync::Once;
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering;
+  use nyquest::ClientBuilder;
+  use crate::utils::era_secs;
+  use super::*;
+
+  /// 模拟访客系统的本地HTTP服务，返回服务的基础URL与已处理的握手次数
+  fn visitor_stand_in(genvisitor_retcode: i64) -> (String, Arc<AtomicUsize>) {
+    let stand_in_accept = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stand_in_addr = stand_in_accept.local_addr().unwrap();
+    let incarnate_cnt = Arc::new(AtomicUsize::new(0));
+    let incarnate_cnt_thd = incarnate_cnt.clone();
+
+    std::thread::spawn(move || {
+      for stand_in_conn in stand_in_accept.incoming() {
+        let mut stand_in_conn = stand_in_conn.unwrap();
+        let mut stand_in_reader = BufReader::new(stand_in_conn.try_clone().unwrap());
+        let mut req_line = String::new();
+        stand_in_reader.read_line(&mut req_line).unwrap();
+        // 读完请求头与表单
+        let mut req_ctn_len = 0;
+        loop {
+          let mut req_hdr = String::new();
+          stand_in_reader.read_line(&mut req_hdr).unwrap();
+          if req_hdr.trim().is_empty() {
+            break;
+          }
+          if let Some((hdr_name, hdr_val)) = req_hdr.split_once(':')
+            && hdr_name.eq_ignore_ascii_case("content-length") {
+            req_ctn_len = hdr_val.trim().parse().unwrap();
+          }
+        }
+        let mut req_bd = vec![0; req_ctn_len];
+        std::io::Read::read_exact(&mut stand_in_reader, &mut req_bd).unwrap();
+
+        let (resp_bd, resp_set_cok) = if req_line.starts_with("POST /visitor/genvisitor ") {
+          assert!(String::from_utf8(req_bd).unwrap().contains("cb=gen_callback"));
+          (format!("window.gen_callback && gen_callback({{\"retcode\":{},\"msg\":\"succ\",\
+                    \"data\":{{\"tid\":\"a+b/c=\",\"new_tid\":true,\"confidence\":95}}}});",
+                   genvisitor_retcode), String::new())
+        } else if req_line.starts_with("GET /visitor/visitor?a=incarnate&t=a%2Bb%2Fc%3D&w=3&c=095") {
+          let cnt = incarnate_cnt_thd.fetch_add(1, Ordering::SeqCst) + 1;
+          (format!("window.cross_domain && cross_domain({{\"retcode\":20000000,\"msg\":\"succ\",\
+                    \"data\":{{\"sub\":\"sub{}\",\"subp\":\"subp{}\"}}}});", cnt, cnt),
+           "Set-Cookie: SUB=sub; expires=Thu, 15-Oct-2026 09:00:00 GMT; path=/; \
+            domain=.weibo.com; httponly\r\n".to_string())
+        } else {
+          panic!("unexpected request: {}", req_line);
+        };
+        write!(stand_in_conn, "HTTP/1.1 200 OK\r\nContent-Type: application/javascript\r\n\
+                               {}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+               resp_set_cok, resp_bd.len(), resp_bd).unwrap();
+      }
+    });
+    (format!("http://{}/visitor/", stand_in_addr), incarnate_cnt)
+  }
+
+  async fn visitor_clt() -> AsyncClient {
+    // nyquest的后端只能注册一次
+    static NYQUEST_REGISTER: Once = Once::new();
+    NYQUEST_REGISTER.call_once(|| {
+      crate::tls::tls_register(&crate::prefs::WeiboPrefs::default()).unwrap();
+    });
+    ClientBuilder::default().no_redirects().build_async().await.unwrap()
+  }
+
+  #[tokio::test]
+  async fn visitor_handshake_against_stand_in() {
+    let (visitor_url, _) = visitor_stand_in(VISITOR_RETCODE_SUC);
+    let weibo_clt = visitor_clt().await;
+
+    let weibo_visitor = visitor_handshake(&weibo_clt, &visitor_url, 0, 60).await.unwrap();
+    assert_eq!(weibo_visitor.cok, "SUB=sub1; SUBP=subp1");
+    assert_eq!(Some(weibo_visitor.expire_secs), era_secs("2026-10-15 09:00:00"));
+  }
+
+  #[tokio::test]
+  async fn visitor_cache_refreshes_before_expiry() {
+    let (visitor_url, incarnate_cnt) = visitor_stand_in(VISITOR_RETCODE_SUC);
+    let weibo_clt = visitor_clt().await;
+    let visitor_cache = WeiboVisitorCache::new();
+    let expire_secs = era_secs("2026-10-15 09:00:00").unwrap();
+
+    let visitor_cok = visitor_cache.visitor_cok(
+      &weibo_clt, &visitor_url, expire_secs - 7200, 600, 60).await.unwrap();
+    assert_eq!(visitor_cok, "SUB=sub1; SUBP=subp1");
+    // 距离过期还很久，使用缓存
+    let visitor_cok = visitor_cache.visitor_cok(
+      &weibo_clt, &visitor_url, expire_secs - 3600, 600, 60).await.unwrap();
+    assert_eq!(visitor_cok, "SUB=sub1; SUBP=subp1");
+    assert_eq!(incarnate_cnt.load(Ordering::SeqCst), 1);
+    // 进入提前刷新的窗口，重新握手
+    let visitor_cok = visitor_cache.visitor_cok(
+      &weibo_clt, &visitor_url, expire_secs - 300, 600, 60).await.unwrap();
+    assert_eq!(visitor_cok, "SUB=sub2; SUBP=subp2");
+    // 被判定为失效后，重新握手
+    visitor_cache.visitor_expire().await;
+    let visitor_cok = visitor_cache.visitor_cok(
+      &weibo_clt, &visitor_url, expire_secs - 7200, 600, 60).await.unwrap();
+    assert_eq!(visitor_cok, "SUB=sub3; SUBP=subp3");
+    assert_eq!(incarnate_cnt.load(Ordering::SeqCst), 3);
+  }
+
+  #[tokio::test]
+  async fn visitor_handshake_rejects_bad_retcode() {
+    let (visitor_url, incarnate_cnt) = visitor_stand_in(50010002);
+    let weibo_clt = visitor_clt().await;
+
+    let visitor_flaw = visitor_handshake(&weibo_clt, &visitor_url, 0, 60).await.unwrap_err();
+    assert!(visitor_flaw.to_string().contains("retcode is 50010002"));
+    assert_eq!(incarnate_cnt.load(Ordering::SeqCst), 0);
+  }
+
+  #[test]
+  fn anly_set_cok_expire_prefers_max_age() {
+    let set_coks = vec![
+      "SUBP=x; expires=Thu, 15-Oct-2026 09:00:00 GMT".to_string(),
+      "SUB=y; Max-Age=3600; expires=Thu, 15 Oct 2026 09:00:00 GMT".to_string(),
+    ];
+    assert_eq!(anly_set_cok_expire(&set_coks, "SUB", 100), Some(3700));
+    assert_eq!(anly_set_cok_expire(&set_coks, "SUBP", 100), era_secs("2026-10-15 09:00:00"));
+    assert_eq!(anly_set_cok_val(&set_coks, "SUB"), Some("y".to_string()));
+    assert_eq!(anly_set_cok_expire(&set_coks, "ALF", 100), None);
+  }
+}
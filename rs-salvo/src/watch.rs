@@ -0,0 +1,155 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use log::info;
+use log::warn;
+use rbatis::RBatis;
+use tokio::sync::RwLock;
+use crate::dbs::WeiboKeyword;
+use crate::exceptions::WeiboError;
+use crate::fold::WmFold;
+use crate::prefs::prefs;
+use crate::wm::CharWuManber;
+use crate::wm::WmOverlap;
+use crate::wm::WmSite;
+
+/// 全局的关键词监控匹配器，关键词变化时重新编译
+pub static WEIBO_WATCH_CACHE: WeiboWatchCache = WeiboWatchCache::new();
+
+/// 关键词命中的文本类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WeiboHitIlk {
+  // 热搜标题
+  HotSearch,
+  // 热门推荐正文
+  Timeline,
+  // 热门推荐评论
+  Comm,
+}
+
+impl WeiboHitIlk {
+  /// 数据库中存储的类型名称
+  pub fn hit_ilk(&self) -> &'static str {
+    match self {
+      WeiboHitIlk::HotSearch => "hot_search",
+      WeiboHitIlk::Timeline => "timeline",
+      WeiboHitIlk::Comm => "comm",
+    }
+  }
+
+  /// 由请求中的类型名称解析
+  pub fn anly_hit_ilk(hit_ilk: &str) -> Option<Self> {
+    match hit_ilk {
+      "hot_search" => Some(WeiboHitIlk::HotSearch),
+      "timeline" => Some(WeiboHitIlk::Timeline),
+      "comm" => Some(WeiboHitIlk::Comm),
+      _ => None,
+    }
+  }
+}
+
+/// 由全部关键词编译的匹配器，关键词按字符匹配，按`keyword_fold_*`配置规范化
+#[derive(Debug)]
+pub struct WeiboWatch {
+  matcher: CharWuManber,
+}
+
+impl WeiboWatch {
+  /// 查找文本中命中的关键词
+  ///
+  /// ## 参数
+  /// - `text`：热搜标题、热门推荐或评论的内容
+  ///
+  /// ## 返回
+  /// 命中的关键词与其在文本中的位置，按出现位置排列
+  pub fn watch_search(&self, text: &str) -> Vec<(String, WmSite)> {
+    let mut watch_hits: Vec<(String, WmSite)> = self.matcher.search(text).
+      into_iter().
+      flat_map(|(keyword, hit_sites)| {
+        hit_sites.into_iter().map(move |hit_site| (keyword.clone(), hit_site))
+      }).
+      collect();
+    // 相同的位置只可能是同一个关键词
+    watch_hits.sort_by_key(|watch_hit| watch_hit.1);
+    watch_hits
+  }
+
+  /// 将文本中命中的关键词替换为`redact_repl`，重叠命中按`redact_overlap`取舍
+  ///
+  /// ## 参数
+  /// - `text`：热门推荐或评论的内容
+  ///
+  /// ## 返回
+  /// 替换后的内容
+  pub fn watch_redact(&self, text: &str) -> String {
+    let redact_overlap = WmOverlap::anly_overlap(&prefs().redact_overlap).unwrap_or_default();
+    self.matcher.replace(text, redact_overlap, |_, _| prefs().redact_repl.clone())
+  }
+}
+
+/// 关键词监控匹配器的缓存。编译时持有写锁，编译完成前的匹配等待新的匹配器
+pub struct WeiboWatchCache {
+  watch: RwLock<Option<Arc<WeiboWatch>>>,
+}
+
+impl WeiboWatchCache {
+  pub const fn new() -> Self {
+    Self {
+      watch: RwLock::const_new(None),
+    }
+  }
+
+  /// 读取数据库中全部列表的关键词，重新编译匹配器。启动时与关键词增删后调用
+  ///
+  /// ## 参数
+  /// - `weibo_db_rb_conn`：rbatis数据库连接
+  ///
+  /// ## 返回
+  /// 去重后的关键词数量，没有关键词时不做匹配
+  pub async fn watch_compile(&self, weibo_db_rb_conn: &RBatis) -> Result<usize, WeiboError> {
+    let mut watch = self.watch.write().await;
+    let keyword_fold = keyword_fold();
+    // 同一关键词可能属于多个列表，只编译一次
+    let keyword_sets: BTreeSet<String> = WeiboKeyword::weibo_keyword_r(weibo_db_rb_conn, None).
+      await?.
+      into_iter().
+      map(|weibo_keyword| weibo_keyword.keyword).
+      filter(|keyword| {
+        // 规范化配置变化后，原有的关键词可能只剩下被删除的字符
+        let keyword_empty = keyword_fold.fold_text(keyword).chars.is_empty();
+        if keyword_empty {
+          warn!("keyword {:?} is empty after folding, skipped", keyword);
+        }
+        !keyword_empty
+      }).
+      collect();
+    let keyword_arrs: Vec<&str> = keyword_sets.iter().map(String::as_str).collect();
+
+    *watch = if keyword_arrs.is_empty() {
+      None
+    } else {
+      let matcher = CharWuManber::new_fold(&keyword_arrs, None, keyword_fold).
+        map_err(|flaw| WeiboError::SalvoError(flaw.to_string()))?;
+      Some(Arc::new(WeiboWatch { matcher }))
+    };
+    info!("keyword watch compiled with {} keywords", keyword_arrs.len());
+    Ok(keyword_arrs.len())
+  }
+
+  /// 获取当前的匹配器，一次爬取中的全部文本使用同一个匹配器
+  ///
+  /// ## 返回
+  /// 没有关键词时返回None
+  pub async fn watch(&self) -> Option<Arc<WeiboWatch>> {
+    self.watch.read().await.clone()
+  }
+}
+
+/// 关键词与原文的规范化选项，来自`keyword_fold_*`配置
+pub fn keyword_fold() -> WmFold {
+  WmFold {
+    case: prefs().keyword_fold_case,
+    width: prefs().keyword_fold_width,
+    trad: prefs().keyword_fold_trad,
+    strip: prefs().keyword_fold_strip,
+  }
+}
@@ -1,19 +1,31 @@
+use hifitime::prelude::Epoch;
+use jzon::JsonValue;
+use log::warn;
 use nyquest::AsyncClient;
+use nyquest::body_form;
 use nyquest::r#async::Request;
 use nyquest::r#async::Response;
+use percent_encoding::NON_ALPHANUMERIC;
+use percent_encoding::utf8_percent_encode;
+use rbatis::RBatis;
+use crate::dbs::WeiboCok;
 use crate::exceptions::WeiboError;
-use crate::prefs::WEIBO_COK;
+use crate::gain::gain_reap;
+use crate::prefs::prefs;
+use crate::utils::secs_era;
+use crate::visitor::WEIBO_VISITOR_CACHE;
 
 /// 访问链接获取微博热搜
 pub async fn gain_side_hotsearch(weibo_clt: &AsyncClient) -> Result<String, WeiboError> {
-  let gain_info = Request::get("side/hotSearch");
   // let reap = weibo_clt.request(gain_info).await;
   // if let Err(reap_flaw) = reap {
   //   eprintln!("nyquest error: {:?}", reap_flaw);
   //   return Err(NyquestError::new(format!("nyquest debug flaw info: {}", reap_flaw)));
   // }
   // let reap = reap?;
-  let reap: Response = weibo_clt.request(gain_info).await?;
+  let reap: Response = gain_reap(weibo_clt, "/ajax/side/hotSearch", || {
+    Request::get("side/hotSearch")
+  }).await?;
   if !reap.status().is_successful() {
     return Err(WeiboError::NyquestError(format!("/ajax/side/hotSearch status code is {}",
                                                 reap.status().code())));
@@ -23,24 +35,37 @@ pub async fn gain_side_hotsearch(weibo_clt: &AsyncClient) -> Result<String, Weib
 }
 
 /// 访问链接获取微博热门推荐
-pub async fn gain_feed_hottimeline(weibo_clt: &AsyncClient) -> Result<String, WeiboError> {
-  let gain_info = Request::get("feed/hottimeline?\
-                                since_id=0&refresh=0&group_id=102803&containerid=102803&\
-                                extparam=discover|new_feed&max_id=0&count=10")
-    .with_header("cookie", WEIBO_COK);
-  let reap: Response = weibo_clt.request(gain_info).await?;
-  if !reap.status().is_successful() {
-    return Err(WeiboError::NyquestError(format!("/ajax/feed/hottimeline status code is {}",
-                                                reap.status().code())));
-  }
-  let reap_talks = reap.text().await?;
-  Ok(reap_talks)
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接，用于从cookie池选取cookie
+/// - `max_id`：分页游标，第一页为0，之后为上一页返回的max_id
+/// - `count`：每页的数量
+pub async fn gain_feed_hottimeline(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, max_id: &str, count: u32,
+) -> Result<String, WeiboError> {
+  let gain_pth = format!("feed/hottimeline?\
+                          since_id=0&refresh=0&group_id=102803&containerid=102803&\
+                          extparam=discover|new_feed&max_id={max_id}&count={count}");
+  gain_cok_talk(weibo_clt, weibo_db_rb_conn, &gain_pth, "/ajax/feed/hottimeline").await
+}
+
+/// 访问链接获取微博长微博的全文
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接，用于从cookie池选取cookie
+/// - `mblogid`：长微博的mblogid
+pub async fn gain_statuses_longtext(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, mblogid: &str,
+) -> Result<String, WeiboError> {
+  let gain_pth = format!("statuses/longtext?id={mblogid}");
+  gain_cok_talk(weibo_clt, weibo_db_rb_conn, &gain_pth, "/ajax/statuses/longtext").await
 }
 
 /// 访问链接获取微博图片
 pub async fn gain_sinaimg(weibo_clt: &AsyncClient, pic_url: &str) -> Result<Vec<u8>, WeiboError> {
-  let gain_info = Request::get(pic_url.to_string());
-  let reap: Response = weibo_clt.request(gain_info).await?;
+  let reap: Response = gain_reap(weibo_clt, "sinaimg", || {
+    Request::get(pic_url.to_string())
+  }).await?;
   if !reap.status().is_successful() {
     return Err(WeiboError::NyquestError(format!("{} status code is {}",
                                                 pic_url, reap.status().code())));
@@ -49,18 +74,183 @@ pub async fn gain_sinaimg(weibo_clt: &AsyncClient, pic_url: &str) -> Result<Vec<
   Ok(reap_byt)
 }
 
+/// 访问链接获取微博热门推荐的评论
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接，用于从cookie池选取cookie
+/// - `mid`：热门推荐的mid
+/// - `uid`：热门推荐的用户id
+/// - `max_id`：分页游标，第一页为0，之后为上一页返回的max_id
 pub async fn gain_status_build_comments(
-  weibo_clt: &AsyncClient, mid: &str, uid: &str) -> Result<String, WeiboError> {
-  let gain_info = Request::get(format!("statuses/buildComments?\
-                                is_reload=1&id={mid}&is_show_bulletin=2&\
-                                is_mix=0&count=20&type=feed&uid={uid}&\
-                                fetch_level=0&locale=zh-CN"))
-    .with_header("cookie", WEIBO_COK);
-  let reap: Response = weibo_clt.request(gain_info).await?;
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, mid: &str, uid: &str, max_id: &str,
+) -> Result<String, WeiboError> {
+  let max_id_qry = if max_id == "0" { String::new() } else { format!("&flow=0&max_id={max_id}") };
+  let gain_pth = format!("statuses/buildComments?\
+                          is_reload=1&id={mid}&is_show_bulletin=2&\
+                          is_mix=0&count=20&type=feed&uid={uid}&\
+                          fetch_level=0&locale=zh-CN{max_id_qry}");
+  gain_cok_talk(weibo_clt, weibo_db_rb_conn, &gain_pth, "/ajax/statuses/buildComments").await
+}
+
+/// 访问链接获取微博评论的楼中楼回复
+///
+/// ## 参数
+/// - `weibo_db_rb_conn`：rbatis数据库连接，用于从cookie池选取cookie
+/// - `comm_id`：根评论的id
+/// - `uid`：热门推荐的用户id
+/// - `max_id`：分页游标，第一页为0，之后为上一页返回的max_id
+pub async fn gain_status_build_comment_replies(
+  weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, comm_id: &str, uid: &str, max_id: &str,
+) -> Result<String, WeiboError> {
+  let gain_pth = format!("statuses/buildComments?\
+                          is_reload=1&id={comm_id}&is_show_bulletin=2&\
+                          is_mix=1&fetch_level=1&max_id={max_id}&count=20&uid={uid}&\
+                          locale=zh-CN");
+  gain_cok_talk(weibo_clt, weibo_db_rb_conn, &gain_pth, "/ajax/statuses/buildComments").await
+}
+
+/// 访问微博访客系统生成访客tid（genvisitor）
+///
+/// ## 参数
+/// - `visitor_url`：访客系统的基础URL，以`/`结尾
+///
+/// ## 返回
+/// JSONP格式的`gen_callback({...})`
+pub async fn gain_visitor_genvisitor(
+  weibo_clt: &AsyncClient, visitor_url: &str) -> Result<String, WeiboError> {
+  let reap: Response = gain_reap(weibo_clt, "/visitor/genvisitor", || {
+    Request::post(format!("{visitor_url}genvisitor")).with_body(body_form! {
+      "cb" => "gen_callback",
+      "fp" => "{\"os\":\"1\",\"browser\":\"Chrome130,0,0,0\",\"fonts\":\"undefined\",\
+               \"screenInfo\":\"1920*1080*24\",\"plugins\":\"\"}",
+    })
+  }).await?;
   if !reap.status().is_successful() {
-    return Err(WeiboError::NyquestError(format!("/ajax/statuses/buildComments status code is {}",
+    return Err(WeiboError::NyquestError(format!("/visitor/genvisitor status code is {}",
                                                 reap.status().code())));
   }
   let reap_talks = reap.text().await?;
   Ok(reap_talks)
 }
+
+/// 访问微博访客系统，以访客tid换取访客cookie（incarnate）
+///
+/// ## 参数
+/// - `visitor_url`：访客系统的基础URL，以`/`结尾
+/// - `tid`：genvisitor返回的访客tid
+/// - `w`：新生成的tid为3，否则为2
+/// - `c`：genvisitor返回的confidence，补齐为3位
+///
+/// ## 返回
+/// JSONP格式的`cross_domain({...})`，以及响应中的Set-Cookie
+pub async fn gain_visitor_incarnate(
+  weibo_clt: &AsyncClient, visitor_url: &str, tid: &str, w: u8, c: &str,
+) -> Result<(String, Vec<String>), WeiboError> {
+  let tid = utf8_percent_encode(tid, NON_ALPHANUMERIC);
+  let rand_val: f64 = rand::random();
+  let reap: Response = gain_reap(weibo_clt, "/visitor/visitor", || {
+    Request::get(format!("{visitor_url}visitor?\
+                          a=incarnate&t={tid}&w={w}&c={c}&gc=&\
+                          cb=cross_domain&from=weibo&_rand={rand_val}"))
+  }).await?;
+  if !reap.status().is_successful() {
+    return Err(WeiboError::NyquestError(format!("/visitor/visitor status code is {}",
+                                                reap.status().code())));
+  }
+  let reap_set_coks = reap.get_header("set-cookie").unwrap_or_default();
+  let reap_talks = reap.text().await?;
+  Ok((reap_talks, reap_set_coks))
+}
+
+/// 请求时携带的cookie的来源
+enum WeiboCokSrc {
+  // cookie池中的cookie，记录其id
+  Pool(usize),
+  // 配置中的cok
+  Prefs,
+  // 访客系统获取的访客cookie
+  Visitor,
+}
+
+/// 携带cookie访问链接。cookie依次取自cookie池、配置中的cok，都没有时通过访客系统获取访客cookie。
+///
+/// 请求被重定向（通常是跳转到登录或访客验证页）、被拒绝（401、403、418），或返回`ok`为-100
+/// 的JSON时，判定cookie失效或被封禁：池中的cookie会被隔离`cok_quarantine_secs`秒，
+/// 访客cookie会被丢弃并重新获取，之后换用下一个cookie重试，最多尝试`cok_attempt_max`次。
+///
+/// ## 参数
+/// - `weibo_clt`：nyquest异步HTTP客户端
+/// - `weibo_db_rb_conn`：rbatis数据库连接
+/// - `gain_pth`：相对于base_url的链接
+/// - `gain_des`：用于错误信息的接口名称
+async fn gain_cok_talk(weibo_clt: &AsyncClient, weibo_db_rb_conn: &RBatis, gain_pth: &str,
+                       gain_des: &str) -> Result<String, WeiboError> {
+  let mut cok_flaw = String::new();
+  for _ in 0..prefs().cok_attempt_max {
+    let nub_secs = Epoch::now()?.to_unix_seconds() as i64;
+    let weibo_cok = WeiboCok::weibo_cok_pick(weibo_db_rb_conn, &secs_era(nub_secs)).await?;
+    let weibo_cok = weibo_cok.and_then(|weibo_cok| Some((weibo_cok.id?, weibo_cok.cok)));
+    let (cok, cok_src) = match weibo_cok {
+      Some((cok_id, cok)) => (cok, WeiboCokSrc::Pool(cok_id)),
+      None if !prefs().cok.is_empty() || !prefs().visitor => {
+        (prefs().cok.clone(), WeiboCokSrc::Prefs)
+      }
+      None => {
+        let cok = WEIBO_VISITOR_CACHE.visitor_cok(
+          weibo_clt, &prefs().visitor_url, nub_secs, prefs().visitor_refresh_secs as i64,
+          prefs().visitor_ttl_secs as i64).await?;
+        (cok, WeiboCokSrc::Visitor)
+      }
+    };
+
+    let reap: Response = gain_reap(weibo_clt, gain_des, || {
+      Request::get(gain_pth.to_string()).with_header("cookie", cok.clone())
+    }).await?;
+    let reap_code = reap.status().code();
+    cok_flaw = if (300..400).contains(&reap_code) {
+      let reap_loc = reap.get_header("location").ok().
+        and_then(|reap_locs| reap_locs.into_iter().next()).
+        unwrap_or_default();
+      format!("{} redirected ({}) to {}", gain_des, reap_code, reap_loc)
+    } else if [401, 403, 418].contains(&reap_code) {
+      format!("{} rejected with status code {}", gain_des, reap_code)
+    } else if !reap.status().is_successful() {
+      return Err(WeiboError::NyquestError(format!("{} status code is {}",
+                                                  gain_des, reap_code)));
+    } else {
+      let reap_talks = reap.text().await?;
+      if !anly_cok_expired(&reap_talks) {
+        if let WeiboCokSrc::Pool(cok_id) = cok_src {
+          WeiboCok::weibo_cok_suc_u(weibo_db_rb_conn, cok_id).await?;
+        }
+        return Ok(reap_talks);
+      }
+      format!("{} answered ok=-100", gain_des)
+    };
+
+    match cok_src {
+      WeiboCokSrc::Pool(cok_id) => {
+        warn!("cookie {} quarantined: {}", cok_id, cok_flaw);
+        let until_era = secs_era(nub_secs + prefs().cok_quarantine_secs as i64);
+        WeiboCok::weibo_cok_flaw_u(weibo_db_rb_conn, cok_id, &until_era, &cok_flaw).await?;
+      }
+      WeiboCokSrc::Visitor => {
+        warn!("visitor cookie dropped: {}", cok_flaw);
+        WEIBO_VISITOR_CACHE.visitor_expire().await;
+      }
+      // 配置中的cookie无法隔离，也没有其他cookie可换，直接返回错误
+      WeiboCokSrc::Prefs => break,
+    }
+  }
+  Err(WeiboError::NyquestError(format!("cookie expired or blocked: {}", cok_flaw)))
+}
+
+/// 判断接口返回的JSON是否表示cookie失效，即`{"ok": -100, "url": "https://passport...."}`
+///
+/// ## 参数
+/// - `reap_talks`：接口返回的内容
+fn anly_cok_expired(reap_talks: &str) -> bool {
+  jzon::parse(reap_talks).
+    map(|reap_jquin: JsonValue| reap_jquin["ok"].as_i32() == Some(-100)).
+    unwrap_or(false)
+}
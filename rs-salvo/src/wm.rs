@@ -1,29 +1,84 @@
-#![allow(dead_code)]
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use crate::fold::WmFold;
+
+/// 以(后缀块, 前缀块)为键的候选模式表
+type HashPrefix<T> = HashMap<(Vec<T>, Vec<T>), Vec<Vec<T>>>;
+
+/// 匹配器的字母表元素，字节或字符
+pub trait WmSym: Clone + Eq + Hash {}
+
+impl<T: Clone + Eq + Hash> WmSym for T {}
+
+/// 构造匹配器时的参数错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WmError {
+  // 没有模式
+  EmptyPats,
+  // 第几个模式为空
+  EmptyPat(usize),
+  // 模式规范化后为空
+  FoldedEmpty(String),
+  // 块的大小为0或大于最短模式的长度
+  BlkSize { blk_size: usize, pat_min: usize },
+}
+
+impl fmt::Display for WmError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WmError::EmptyPats => write!(f, "At least one pattern is required."),
+      WmError::EmptyPat(pat_idx) => write!(f, "Pattern #{} is empty.", pat_idx),
+      WmError::FoldedEmpty(pat) => write!(f, "Pattern {:?} is empty after folding.", pat),
+      WmError::BlkSize { blk_size, pat_min } => write!(
+        f, "Block size {} must be between 1 and the shortest pattern length {}.",
+        blk_size, pat_min),
+    }
+  }
+}
+
+impl Error for WmError {}
 
 
+/// Wu-Manber多模式匹配器，默认以字节为单位匹配
 #[derive(Debug)]
-pub struct WuManber {
-  pats: Vec<Vec<u8>>,
+pub struct WuManber<T: WmSym = u8> {
+  pats: Vec<Vec<T>>,
   m: usize,
   b: usize,
   other: usize,
-  shift: HashMap<Vec<u8>, usize>,
-  hash_prefix: HashMap<(Vec<u8>, Vec<u8>), Vec<Vec<u8>>>,
+  shift: HashMap<Vec<T>, usize>,
+  hash_prefix: HashPrefix<T>,
 }
 
 
-impl WuManber {
-  pub fn new<P: AsRef<[u8]>>(pats: &[P], b: Option<usize>) -> Result<Self, String> {
-    let pats: Vec<Vec<u8>> = pats.iter().map(|pati| pati.as_ref().to_vec()).collect();
-    let m = pats.iter().map(|pati| pati.len()).min().unwrap();
-    let b = match b {
-      Some(val) => val,
-      None => m.min(2),
-    };
+impl<T: WmSym> WuManber<T> {
+  /// ## 参数
+  /// - `pats`：模式，不能为空，也不能包含空模式；重复的模式只保留第一个
+  /// - `b`：块的大小，默认为最短模式的长度与2中较小的一个
+  pub fn new<P: AsRef<[T]>>(pats: &[P], b: Option<usize>) -> Result<Self, WmError> {
+    if pats.is_empty() {
+      return Err(WmError::EmptyPats);
+    }
+    let mut pat_sets: HashSet<&[T]> = HashSet::new();
+    let mut pat_arrs: Vec<Vec<T>> = Vec::with_capacity(pats.len());
+    for (pat_idx, pati) in pats.iter().enumerate() {
+      if pati.as_ref().is_empty() {
+        return Err(WmError::EmptyPat(pat_idx));
+      }
+      // 重复的模式只保留第一个，否则同一位置会报告多次
+      if pat_sets.insert(pati.as_ref()) {
+        pat_arrs.push(pati.as_ref().to_vec());
+      }
+    }
+    let pats = pat_arrs;
+    let m = pats.iter().map(Vec::len).min().unwrap_or_default();
+    let b = b.unwrap_or(m.min(2));
 
-    if m < b {
-      return Err("Pattern length must be greater than or equal to block size.".into());
+    if b == 0 || b > m {
+      return Err(WmError::BlkSize { blk_size: b, pat_min: m });
     }
 
     let other = m - b + 1;
@@ -33,19 +88,20 @@ impl WuManber {
     Ok(WuManber { pats, m, b, other, shift, hash_prefix })
   }
 
-  fn build_shift(pats: &[Vec<u8>], m: usize, b: usize, other: usize) -> HashMap<Vec<u8>, usize> {
-    let mut blks: HashSet<Vec<u8>> = HashSet::new();
+  fn build_shift(pats: &[Vec<T>], m: usize, b: usize, other: usize) -> HashMap<Vec<T>, usize> {
+    let mut blks: HashSet<Vec<T>> = HashSet::new();
     for pati in &pats.to_vec() {
       for i in 0..=m - b {
         blks.insert(pati[i..i + b].to_vec());
       }
     }
 
-    let mut shifts: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut shifts: HashMap<Vec<T>, usize> = HashMap::new();
     for blki in blks {
       let mut better = other;
       for pati in &pats.to_vec() {
-        if let Some(idx) = find_subslice_left(&pati[..m], &blki) {
+        // 块在模式中多次出现时，最靠右的出现位置决定最小的安全跳跃距离
+        if let Some(idx) = find_subslice_right(&pati[..m], &blki) {
           better = better.min(m - idx - b);
         }
       }
@@ -55,10 +111,10 @@ impl WuManber {
   }
 
   fn build_hash_prefix(
-    pats: &[Vec<u8>], m: usize, b: usize, shift: &HashMap<Vec<u8>, usize>,
-  ) -> HashMap<(Vec<u8>, Vec<u8>), Vec<Vec<u8>>> {
-    let mut hash_prefix: HashMap<(Vec<u8>, Vec<u8>), Vec<Vec<u8>>> = HashMap::new();
-    let zero_shifts: HashSet<&Vec<u8>> = shift.iter()
+    pats: &[Vec<T>], m: usize, b: usize, shift: &HashMap<Vec<T>, usize>,
+  ) -> HashPrefix<T> {
+    let mut hash_prefix: HashPrefix<T> = HashMap::new();
+    let zero_shifts: HashSet<&Vec<T>> = shift.iter()
       .filter(|(_, shift_v)| **shift_v == 0)
       .map(|(shift_k, _)| shift_k)
       .collect();
@@ -73,32 +129,33 @@ impl WuManber {
     hash_prefix
   }
 
-  pub fn search(&self, text: &[u8]) -> HashMap<Vec<u8>, Vec<(usize, usize)>> {
-    let mut results: HashMap<Vec<u8>, Vec<(usize, usize)>> =
+  #[allow(dead_code)]
+  pub fn search(&self, text: &[T]) -> HashMap<Vec<T>, Vec<(usize, usize)>> {
+    let mut results: HashMap<Vec<T>, Vec<(usize, usize)>> =
       self.pats.iter().cloned().map(|pati| (pati, Vec::new())).collect();
 
+    // 短于最短模式的原文不可能命中，窗口也无法对齐到原文开头
     let textl = text.len();
-    if textl < self.b {
+    if textl < self.m {
       return results;
     }
 
     let mut site: usize = self.m - self.b;
-    while site <= textl - self.b {
-      let now_suffix: &[u8] = &text[site..site + self.b];
+    while site + self.b <= textl {
+      let now_suffix: &[T] = &text[site..site + self.b];
       let mut step: usize = match self.shift.get(now_suffix) {
         Some(&shift_val) => {
           if shift_val == 0 {
             // 原文中对应的模式前缀块的字符子串 起始位置
             let win_start = site - (self.m - self.b);
-            let now_prefix: &[u8] = &text[win_start..win_start + self.b];
+            let now_prefix: &[T] = &text[win_start..win_start + self.b];
             if let Some(inner) = self.hash_prefix.get(&(now_suffix.to_vec(), now_prefix.to_vec())) {
               for now_pat in inner {
                 let result_start = win_start;
                 let result_finish = result_start + now_pat.len();
-                if result_finish <= textl && &text[result_start..result_finish] == now_pat.as_slice() {
-                  results.get_mut(now_pat).map(
-                    |result_val| result_val.push((result_start, result_finish))
-                  );
+                if result_finish <= textl && &text[result_start..result_finish] == now_pat.as_slice()
+                  && let Some(result_val) = results.get_mut(now_pat) {
+                  result_val.push((result_start, result_finish));
                 }
               }
             }
@@ -118,73 +175,92 @@ impl WuManber {
 
     results
   }
+
+  /// 按重叠策略替换原文中的命中
+  ///
+  /// ## 参数
+  /// - `text`：原文
+  /// - `overlap`：重叠命中的取舍策略
+  /// - `repl`：由命中的模式与被替换的原文片段得到替换内容
+  ///
+  /// ## 返回
+  /// 替换后的文本
+  #[allow(dead_code)]
+  pub fn replace<F>(&self, text: &[T], overlap: WmOverlap, repl: F) -> Vec<T>
+  where F: FnMut(&[T], &[T]) -> Vec<T> {
+    wm_replace(text, &self.pats, self.search(text), overlap, repl)
+  }
+
+  /// 按重叠策略将原文中命中的元素逐个替换为遮盖符号，替换后长度不变
+  #[allow(dead_code)]
+  pub fn mask(&self, text: &[T], overlap: WmOverlap, mask_sym: T) -> Vec<T> {
+    self.replace(text, overlap, |_, span| vec![mask_sym.clone(); span.len()])
+  }
 }
 
 
+/// 在Wu-Manber的基础上，命中后按slip表跳跃的DHSWuManber匹配器
 #[derive(Debug)]
-pub struct DHSWuManber {
-  base: WuManber,
-  slip: HashMap<Vec<u8>, usize>,
+pub struct DHSWuManber<T: WmSym = u8> {
+  base: WuManber<T>,
+  slip: HashMap<Vec<T>, usize>,
 }
 
 
-impl DHSWuManber {
-  pub fn new<P: AsRef<[u8]>>(pats: &[P], blk_size: Option<usize>) -> Result<Self, String> {
+impl<T: WmSym> DHSWuManber<T> {
+  pub fn new<P: AsRef<[T]>>(pats: &[P], blk_size: Option<usize>) -> Result<Self, WmError> {
     let base = WuManber::new(pats, blk_size)?;
     let slip = Self::build_slip(&base);
     Ok(DHSWuManber { base, slip })
   }
 
-  fn build_slip(base: &WuManber) -> HashMap<Vec<u8>, usize> {
-    let mut slip: HashMap<Vec<u8>, usize> = HashMap::new();
-    let pats_m: Vec<&[u8]> = base.pats.iter().map(|pati| &pati[..base.m]).collect();
+  fn build_slip(base: &WuManber<T>) -> HashMap<Vec<T>, usize> {
+    let mut slip: HashMap<Vec<T>, usize> = HashMap::new();
+    let pats_m: Vec<&[T]> = base.pats.iter().map(|pati| &pati[..base.m]).collect();
 
     for (shift_k, &shift_v) in &base.shift {
       if shift_v == 0 {
+        // 后缀块在各模式前缀中、末尾位置之前最靠右的出现位置，窗口右移到使其与后缀块对齐；
+        // 没有出现时整体越过后缀块
         let mut slip_val = base.other;
-        let mut better_idx: isize = -1;
         for pati_m in &pats_m {
-          if let Some(idx) = find_subslice_right(pati_m, &shift_k) {
-            if idx != (base.m - base.b) && (idx as isize) > better_idx {
-              better_idx = idx as isize;
-            }
+          if let Some(idx) = find_subslice_right(&pati_m[..base.m - 1], shift_k) {
+            slip_val = slip_val.min(base.m - base.b - idx);
           }
         }
-        if better_idx != -1 {
-          slip_val = base.m - (better_idx as usize);
-        }
         slip.insert(shift_k.clone(), slip_val);
       }
     }
     slip
   }
 
-  pub fn search(&self, text: &[u8]) -> HashMap<Vec<u8>, Vec<(usize, usize)>> {
-    let mut results: HashMap<Vec<u8>, Vec<(usize, usize)>> =
+  pub fn search(&self, text: &[T]) -> HashMap<Vec<T>, Vec<(usize, usize)>> {
+    let mut results: HashMap<Vec<T>, Vec<(usize, usize)>> =
       self.base.pats.iter().cloned().map(|pati| (pati, Vec::new())).collect();
 
+    // 短于最短模式的原文不可能命中，窗口也无法对齐到原文开头
     let textl = text.len();
-    if textl < self.base.b {
+    if textl < self.base.m {
       return results;
     }
 
     let mut site: usize = self.base.m - self.base.b;
-    while site <= textl - self.base.b {
-      let now_suffix: &[u8] = &text[site..site + self.base.b];
+    while site + self.base.b <= textl {
+      let now_suffix: &[T] = &text[site..site + self.base.b];
       let mut step = match self.base.shift.get(now_suffix) {
         Some(&shift_val) => {
           if shift_val == 0 {
             let slip_step: usize = *self.slip.get(now_suffix).unwrap_or(&1);
             // 原文中对应的模式前缀块的字符子串 起始位置
             let win_start = site - (self.base.m - self.base.b);
-            let now_prefix: &[u8] = &text[win_start..win_start + self.base.b];
+            let now_prefix: &[T] = &text[win_start..win_start + self.base.b];
             if let Some(inner) = self.base.hash_prefix.get(&(now_suffix.to_vec(), now_prefix.to_vec())) {
               for now_pat in inner {
                 let result_start = win_start;
                 let result_finish = result_start + now_pat.len();
-                if result_finish <= textl && &text[result_start..result_finish] == now_pat.as_slice() {
-                  results.get_mut(now_pat).map(
-                    |result_val| result_val.push((result_start, result_finish)));
+                if result_finish <= textl && &text[result_start..result_finish] == now_pat.as_slice()
+                  && let Some(result_val) = results.get_mut(now_pat) {
+                  result_val.push((result_start, result_finish));
                 }
               }
             }
@@ -203,37 +279,257 @@ impl DHSWuManber {
     }
     results
   }
+
+  /// 按重叠策略替换原文中的命中，参数与返回同`WuManber::replace`
+  #[allow(dead_code)]
+  pub fn replace<F>(&self, text: &[T], overlap: WmOverlap, repl: F) -> Vec<T>
+  where F: FnMut(&[T], &[T]) -> Vec<T> {
+    wm_replace(text, &self.base.pats, self.search(text), overlap, repl)
+  }
+
+  /// 按重叠策略将原文中命中的元素逐个替换为遮盖符号，替换后长度不变
+  #[allow(dead_code)]
+  pub fn mask(&self, text: &[T], overlap: WmOverlap, mask_sym: T) -> Vec<T> {
+    self.replace(text, overlap, |_, span| vec![mask_sym.clone(); span.len()])
+  }
 }
 
 
-/// 在字节序列中查找目标片段的第一个出现位置
+/// 重叠命中的取舍策略，用于替换与遮盖
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WmOverlap {
+  // 从左到右取起始位置最靠左的命中，起始位置相同时取最长的，丢弃与已取命中重叠的命中
+  #[default]
+  LeftmostLongest,
+  // 从左到右取起始位置最靠左的命中，起始位置相同时取构造时靠前的模式
+  LeftmostFirst,
+  // 全部命中都生效，互相重叠的命中合并为一段，按其中最靠左、最长的命中的模式替换
+  All,
+}
+
+impl WmOverlap {
+  /// 由配置中的策略名称解析
+  pub fn anly_overlap(overlap: &str) -> Option<Self> {
+    match overlap {
+      "leftmost_longest" => Some(WmOverlap::LeftmostLongest),
+      "leftmost_first" => Some(WmOverlap::LeftmostFirst),
+      "all" => Some(WmOverlap::All),
+      _ => None,
+    }
+  }
+}
+
+
+/// 按重叠策略从全部命中中选出互不重叠的替换范围
 ///
 /// ## 参数
-/// - `hay`：原始字节序列
-/// - `needle`：目标子字节片段
+/// - `sites`：(模式序号, 起始位置, 结束位置)，模式序号为模式在构造时的顺序
+/// - `overlap`：重叠命中的取舍策略
 ///
 /// ## 返回
-/// 子片段在字节序列中的第一个出现位置
-fn find_subslice_left(hay: &[u8], needle: &[u8]) -> Option<usize> {
-  if needle.is_empty() {
-    return Some(0);
+/// 互不重叠、按起始位置升序排列的替换范围
+fn wm_pick(mut sites: Vec<(usize, usize, usize)>, overlap: WmOverlap) -> Vec<(usize, usize, usize)> {
+  sites.retain(|&(_, start, finish)| start < finish);
+  match overlap {
+    WmOverlap::LeftmostFirst => {
+      sites.sort_by_key(|&(pat_ord, start, finish)| (start, pat_ord, Reverse(finish)));
+    }
+    WmOverlap::LeftmostLongest | WmOverlap::All => {
+      sites.sort_by_key(|&(pat_ord, start, finish)| (start, Reverse(finish), pat_ord));
+    }
   }
-  if needle.len() > hay.len() {
-    return None;
+
+  let mut picks: Vec<(usize, usize, usize)> = Vec::with_capacity(sites.len());
+  for site in sites {
+    match picks.last_mut() {
+      Some(pick) if site.1 < pick.2 => {
+        if overlap == WmOverlap::All {
+          pick.2 = pick.2.max(site.2);
+        }
+      }
+      _ => picks.push(site),
+    }
+  }
+  picks
+}
+
+
+/// 按选出的替换范围拼接替换后的文本
+fn wm_replace<T: WmSym, F>(text: &[T], pats: &[Vec<T>], results: HashMap<Vec<T>, Vec<(usize, usize)>>,
+                           overlap: WmOverlap, mut repl: F) -> Vec<T>
+where F: FnMut(&[T], &[T]) -> Vec<T> {
+  let sites: Vec<(usize, usize, usize)> = results.into_iter().
+    flat_map(|(pat, pat_sites)| {
+      // 重复的模式以第一次出现的位置为序
+      let pat_ord = pats.iter().position(|pati| *pati == pat).unwrap_or(pats.len());
+      pat_sites.into_iter().map(move |(start, finish)| (pat_ord, start, finish))
+    }).
+    collect();
+
+  let mut replaced: Vec<T> = Vec::with_capacity(text.len());
+  let mut site = 0;
+  for (pat_ord, start, finish) in wm_pick(sites, overlap) {
+    replaced.extend_from_slice(&text[site..start]);
+    replaced.extend(repl(&pats[pat_ord], &text[start..finish]));
+    site = finish;
   }
-  hay.windows(needle.len()).position(|hay_window| hay_window == needle)
+  replaced.extend_from_slice(&text[site..]);
+  replaced
+}
+
+
+/// 一次命中在原文中的位置，均为左闭右开
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WmSite {
+  // 起始字节偏移
+  pub byte_start: usize,
+  // 结束字节偏移
+  pub byte_finish: usize,
+  // 起始字符偏移
+  pub char_start: usize,
+  // 结束字符偏移
+  pub char_finish: usize,
 }
 
 
-/// 在字节序列中从右查找目标片段的第一个出现位置
+/// 以字符为单位匹配的DHSWuManber，用于中文等多字节的UTF-8文本。
+///
+/// 按字节匹配时，默认2字节的块会切开3字节的汉字，移位表中多是半个汉字拼成的无用块；
+/// 按字符匹配时块总由完整的字符组成，命中位置也总在字符边界上。
+/// 可选在匹配前规范化模式与原文，命中位置总是换算回未规范化的原文。
+#[derive(Debug)]
+pub struct CharWuManber {
+  base: DHSWuManber<char>,
+  fold: WmFold,
+  // 去重后的原模式，按构造时的顺序排列
+  pats: Vec<String>,
+  // 规范化后的模式对应的原模式在pats中的序号，不同的原模式可能规范化为同一个模式
+  pat_origs: HashMap<Vec<char>, Vec<usize>>,
+}
+
+
+impl CharWuManber {
+  /// ## 参数
+  /// - `pats`：模式
+  /// - `blk_size`：块的字符数，默认为最短模式的字符数与2中较小的一个
+  #[allow(dead_code)]
+  pub fn new<P: AsRef<str>>(pats: &[P], blk_size: Option<usize>) -> Result<Self, WmError> {
+    Self::new_fold(pats, blk_size, WmFold::default())
+  }
+
+  /// ## 参数
+  /// - `pats`：模式
+  /// - `blk_size`：规范化后块的字符数，默认为规范化后最短模式的字符数与2中较小的一个
+  /// - `fold`：模式与原文的规范化选项
+  pub fn new_fold<P: AsRef<str>>(pats: &[P], blk_size: Option<usize>,
+                                 fold: WmFold) -> Result<Self, WmError> {
+    let mut pat_arrs: Vec<String> = vec![];
+    let mut pat_origs: HashMap<Vec<char>, Vec<usize>> = HashMap::new();
+    for (pat_idx, pati) in pats.iter().enumerate() {
+      if pati.as_ref().is_empty() {
+        return Err(WmError::EmptyPat(pat_idx));
+      }
+      let pat_folded = fold.fold_text(pati.as_ref()).chars;
+      if pat_folded.is_empty() {
+        return Err(WmError::FoldedEmpty(pati.as_ref().to_string()));
+      }
+      if !pat_arrs.iter().any(|pat_orig| pat_orig == pati.as_ref()) {
+        pat_origs.entry(pat_folded).or_default().push(pat_arrs.len());
+        pat_arrs.push(pati.as_ref().to_string());
+      }
+    }
+
+    let pats_folded: Vec<&Vec<char>> = pat_origs.keys().collect();
+    let base = DHSWuManber::new(&pats_folded, blk_size)?;
+    Ok(CharWuManber { base, fold, pats: pat_arrs, pat_origs })
+  }
+
+  /// ## 参数
+  /// - `text`：原文
+  ///
+  /// ## 返回
+  /// 每个原模式的命中位置，按起始位置升序排列，同时给出原文中的字节偏移与字符偏移
+  pub fn search(&self, text: &str) -> HashMap<String, Vec<WmSite>> {
+    let text_folded = self.fold.fold_text(text);
+    let mut char_bytes: Vec<usize> = text.char_indices().map(|(byte_idx, _)| byte_idx).collect();
+    // 末尾补上原文的字节数，结束字符偏移也能换算为字节偏移
+    char_bytes.push(text.len());
+
+    let mut results: HashMap<String, Vec<WmSite>> = HashMap::new();
+    for (pat_folded, fold_sites) in self.base.search(&text_folded.chars) {
+      let mut pat_sites: Vec<WmSite> = fold_sites.into_iter().
+        map(|(fold_start, fold_finish)| {
+          // 命中范围覆盖第一个与最后一个命中字符在原文中的位置，包括其间删除的字符
+          let char_start = text_folded.char_srcs[fold_start];
+          let char_finish = text_folded.char_srcs[fold_finish - 1] + 1;
+          WmSite {
+            byte_start: char_bytes[char_start],
+            byte_finish: char_bytes[char_finish],
+            char_start,
+            char_finish,
+          }
+        }).
+        collect();
+      // 大小写折叠展开的字符内可能有多个命中对应原文中的同一位置
+      pat_sites.dedup();
+      for &pat_ord in &self.pat_origs[&pat_folded] {
+        results.insert(self.pats[pat_ord].clone(), pat_sites.clone());
+      }
+    }
+    results
+  }
+
+  /// 按重叠策略替换原文中的命中。规范化时命中范围覆盖第一个与最后一个命中字符之间的原文，
+  /// 其间删除的字符一并替换
+  ///
+  /// ## 参数
+  /// - `text`：原文
+  /// - `overlap`：重叠命中的取舍策略
+  /// - `repl`：由命中的原模式与被替换的原文片段得到替换内容
+  ///
+  /// ## 返回
+  /// 替换后的文本
+  pub fn replace<F>(&self, text: &str, overlap: WmOverlap, mut repl: F) -> String
+  where F: FnMut(&str, &str) -> String {
+    let pat_ords: HashMap<&str, usize> = self.pats.iter().enumerate().
+      map(|(pat_ord, pat_orig)| (pat_orig.as_str(), pat_ord)).
+      collect();
+    let sites: Vec<(usize, usize, usize)> = self.search(text).into_iter().
+      flat_map(|(pat_orig, pat_sites)| {
+        let pat_ord = pat_ords[pat_orig.as_str()];
+        pat_sites.into_iter().
+          map(move |pat_site| (pat_ord, pat_site.byte_start, pat_site.byte_finish))
+      }).
+      collect();
+
+    let mut replaced = String::with_capacity(text.len());
+    let mut site = 0;
+    for (pat_ord, start, finish) in wm_pick(sites, overlap) {
+      replaced.push_str(&text[site..start]);
+      replaced.push_str(&repl(&self.pats[pat_ord], &text[start..finish]));
+      site = finish;
+    }
+    replaced.push_str(&text[site..]);
+    replaced
+  }
+
+  /// 按重叠策略将原文中命中的字符逐个替换为遮盖字符，替换后字符数不变
+  #[allow(dead_code)]
+  pub fn mask(&self, text: &str, overlap: WmOverlap, mask_char: char) -> String {
+    self.replace(text, overlap, |_, span| span.chars().map(|_| mask_char).collect())
+  }
+}
+
+
+/// 在序列中从右查找目标片段的第一个出现位置
 ///
 /// ## 参数
-/// - `hay`：原始字节序列
-/// - `needle`：目标子字节片段
+/// - `hay`：原始序列
+/// - `needle`：目标子片段
 ///
 /// ## 返回
-/// 子片段在字节序列中的右侧第一个出现位置
-fn find_subslice_right(hay: &[u8], needle: &[u8]) -> Option<usize> {
+/// 子片段在序列中的右侧第一个出现位置
+fn find_subslice_right<T: PartialEq>(hay: &[T], needle: &[T]) -> Option<usize> {
   if needle.is_empty() {
     return Some(hay.len());
   }
@@ -249,48 +545,353 @@ fn find_subslice_right(hay: &[u8], needle: &[u8]) -> Option<usize> {
 }
 
 
-/// Helper: 将结果打印为更友好的 UTF-8（若无法解码则打印 bytes）
-fn print_matches(res: &HashMap<Vec<u8>, Vec<(usize, usize)>>, text: &[u8]) {
-  for (pat, vecpos) in res {
-    match String::from_utf8(pat.clone()) {
-      Ok(s) => {
-        println!("pattern {:?} => occurrences: {}", s, vecpos.len());
+#[cfg(test)]
+mod tests {
+  use proptest::prelude::*;
+  use super::*;
+
+  /// 中文关键词语料，包含单字、互相重叠、互为前后缀、中英混合与4字节字符的关键词
+  const CORPUS_KEYWORDS: [&str; 16] = [
+    "热点", "今日热点", "点", "新闻", "新闻联播", "联播", "大新闻", "微博热搜", "热搜", "搜",
+    "张三", "iPhone发布会", "发布会", "😂笑死", "笑死我了", "热热热",
+  ];
+
+  /// 语料中的原文
+  const CORPUS_TEXTS: [&str; 6] = [
+    "#今日热点# 新闻联播报道了大新闻，微博热搜第一",
+    "回复@张三: 今天的iPhone发布会😂笑死我了，热热热热热",
+    "点点点点点，热点热点热点！",
+    "no chinese at all",
+    "",
+    "搜",
+  ];
+
+  /// 按字符逐个位置比较的朴素多模式匹配
+  fn naive_search(pats: &[&str], text: &str) -> HashMap<String, Vec<WmSite>> {
+    let text_sites: Vec<(usize, char)> = text.char_indices().collect();
+    let mut results: HashMap<String, Vec<WmSite>> = HashMap::new();
+    for pati in pats {
+      let pat_sites = results.entry(pati.to_string()).or_default();
+      for (char_start, &(byte_start, _)) in text_sites.iter().enumerate() {
+        if text[byte_start..].starts_with(pati) {
+          pat_sites.push(WmSite {
+            byte_start,
+            byte_finish: byte_start + pati.len(),
+            char_start,
+            char_finish: char_start + pati.chars().count(),
+          });
+        }
       }
-      Err(_) => {
-        println!("pattern {:?} (bytes) => occurrences: {}", pat, vecpos.len());
+    }
+    results
+  }
+
+  /// 按字节逐个位置比较的朴素多模式匹配
+  fn naive_byte_search(pats: &[Vec<u8>], text: &[u8]) -> HashMap<Vec<u8>, Vec<(usize, usize)>> {
+    pats.iter().map(|pati| {
+      let pat_sites = (0..text.len()).
+        filter(|&start| text[start..].starts_with(pati)).
+        map(|start| (start, start + pati.len())).
+        collect();
+      (pati.clone(), pat_sites)
+    }).collect()
+  }
+
+  /// 随机的模式集合、原文与块大小。字母表较小时命中与重叠更多
+  fn byte_cases(sym_max: u8) -> impl Strategy<Value = (Vec<Vec<u8>>, Vec<u8>, Option<usize>)> {
+    (prop::collection::vec(prop::collection::vec(0..=sym_max, 1..12), 1..8),
+     prop::collection::vec(0..=sym_max, 0..96),
+     prop::option::of(1usize..4)).
+      prop_map(|(pats, text, blk_size)| {
+        // 块的大小不超过最短模式的长度
+        let pat_min = pats.iter().map(Vec::len).min().unwrap();
+        (pats, text, blk_size.map(|blk_size| blk_size.min(pat_min)))
+      })
+  }
+
+  /// 语料关键词中的全部字符，加上标点、空格与常用字
+  fn corpus_chars() -> Vec<char> {
+    CORPUS_KEYWORDS.concat().chars().
+      chain("，。！ 的了是a".chars()).
+      collect()
+  }
+
+  /// 固定种子的xorshift，从给定的字符中生成可复现的随机文本
+  ///
+  /// ## 参数
+  /// - `seed`：随机种子，每次生成后更新
+  /// - `text_len`：文本的字符数
+  /// - `text_chars`：可用的字符
+  fn corpus_text(seed: &mut u64, text_len: usize, text_chars: &[char]) -> String {
+    (0..text_len).map(|_| {
+      *seed ^= *seed << 13;
+      *seed ^= *seed >> 7;
+      *seed ^= *seed << 17;
+      text_chars[(*seed % text_chars.len() as u64) as usize]
+    }).collect()
+  }
+
+  #[test]
+  fn wu_manber_finds_textbook_example() {
+    let pats = ["still", "trill", "study", "basic", "stability"];
+    let text = b"this chapter will introduce the basic concepts about stability and study";
+    let expected: HashMap<Vec<u8>, Vec<(usize, usize)>> = HashMap::from([
+      (b"still".to_vec(), vec![]),
+      (b"trill".to_vec(), vec![]),
+      (b"study".to_vec(), vec![(67, 72)]),
+      (b"basic".to_vec(), vec![(32, 37)]),
+      (b"stability".to_vec(), vec![(53, 62)]),
+    ]);
+    assert_eq!(WuManber::new(&pats, None).unwrap().search(text), expected);
+    assert_eq!(DHSWuManber::new(&pats, None).unwrap().search(text), expected);
+  }
+
+  #[test]
+  fn wu_manber_repeated_block_regression() {
+    // 块在模式中多次出现时，移位取最靠右的出现位置；命中后的slip不越过可能的下一个命中
+    let wm = WuManber::new(&["abab"], Some(2)).unwrap();
+    let dhs_wm = DHSWuManber::new(&["abab"], Some(2)).unwrap();
+    assert_eq!(wm.search(b"xabab")[&b"abab".to_vec()], vec![(1, 5)]);
+    assert_eq!(dhs_wm.search(b"xabab")[&b"abab".to_vec()], vec![(1, 5)]);
+    assert_eq!(wm.search(b"xababab")[&b"abab".to_vec()], vec![(1, 5), (3, 7)]);
+    assert_eq!(dhs_wm.search(b"xababab")[&b"abab".to_vec()], vec![(1, 5), (3, 7)]);
+  }
+
+  #[test]
+  fn wu_manber_rejects_invalid_input() {
+    let no_pats: [&[u8]; 0] = [];
+    assert_eq!(WuManber::new(&no_pats, None).unwrap_err(), WmError::EmptyPats);
+    assert_eq!(DHSWuManber::new(&["ab", ""], None).unwrap_err(), WmError::EmptyPat(1));
+    assert_eq!(WuManber::new(&["abc"], Some(0)).unwrap_err(),
+               WmError::BlkSize { blk_size: 0, pat_min: 3 });
+    assert_eq!(DHSWuManber::new(&["abc", "de"], Some(3)).unwrap_err(),
+               WmError::BlkSize { blk_size: 3, pat_min: 2 });
+    let no_keywords: [&str; 0] = [];
+    assert_eq!(CharWuManber::new(&no_keywords, None).unwrap_err(), WmError::EmptyPats);
+    assert_eq!(CharWuManber::new(&["热点", ""], None).unwrap_err(), WmError::EmptyPat(1));
+
+    // 原文短于模式或块时没有命中
+    let dhs_wm = DHSWuManber::new(&["abcdef", "xyz"], Some(3)).unwrap();
+    for text in [&b""[..], b"a", b"ab", b"xy"] {
+      assert!(dhs_wm.search(text).values().all(Vec::is_empty));
+      assert_eq!(dhs_wm.mask(text, WmOverlap::All, b'*'), text);
+    }
+    // 重复的模式只报告一次
+    let dup_wm = WuManber::new(&["ab", "ab", "b"], None).unwrap();
+    assert_eq!(dup_wm.search(b"abab")[&b"ab".to_vec()], vec![(0, 2), (2, 4)]);
+  }
+
+  proptest! {
+    #[test]
+    fn wu_manber_equals_naive_on_small_alphabet((pats, text, blk_size) in byte_cases(3)) {
+      let expected = naive_byte_search(&pats, &text);
+      prop_assert_eq!(WuManber::new(&pats, blk_size).unwrap().search(&text), expected.clone());
+      prop_assert_eq!(DHSWuManber::new(&pats, blk_size).unwrap().search(&text), expected);
+    }
+
+    #[test]
+    fn wu_manber_equals_naive_on_any_bytes((pats, text, blk_size) in byte_cases(u8::MAX)) {
+      let expected = naive_byte_search(&pats, &text);
+      prop_assert_eq!(WuManber::new(&pats, blk_size).unwrap().search(&text), expected.clone());
+      prop_assert_eq!(DHSWuManber::new(&pats, blk_size).unwrap().search(&text), expected);
+    }
+
+    #[test]
+    fn wu_manber_mask_all_covers_naive_hits((pats, text, blk_size) in byte_cases(3)) {
+      let mut masked = text.clone();
+      for pat_sites in naive_byte_search(&pats, &text).values() {
+        for &(start, finish) in pat_sites {
+          masked[start..finish].fill(u8::MAX);
+        }
       }
+      let dhs_wm = DHSWuManber::new(&pats, blk_size).unwrap();
+      prop_assert_eq!(dhs_wm.mask(&text, WmOverlap::All, u8::MAX), masked);
+    }
+  }
+
+  #[test]
+  fn char_wu_manber_equals_naive_on_chinese_corpus() {
+    let char_wm = CharWuManber::new(&CORPUS_KEYWORDS, None).unwrap();
+    for text in CORPUS_TEXTS {
+      assert_eq!(char_wm.search(text), naive_search(&CORPUS_KEYWORDS, text), "{text}");
+    }
+
+    let corpus_chars = corpus_chars();
+    let mut seed = 0x5eed_u64;
+    for text_len in 0..400 {
+      let text = corpus_text(&mut seed, text_len % 80, &corpus_chars);
+      assert_eq!(char_wm.search(&text), naive_search(&CORPUS_KEYWORDS, &text), "{text}");
     }
-    for (s, e) in vecpos {
-      match std::str::from_utf8(&text[*s..*e]) {
-        Ok(snippet) => println!("  at {}..{} => {:?}", s, e, snippet),
-        Err(_) => println!("  at {}..{} => bytes{:?}", s, e, &text[*s..*e]),
+  }
+
+  #[test]
+  fn char_wu_manber_equals_naive_with_block_sizes() {
+    // 每组关键词的最短字符数不小于块的字符数
+    let corpus_chars = corpus_chars();
+    let mut seed = 0xb10c_u64;
+    for (pats, blk_size) in [(vec!["今日热点", "新闻联播", "微博热搜"], 3),
+                             (vec!["热点", "新闻", "张三", "热搜"], 2),
+                             (vec!["点", "搜", "热"], 1),
+                             // 块在模式中重复出现
+                             (vec!["热热热", "点点点点", "热点热点"], 2)] {
+      let char_wm = CharWuManber::new(&pats, Some(blk_size)).unwrap();
+      for text_len in 0..200 {
+        let text = corpus_text(&mut seed, text_len % 60, &corpus_chars);
+        assert_eq!(char_wm.search(&text), naive_search(&pats, &text), "{text}");
       }
     }
   }
-}
 
+  #[test]
+  fn char_wu_manber_fold_maps_to_original() {
+    let wm_fold = WmFold { case: true, width: true, trad: true, strip: true };
+    let char_wm = CharWuManber::new_fold(&["微博热搜", "iPhone", "IPHONE"], None, wm_fold).
+      unwrap();
+    let text = "看 微\u{200B}博·熱 搜，ｉＰＨＯＮＥ发布";
+    let results = char_wm.search(text);
+
+    let weibo_sites = &results["微博热搜"];
+    assert_eq!(weibo_sites.len(), 1);
+    assert_eq!(&text[weibo_sites[0].byte_start..weibo_sites[0].byte_finish], "微\u{200B}博·熱 搜");
+    assert_eq!((weibo_sites[0].char_start, weibo_sites[0].char_finish), (2, 9));
+    // 规范化为同一模式的原模式都有命中
+    for pat in ["iPhone", "IPHONE"] {
+      let iphone_site = results[pat][0];
+      assert_eq!(&text[iphone_site.byte_start..iphone_site.byte_finish], "ｉＰＨＯＮＥ");
+    }
 
-pub fn test_wu_manber() {
-  let patterns = ["still", "trill", "study", "basic", "stability"];
-  let patterns_bytes: Vec<&[u8]> = patterns.iter().map(|s| s.as_bytes()).collect();
-  let text = b"this chapter will introduce the basic concepts about stability and study";
+    // 不规范化时按原样匹配
+    assert!(CharWuManber::new(&["微博热搜"], None).unwrap().search(text)["微博热搜"].is_empty());
+    assert!(CharWuManber::new_fold(&[" · "], None, wm_fold).is_err());
+  }
+
+  #[test]
+  fn char_wu_manber_fold_equals_naive_on_folded_corpus() {
+    let wm_fold = WmFold { case: true, width: true, trad: true, strip: true };
+    let fold_keywords = ["熱點", "新聞聯播", "ＩＰＨＯＮＥ發布會", "微博 熱搜", "张三", "a"];
+    let char_wm = CharWuManber::new_fold(&fold_keywords, None, wm_fold).unwrap();
+    let fold_chars: Vec<char> = "熱点点新闻聞聯联播ＩiPHoneＥ發发布會会微博搜张三a \u{200B}·".
+      chars().
+      collect();
+
+    let mut seed = 0xf01d_u64;
+    for text_len in 0..300 {
+      let text = corpus_text(&mut seed, text_len % 50, &fold_chars);
+      let text_folded = wm_fold.fold_text(&text);
+      let char_bytes: Vec<usize> = text.char_indices().map(|(byte_idx, _)| byte_idx).
+        chain([text.len()]).
+        collect();
+
+      let results = char_wm.search(&text);
+      for keyword in fold_keywords {
+        // 在规范化后的文本中朴素地查找规范化后的关键词，再换算回原文
+        let keyword_folded = wm_fold.fold_text(keyword).chars;
+        let naive_sites: Vec<WmSite> = (0..text_folded.chars.len()).
+          filter(|&fold_start| text_folded.chars[fold_start..].starts_with(&keyword_folded)).
+          map(|fold_start| {
+            let char_start = text_folded.char_srcs[fold_start];
+            let char_finish = text_folded.char_srcs[fold_start + keyword_folded.len() - 1] + 1;
+            WmSite {
+              byte_start: char_bytes[char_start],
+              byte_finish: char_bytes[char_finish],
+              char_start,
+              char_finish,
+            }
+          }).
+          collect();
+        assert_eq!(results[keyword], naive_sites, "{keyword} {text}");
+      }
+    }
+  }
 
-  let wm = WuManber::new(&patterns_bytes, None).unwrap();
-  let res_wm = wm.search(text);
-  println!("WM matches:");
-  print_matches(&res_wm, text);
+  #[test]
+  fn char_wu_manber_sites_on_char_boundaries() {
+    let char_wm = CharWuManber::new(&CORPUS_KEYWORDS, None).unwrap();
+    let byte_wm = DHSWuManber::new(&CORPUS_KEYWORDS, None).unwrap();
+    let corpus_chars = corpus_chars();
+    let mut seed = 0xc4a7_u64;
+    for text_len in 0..200 {
+      let text = corpus_text(&mut seed, text_len % 60, &corpus_chars);
+      let byte_results = byte_wm.search(text.as_bytes());
+      for (pat, pat_sites) in char_wm.search(&text) {
+        for pat_site in &pat_sites {
+          assert_eq!(&text[pat_site.byte_start..pat_site.byte_finish], pat);
+          let char_text: String = text.chars().
+            skip(pat_site.char_start).
+            take(pat_site.char_finish - pat_site.char_start).
+            collect();
+          assert_eq!(char_text, pat);
+        }
+        // 按字节匹配的结果与按字符匹配的字节偏移一致
+        let byte_sites: Vec<(usize, usize)> = pat_sites.iter().
+          map(|pat_site| (pat_site.byte_start, pat_site.byte_finish)).
+          collect();
+        assert_eq!(byte_results[pat.as_bytes()], byte_sites, "{text}");
+      }
+    }
+  }
 
-  println!("SHIFT: {:?}", wm.shift);
-  println!("HASH_PREFIX: {:?}", wm.hash_prefix);
-  println!("==============================");
+  #[test]
+  fn wu_manber_replace_by_overlap() {
+    let pats = ["ab", "abcd", "bc", "cde"];
+    let wm = WuManber::new(&pats, None).unwrap();
+    let dhs_wm = DHSWuManber::new(&pats, None).unwrap();
+    let text = b"xabcdex abz";
+    let bracket = |pat: &[u8], _: &[u8]| [b"[", pat, b"]"].concat();
+    for (overlap, replaced) in [(WmOverlap::LeftmostLongest, "x[abcd]ex [ab]z"),
+                                (WmOverlap::LeftmostFirst, "x[ab][cde]x [ab]z"),
+                                (WmOverlap::All, "x[abcd]x [ab]z")] {
+      assert_eq!(wm.replace(text, overlap, bracket), replaced.as_bytes(), "{overlap:?}");
+      assert_eq!(dhs_wm.replace(text, overlap, bracket), replaced.as_bytes(), "{overlap:?}");
+    }
+    assert_eq!(dhs_wm.mask(text, WmOverlap::LeftmostFirst, b'*'), b"x*****x **z");
+    assert_eq!(dhs_wm.mask(text, WmOverlap::All, b'*'), b"x*****x **z");
+    assert_eq!(dhs_wm.mask(b"nothing", WmOverlap::All, b'*'), b"nothing");
+  }
 
-  let dhs = DHSWuManber::new(&patterns_bytes, None).unwrap();
-  let res_dhs = dhs.search(text);
-  println!("DHSWM matches:");
-  print_matches(&res_dhs, text);
+  #[test]
+  fn char_wu_manber_replace_per_pattern() {
+    let wm_fold = WmFold { trad: true, ..WmFold::default() };
+    let char_wm = CharWuManber::new_fold(&["热点", "热点新闻", "新闻"], None, wm_fold).unwrap();
+    let pat_repls: HashMap<&str, &str> =
+      HashMap::from([("热点", "[热]"), ("热点新闻", "[热闻]"), ("新闻", "[闻]")]);
+    let text = "今日熱點新聞，热点";
+    for (overlap, replaced) in [(WmOverlap::LeftmostLongest, "今日[热闻]，[热]"),
+                                (WmOverlap::LeftmostFirst, "今日[热][闻]，[热]"),
+                                (WmOverlap::All, "今日[热闻]，[热]")] {
+      assert_eq!(char_wm.replace(text, overlap, |pat, _| pat_repls[pat].to_string()), replaced);
+    }
+    // 替换的是原文中的片段，而不是规范化后的片段
+    assert_eq!(char_wm.replace(text, WmOverlap::All, |_, span| format!("<{span}>")),
+               "今日<熱點新聞>，<热点>");
+    assert_eq!(char_wm.mask(text, WmOverlap::LeftmostFirst, '*'), "今日****，**");
+  }
 
-  println!("SHIFT: {:?}", dhs.base.shift);
-  println!("HASH_PREFIX: {:?}", dhs.base.hash_prefix);
-  println!("SLIP: {:?}", dhs.slip);
-  println!("==============================");
+  #[test]
+  fn char_wu_manber_mask_all_covers_every_hit() {
+    let char_wm = CharWuManber::new(&CORPUS_KEYWORDS, None).unwrap();
+    let corpus_chars = corpus_chars();
+    let mut seed = 0x3a5c_u64;
+    for text_len in 0..300 {
+      let text = corpus_text(&mut seed, text_len % 60, &corpus_chars);
+      let mut char_masks = vec![false; text.chars().count()];
+      for pat_sites in naive_search(&CORPUS_KEYWORDS, &text).values() {
+        for pat_site in pat_sites {
+          char_masks[pat_site.char_start..pat_site.char_finish].fill(true);
+        }
+      }
+      let masked: String = text.chars().zip(&char_masks).
+        map(|(text_char, &char_mask)| if char_mask { '*' } else { text_char }).
+        collect();
+      assert_eq!(char_wm.mask(&text, WmOverlap::All, '*'), masked, "{text}");
+
+      // 按最靠左策略只替换部分命中，不命中的字符保持原样
+      let longest = char_wm.mask(&text, WmOverlap::LeftmostLongest, '*');
+      assert_eq!(longest.chars().count(), char_masks.len());
+      for ((longest_char, text_char), &char_mask) in longest.chars().zip(text.chars()).
+        zip(&char_masks) {
+        assert!(longest_char == text_char || (char_mask && longest_char == '*'), "{text}");
+      }
+    }
+  }
 }